@@ -3,7 +3,7 @@
 use crate::transport::{DiscordTransport, make_shard};
 use crate::types::{DiscordError, DiscordIntent, DiscordSnapshot};
 use interconnect_client::Connection;
-use interconnect_core::{Identity, Manifest};
+use interconnect_core::{DeltaEncoder, Identity, Manifest};
 use std::collections::VecDeque;
 use std::sync::Arc;
 use twilight_http::Client;
@@ -50,19 +50,19 @@ pub async fn connect(
         channel_name: channel_name.clone(),
         messages: VecDeque::new(),
         seq: 0,
+        deltas: DeltaEncoder::new(),
     };
 
     let initial_snapshot = transport.fetch_initial_snapshot().await?;
 
-    let manifest = Manifest {
-        identity: Identity::local(format!("discord:{channel_id}")),
-        name: channel_name,
-        substrate: None,
-        metadata: serde_json::json!({
-            "type": "discord",
-            "channel_id": channel_id.to_string(),
-        }),
-    };
+    let manifest = Manifest::new(
+        Identity::local(format!("discord:{channel_id}")),
+        channel_name,
+    )
+    .metadata(serde_json::json!({
+        "type": "discord",
+        "channel_id": channel_id.to_string(),
+    }));
 
     let conn = DiscordConnection::established(transport, manifest);
     Ok((conn, initial_snapshot))
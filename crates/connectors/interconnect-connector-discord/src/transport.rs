@@ -5,7 +5,7 @@
 //! bytes become HTTP API calls.
 
 use crate::types::{DiscordError, DiscordIntent, DiscordMessage, DiscordSnapshot};
-use interconnect_core::{ClientWire, ServerWire, Transport};
+use interconnect_core::{ClientWire, DeltaEncoder, Transport};
 use std::collections::VecDeque;
 use std::sync::Arc;
 use twilight_gateway::{EventTypeFlags, Intents, Shard, ShardId};
@@ -22,6 +22,7 @@ pub struct DiscordTransport {
     pub(crate) channel_name: String,
     pub(crate) messages: VecDeque<DiscordMessage>,
     pub(crate) seq: u64,
+    pub(crate) deltas: DeltaEncoder,
 }
 
 impl DiscordTransport {
@@ -72,6 +73,10 @@ impl Transport for DiscordTransport {
 
     async fn send(&mut self, data: &[u8]) -> Result<(), Self::Error> {
         let wire: ClientWire<DiscordIntent> = serde_json::from_slice(data)?;
+        if let ClientWire::Ack { seq } = wire {
+            self.deltas.ack(seq);
+            return Ok(());
+        }
         match wire {
             ClientWire::Intent(DiscordIntent::SendMessage { content }) => {
                 self.http
@@ -115,10 +120,7 @@ impl Transport for DiscordTransport {
                     });
 
                     let snapshot = self.current_snapshot();
-                    let wire = ServerWire::<DiscordSnapshot>::Snapshot {
-                        seq: self.seq,
-                        data: snapshot,
-                    };
+                    let wire = self.deltas.encode(self.seq, snapshot)?;
                     self.seq += 1;
                     return Ok(Some(serde_json::to_vec(&wire)?));
                 }
@@ -41,12 +41,11 @@ pub async fn connect(root: impl AsRef<Path>) -> Result<(FsConnection, FsSnapshot
         .map(|n| n.to_string_lossy().into_owned())
         .unwrap_or_else(|| root.to_string_lossy().into_owned());
 
-    let manifest = Manifest {
-        identity: Identity::local(format!("fs:{}", root.to_string_lossy())),
+    let manifest = Manifest::new(
+        Identity::local(format!("fs:{}", root.to_string_lossy())),
         name,
-        substrate: None,
-        metadata: serde_json::json!({ "type": "fs", "root": root }),
-    };
+    )
+    .metadata(serde_json::json!({ "type": "fs", "root": root }));
 
     let conn = FsConnection::established(transport, manifest);
     Ok((conn, initial_snapshot))
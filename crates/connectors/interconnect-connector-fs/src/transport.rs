@@ -1,7 +1,7 @@
 //! Filesystem transport — watches a directory and presents it as a room.
 
 use crate::types::{FsError, FsFile, FsIntent, FsSnapshot};
-use interconnect_core::{ClientWire, ServerWire, Transport};
+use interconnect_core::{ClientWire, DeltaEncoder, Transport};
 use notify::{RecommendedWatcher, RecursiveMode, Watcher};
 use std::path::{Path, PathBuf};
 use tokio::sync::mpsc;
@@ -17,6 +17,7 @@ pub struct FsTransport {
     /// Kept alive to maintain the watch; dropped when the transport is dropped.
     _watcher: RecommendedWatcher,
     seq: u64,
+    deltas: DeltaEncoder,
 }
 
 impl FsTransport {
@@ -33,7 +34,7 @@ impl FsTransport {
 
         watcher.watch(&root, RecursiveMode::Recursive)?;
 
-        Ok(Self { root, event_rx, _watcher: watcher, seq: 0 })
+        Ok(Self { root, event_rx, _watcher: watcher, seq: 0, deltas: DeltaEncoder::new() })
     }
 
     /// Read the full directory snapshot (sync, runs in spawn_blocking).
@@ -107,6 +108,10 @@ impl Transport for FsTransport {
 
     async fn send(&mut self, data: &[u8]) -> Result<(), Self::Error> {
         let wire: ClientWire<FsIntent> = serde_json::from_slice(data)?;
+        if let ClientWire::Ack { seq } = wire {
+            self.deltas.ack(seq);
+            return Ok(());
+        }
         match wire {
             ClientWire::Intent(FsIntent::WriteFile { path, content }) => {
                 let full = self.root.join(&path);
@@ -137,10 +142,7 @@ impl Transport for FsTransport {
                     }
 
                     let snapshot = self.read_snapshot().await?;
-                    let wire = ServerWire::<FsSnapshot>::Snapshot {
-                        seq: self.seq,
-                        data: snapshot,
-                    };
+                    let wire = self.deltas.encode(self.seq, snapshot)?;
                     self.seq += 1;
                     return Ok(Some(serde_json::to_vec(&wire)?));
                 }
@@ -1,7 +1,7 @@
 //! High-level connector entry point.
 
 use interconnect_client::Connection;
-use interconnect_core::{Identity, Manifest};
+use interconnect_core::{DeltaEncoder, Identity, Manifest};
 use reqwest::Client;
 
 use crate::transport::GithubTransport;
@@ -67,19 +67,19 @@ pub async fn connect(
         last_updated_at,
         comments: comments.clone(),
         seq: 0,
+        deltas: DeltaEncoder::new(),
     };
 
-    let manifest = Manifest {
-        identity: Identity::local(format!("github:{owner}/{repo}#{issue_number}")),
-        name: title.clone(),
-        substrate: None,
-        metadata: serde_json::json!({
-            "type": "github_issue",
-            "owner": owner,
-            "repo": repo,
-            "issue_number": issue_number,
-        }),
-    };
+    let manifest = Manifest::new(
+        Identity::local(format!("github:{owner}/{repo}#{issue_number}")),
+        title.clone(),
+    )
+    .metadata(serde_json::json!({
+        "type": "github_issue",
+        "owner": owner,
+        "repo": repo,
+        "issue_number": issue_number,
+    }));
 
     let initial_snapshot = GithubSnapshot {
         owner,
@@ -145,6 +145,7 @@ async fn fetch_initial_comments(
         last_updated_at: String::new(),
         comments: Vec::new(),
         seq: 0,
+        deltas: DeltaEncoder::new(),
     };
     transport.fetch_comments().await
 }
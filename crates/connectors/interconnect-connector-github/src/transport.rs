@@ -8,7 +8,7 @@
 use std::collections::HashMap;
 use std::time::Duration;
 
-use interconnect_core::{ClientWire, ServerWire, Transport};
+use interconnect_core::{ClientWire, DeltaEncoder, Transport};
 use reqwest::Client;
 use serde::Deserialize;
 use tokio::time::sleep;
@@ -30,6 +30,7 @@ pub struct GithubTransport {
     pub(crate) last_updated_at: String,
     pub(crate) comments: Vec<GithubComment>,
     pub(crate) seq: u64,
+    pub(crate) deltas: DeltaEncoder,
 }
 
 /// Partial GitHub comment as returned by the REST API.
@@ -179,6 +180,10 @@ impl Transport for GithubTransport {
 
     async fn send(&mut self, data: &[u8]) -> Result<(), Self::Error> {
         let wire: ClientWire<GithubIntent> = serde_json::from_slice(data)?;
+        if let ClientWire::Ack { seq } = wire {
+            self.deltas.ack(seq);
+            return Ok(());
+        }
         if let ClientWire::Intent(intent) = wire {
             match intent {
                 GithubIntent::AddComment { body } => {
@@ -266,10 +271,7 @@ impl Transport for GithubTransport {
                 self.last_updated_at = new_cursor;
 
                 let snapshot = self.current_snapshot();
-                let wire = ServerWire::<GithubSnapshot>::Snapshot {
-                    seq: self.seq,
-                    data: snapshot,
-                };
+                let wire = self.deltas.encode(self.seq, snapshot)?;
                 self.seq += 1;
                 return Ok(Some(serde_json::to_vec(&wire)?));
             }
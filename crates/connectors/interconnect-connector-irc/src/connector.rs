@@ -3,7 +3,7 @@
 use crate::transport::IrcTransport;
 use crate::types::{IrcError, IrcIntent, IrcSnapshot};
 use interconnect_client::Connection;
-use interconnect_core::{Identity, Manifest};
+use interconnect_core::{DeltaEncoder, Identity, Manifest};
 use std::collections::VecDeque;
 use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
 use tokio::net::TcpStream;
@@ -92,20 +92,20 @@ pub async fn connect(
         server: server.clone(),
         messages: VecDeque::new(),
         seq: 0,
+        deltas: DeltaEncoder::new(),
     };
 
     let initial_snapshot = transport.current_snapshot();
 
-    let manifest = Manifest {
-        identity: Identity::local(format!("irc:{server}{channel}")),
-        name: channel.clone(),
-        substrate: None,
-        metadata: serde_json::json!({
-            "type": "irc",
-            "server": server,
-            "channel": channel,
-        }),
-    };
+    let manifest = Manifest::new(
+        Identity::local(format!("irc:{server}{channel}")),
+        channel.clone(),
+    )
+    .metadata(serde_json::json!({
+        "type": "irc",
+        "server": server,
+        "channel": channel,
+    }));
 
     let conn = IrcConnection::established(transport, manifest);
     Ok((conn, initial_snapshot))
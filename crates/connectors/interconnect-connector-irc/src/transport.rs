@@ -6,7 +6,7 @@
 //! never surfaced as snapshots.
 
 use crate::types::{IrcError, IrcIntent, IrcMessage, IrcSnapshot};
-use interconnect_core::{ClientWire, ServerWire, Transport};
+use interconnect_core::{ClientWire, DeltaEncoder, Transport};
 use std::collections::VecDeque;
 use std::time::{SystemTime, UNIX_EPOCH};
 use tokio::io::{AsyncWriteExt, BufReader, Lines};
@@ -21,6 +21,7 @@ pub struct IrcTransport {
     pub(crate) server: String,
     pub(crate) messages: VecDeque<IrcMessage>,
     pub(crate) seq: u64,
+    pub(crate) deltas: DeltaEncoder,
 }
 
 impl IrcTransport {
@@ -65,6 +66,10 @@ impl Transport for IrcTransport {
 
     async fn send(&mut self, data: &[u8]) -> Result<(), Self::Error> {
         let wire: ClientWire<IrcIntent> = serde_json::from_slice(data)?;
+        if let ClientWire::Ack { seq } = wire {
+            self.deltas.ack(seq);
+            return Ok(());
+        }
         if let ClientWire::Intent(IrcIntent::SendMessage { text }) = wire {
             self.send_raw(&format!("PRIVMSG {} :{}", self.channel, text))
                 .await?;
@@ -125,10 +130,7 @@ impl Transport for IrcTransport {
                     });
 
                     let snapshot = self.current_snapshot();
-                    let wire = ServerWire::<IrcSnapshot>::Snapshot {
-                        seq: self.seq,
-                        data: snapshot,
-                    };
+                    let wire = self.deltas.encode(self.seq, snapshot)?;
                     self.seq += 1;
                     return Ok(Some(serde_json::to_vec(&wire)?));
                 }
@@ -3,7 +3,7 @@
 use crate::transport::MailTransport;
 use crate::types::{MailError, MailIntent, MailSnapshot};
 use interconnect_client::Connection;
-use interconnect_core::{Identity, Manifest};
+use interconnect_core::{DeltaEncoder, Identity, Manifest};
 use reqwest::Client;
 
 pub type MailConnection = Connection<MailTransport, MailIntent, MailSnapshot>;
@@ -60,21 +60,21 @@ pub async fn connect(
         list_name: list_name.clone(),
         messages: Vec::new(),
         seq: 0,
+        deltas: DeltaEncoder::new(),
     };
 
     transport.messages = transport.fetch_messages().await?;
     let initial_snapshot = transport.current_snapshot();
 
-    let manifest = Manifest {
-        identity: Identity::local(format!("maillist:{base_url}/lists/{list_id}")),
-        name: list_name,
-        substrate: None,
-        metadata: serde_json::json!({
-            "type": "maillist",
-            "list_id": list_id,
-            "base_url": base_url,
-        }),
-    };
+    let manifest = Manifest::new(
+        Identity::local(format!("maillist:{base_url}/lists/{list_id}")),
+        list_name,
+    )
+    .metadata(serde_json::json!({
+        "type": "maillist",
+        "list_id": list_id,
+        "base_url": base_url,
+    }));
 
     let conn = MailConnection::established(transport, manifest);
     Ok((conn, initial_snapshot))
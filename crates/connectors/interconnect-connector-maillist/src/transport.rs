@@ -5,7 +5,7 @@
 //! `ClientWire<MailIntent>` bytes become Listmonk API calls.
 
 use crate::types::{MailError, MailIntent, MailMessage, MailSnapshot};
-use interconnect_core::{ClientWire, ServerWire, Transport};
+use interconnect_core::{ClientWire, DeltaEncoder, Transport};
 use reqwest::Client;
 use serde::Deserialize;
 use std::time::Duration;
@@ -70,6 +70,7 @@ pub struct MailTransport {
     pub(crate) list_name: String,
     pub(crate) messages: Vec<MailMessage>,
     pub(crate) seq: u64,
+    pub(crate) deltas: DeltaEncoder,
 }
 
 impl MailTransport {
@@ -134,6 +135,10 @@ impl Transport for MailTransport {
 
     async fn send(&mut self, data: &[u8]) -> Result<(), Self::Error> {
         let wire: ClientWire<MailIntent> = serde_json::from_slice(data)?;
+        if let ClientWire::Ack { seq } = wire {
+            self.deltas.ack(seq);
+            return Ok(());
+        }
         // Auth, Ping, TransferRequest — not applicable for platform connectors.
         if let ClientWire::Intent(MailIntent::SendMessage { subject, body }) = wire {
             // Create the campaign.
@@ -178,10 +183,7 @@ impl Transport for MailTransport {
             if latest.len() > self.messages.len() {
                 self.messages = latest;
                 let snapshot = self.current_snapshot();
-                let wire = ServerWire::<MailSnapshot>::Snapshot {
-                    seq: self.seq,
-                    data: snapshot,
-                };
+                let wire = self.deltas.encode(self.seq, snapshot)?;
                 self.seq += 1;
                 return Ok(Some(serde_json::to_vec(&wire)?));
             }
@@ -3,7 +3,7 @@
 use crate::transport::MatrixTransport;
 use crate::types::{MatrixError, MatrixIntent, MatrixMessage, MatrixSnapshot};
 use interconnect_client::Connection;
-use interconnect_core::{Identity, Manifest};
+use interconnect_core::{DeltaEncoder, Identity, Manifest};
 use reqwest::Client;
 use std::collections::VecDeque;
 
@@ -66,21 +66,19 @@ pub async fn connect(
         since: Some(since),
         messages,
         seq: 0,
+        deltas: DeltaEncoder::new(),
         txn_counter: 0,
     };
 
     let snapshot = transport.current_snapshot();
 
-    let manifest = Manifest {
-        identity: Identity::local(format!("matrix:{room_id}")),
-        name: room_name,
-        substrate: None,
-        metadata: serde_json::json!({
+    let manifest = Manifest::new(Identity::local(format!("matrix:{room_id}")), room_name).metadata(
+        serde_json::json!({
             "type": "matrix",
             "room_id": room_id,
             "homeserver": homeserver,
         }),
-    };
+    );
 
     let conn = MatrixConnection::established(transport, manifest);
     Ok((conn, snapshot))
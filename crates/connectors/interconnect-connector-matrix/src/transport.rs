@@ -5,7 +5,7 @@
 //! become Matrix API calls.
 
 use crate::types::{MatrixError, MatrixIntent, MatrixMessage, MatrixSnapshot};
-use interconnect_core::{ClientWire, ServerWire, Transport};
+use interconnect_core::{ClientWire, DeltaEncoder, Transport};
 use reqwest::Client;
 use std::collections::VecDeque;
 
@@ -24,6 +24,7 @@ pub struct MatrixTransport {
     pub(crate) since: Option<String>,
     pub(crate) messages: VecDeque<MatrixMessage>,
     pub(crate) seq: u64,
+    pub(crate) deltas: DeltaEncoder,
     /// Monotonically increasing counter used to generate transaction IDs.
     pub(crate) txn_counter: u64,
 }
@@ -124,6 +125,10 @@ impl Transport for MatrixTransport {
 
     async fn send(&mut self, data: &[u8]) -> Result<(), Self::Error> {
         let wire: ClientWire<MatrixIntent> = serde_json::from_slice(data)?;
+        if let ClientWire::Ack { seq } = wire {
+            self.deltas.ack(seq);
+            return Ok(());
+        }
         // Auth, Ping, TransferRequest — not applicable for platform connectors.
         if let ClientWire::Intent(MatrixIntent::SendMessage { text }) = wire {
             let txn_id = self.next_txn_id();
@@ -170,10 +175,7 @@ impl Transport for MatrixTransport {
             }
 
             let snapshot = self.current_snapshot();
-            let wire = ServerWire::<MatrixSnapshot>::Snapshot {
-                seq: self.seq,
-                data: snapshot,
-            };
+            let wire = self.deltas.encode(self.seq, snapshot)?;
             self.seq += 1;
             return Ok(Some(serde_json::to_vec(&wire)?));
         }
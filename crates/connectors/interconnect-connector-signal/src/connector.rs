@@ -3,7 +3,7 @@
 use crate::transport::SignalTransport;
 use crate::types::{SignalError, SignalIntent, SignalSnapshot};
 use interconnect_client::Connection;
-use interconnect_core::{Identity, Manifest};
+use interconnect_core::{DeltaEncoder, Identity, Manifest};
 use std::collections::VecDeque;
 use std::path::Path;
 use tokio::io::{AsyncBufReadExt, BufReader};
@@ -86,20 +86,20 @@ pub async fn connect(
         recipient: recipient.clone(),
         messages: VecDeque::new(),
         seq: 0,
+        deltas: DeltaEncoder::new(),
     };
 
     let initial_snapshot = transport.current_snapshot();
 
-    let manifest = Manifest {
-        identity: Identity::local(format!("signal:{account}:{recipient}")),
-        name: recipient.clone(),
-        substrate: None,
-        metadata: serde_json::json!({
-            "type": "signal",
-            "account": account,
-            "recipient": recipient,
-        }),
-    };
+    let manifest = Manifest::new(
+        Identity::local(format!("signal:{account}:{recipient}")),
+        recipient.clone(),
+    )
+    .metadata(serde_json::json!({
+        "type": "signal",
+        "account": account,
+        "recipient": recipient,
+    }));
 
     let conn = SignalConnection::established(transport, manifest);
     Ok((conn, initial_snapshot))
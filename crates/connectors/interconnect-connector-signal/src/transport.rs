@@ -6,7 +6,7 @@
 //! JSON-RPC `send` requests written to signal-cli's stdin.
 
 use crate::types::{SignalError, SignalIntent, SignalMessage, SignalSnapshot};
-use interconnect_core::{ClientWire, ServerWire, Transport};
+use interconnect_core::{ClientWire, DeltaEncoder, Transport};
 use std::collections::VecDeque;
 use std::sync::atomic::{AtomicU64, Ordering};
 use tokio::io::{AsyncWriteExt, BufReader, Lines};
@@ -25,6 +25,7 @@ pub struct SignalTransport {
     pub(crate) recipient: String,
     pub(crate) messages: VecDeque<SignalMessage>,
     pub(crate) seq: u64,
+    pub(crate) deltas: DeltaEncoder,
 }
 
 impl SignalTransport {
@@ -64,6 +65,10 @@ impl Transport for SignalTransport {
 
     async fn send(&mut self, data: &[u8]) -> Result<(), Self::Error> {
         let wire: ClientWire<SignalIntent> = serde_json::from_slice(data)?;
+        if let ClientWire::Ack { seq } = wire {
+            self.deltas.ack(seq);
+            return Ok(());
+        }
         // Auth, Ping, TransferRequest — not applicable for platform connectors.
         if let ClientWire::Intent(SignalIntent::SendMessage { text }) = wire {
             let params = serde_json::json!({
@@ -145,10 +150,7 @@ impl Transport for SignalTransport {
             });
 
             let snapshot = self.current_snapshot();
-            let wire = ServerWire::<SignalSnapshot>::Snapshot {
-                seq: self.seq,
-                data: snapshot,
-            };
+            let wire = self.deltas.encode(self.seq, snapshot)?;
             self.seq += 1;
             return Ok(Some(serde_json::to_vec(&wire)?));
         }
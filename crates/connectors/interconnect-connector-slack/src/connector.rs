@@ -3,7 +3,7 @@
 use crate::transport::SlackTransport;
 use crate::types::{SlackError, SlackIntent, SlackMessage, SlackSnapshot};
 use interconnect_client::Connection;
-use interconnect_core::{Identity, Manifest};
+use interconnect_core::{DeltaEncoder, Identity, Manifest};
 use reqwest::Client;
 use std::collections::VecDeque;
 use tokio_tungstenite::connect_async;
@@ -59,6 +59,7 @@ pub async fn connect(
         channel_name: channel_name.clone(),
         messages: VecDeque::new(),
         seq: 0,
+        deltas: DeltaEncoder::new(),
     };
 
     // Fetch initial message history via conversations.history.
@@ -66,15 +67,11 @@ pub async fn connect(
         .fetch_initial_snapshot(&http, &bot_token, &channel_id)
         .await?;
 
-    let manifest = Manifest {
-        identity: Identity::local(format!("slack:{channel_id}")),
-        name: channel_name,
-        substrate: None,
-        metadata: serde_json::json!({
+    let manifest = Manifest::new(Identity::local(format!("slack:{channel_id}")), channel_name)
+        .metadata(serde_json::json!({
             "type": "slack",
             "channel_id": channel_id,
-        }),
-    };
+        }));
 
     let conn = SlackConnection::established(transport, manifest);
     Ok((conn, initial_snapshot))
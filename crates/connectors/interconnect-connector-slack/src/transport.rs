@@ -6,7 +6,7 @@
 
 use crate::types::{SlackError, SlackIntent, SlackMessage, SlackSnapshot};
 use futures_util::{SinkExt, StreamExt};
-use interconnect_core::{ClientWire, ServerWire, Transport};
+use interconnect_core::{ClientWire, DeltaEncoder, Transport};
 use reqwest::Client;
 use serde::Deserialize;
 use std::collections::VecDeque;
@@ -22,6 +22,7 @@ pub struct SlackTransport {
     pub(crate) channel_name: String,
     pub(crate) messages: VecDeque<SlackMessage>,
     pub(crate) seq: u64,
+    pub(crate) deltas: DeltaEncoder,
 }
 
 /// Partial structure of a Slack Socket Mode envelope.
@@ -75,6 +76,10 @@ impl Transport for SlackTransport {
 
     async fn send(&mut self, data: &[u8]) -> Result<(), Self::Error> {
         let wire: ClientWire<SlackIntent> = serde_json::from_slice(data)?;
+        if let ClientWire::Ack { seq } = wire {
+            self.deltas.ack(seq);
+            return Ok(());
+        }
         // Auth, Ping, TransferRequest — not applicable for platform connectors.
         if let ClientWire::Intent(SlackIntent::SendMessage { text }) = wire {
             let body = serde_json::json!({
@@ -182,10 +187,7 @@ impl Transport for SlackTransport {
                 });
 
                 let snapshot = self.current_snapshot();
-                let wire = ServerWire::<SlackSnapshot>::Snapshot {
-                    seq: self.seq,
-                    data: snapshot,
-                };
+                let wire = self.deltas.encode(self.seq, snapshot)?;
                 self.seq += 1;
                 return Ok(Some(serde_json::to_vec(&wire)?));
             }
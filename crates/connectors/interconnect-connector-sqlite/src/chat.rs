@@ -13,7 +13,7 @@ use std::path::Path;
 use std::sync::{Arc, Mutex};
 
 use interconnect_client::Connection;
-use interconnect_core::{ClientWire, Identity, Manifest, ServerWire, Transport};
+use interconnect_core::{ClientWire, DeltaEncoder, Identity, Manifest, Transport};
 use serde::{Deserialize, Serialize};
 
 use crate::types::SqliteError;
@@ -289,6 +289,7 @@ pub struct SqliteChatTransport {
     path: std::path::PathBuf,
     config: ChatLogConfig,
     seq: u64,
+    deltas: DeltaEncoder,
     /// Last observed (COUNT(*), MAX(rowid)) for cheap change detection.
     last_signature: (i64, i64),
 }
@@ -335,6 +336,10 @@ impl Transport for SqliteChatTransport {
 
     async fn send(&mut self, data: &[u8]) -> Result<(), Self::Error> {
         let wire: ClientWire<ChatIntent> = serde_json::from_slice(data)?;
+        if let ClientWire::Ack { seq } = wire {
+            self.deltas.ack(seq);
+            return Ok(());
+        }
 
         let intent = match wire {
             ClientWire::Intent(i) => i,
@@ -420,10 +425,7 @@ impl Transport for SqliteChatTransport {
 
             if let Some((new_sig, snapshot)) = result {
                 self.last_signature = new_sig;
-                let wire = ServerWire::<SqliteChatSnapshot>::Snapshot {
-                    seq: self.seq,
-                    data: snapshot,
-                };
+                let wire = self.deltas.encode(self.seq, snapshot)?;
                 self.seq += 1;
                 return Ok(Some(serde_json::to_vec(&wire)?));
             }
@@ -485,19 +487,19 @@ pub async fn connect_chat(
         path: path.clone(),
         config,
         seq: 0,
+        deltas: DeltaEncoder::new(),
         last_signature: sig,
     };
 
-    let manifest = Manifest {
-        identity: Identity::local(format!("sqlite:{}:messages", path.display())),
-        name: format!("messages ({})", path.display()),
-        substrate: None,
-        metadata: serde_json::json!({
-            "type": "sqlite_chat",
-            "path": path.display().to_string(),
-            "table": "messages",
-        }),
-    };
+    let manifest = Manifest::new(
+        Identity::local(format!("sqlite:{}:messages", path.display())),
+        format!("messages ({})", path.display()),
+    )
+    .metadata(serde_json::json!({
+        "type": "sqlite_chat",
+        "path": path.display().to_string(),
+        "table": "messages",
+    }));
 
     let connection = SqliteChatConnection::established(transport, manifest);
     Ok((connection, initial_snapshot))
@@ -4,7 +4,7 @@ use std::path::Path;
 use std::sync::{Arc, Mutex};
 
 use interconnect_client::Connection;
-use interconnect_core::{Identity, Manifest};
+use interconnect_core::{DeltaEncoder, Identity, Manifest};
 
 use crate::transport::SqliteTransport;
 use crate::types::{ColumnInfo, SqliteError, SqliteIntent, SqliteSnapshot};
@@ -76,19 +76,19 @@ pub async fn connect(
         table: table.clone(),
         schema,
         seq: 0,
+        deltas: DeltaEncoder::new(),
         last_signature: sig,
     };
 
-    let manifest = Manifest {
-        identity: Identity::local(format!("sqlite:{}:{}", path.display(), table)),
-        name: format!("{table} ({})", path.display()),
-        substrate: None,
-        metadata: serde_json::json!({
-            "type": "sqlite",
-            "path": path.display().to_string(),
-            "table": table,
-        }),
-    };
+    let manifest = Manifest::new(
+        Identity::local(format!("sqlite:{}:{}", path.display(), table)),
+        format!("{table} ({})", path.display()),
+    )
+    .metadata(serde_json::json!({
+        "type": "sqlite",
+        "path": path.display().to_string(),
+        "table": table,
+    }));
 
     let connection = SqliteConnection::established(transport, manifest);
     Ok((connection, initial_snapshot))
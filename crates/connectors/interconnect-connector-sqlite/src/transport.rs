@@ -8,7 +8,7 @@ use std::collections::HashMap;
 use std::path::PathBuf;
 use std::sync::{Arc, Mutex};
 
-use interconnect_core::{ClientWire, ServerWire, Transport};
+use interconnect_core::{ClientWire, DeltaEncoder, Transport};
 use rusqlite::types::ValueRef;
 
 use crate::types::{ColumnInfo, SqliteError, SqliteIntent, SqliteSnapshot};
@@ -22,6 +22,7 @@ pub struct SqliteTransport {
     pub(crate) table: String,
     pub(crate) schema: Vec<ColumnInfo>,
     pub(crate) seq: u64,
+    pub(crate) deltas: DeltaEncoder,
     /// Last observed (count, max_rowid) for cheap change detection.
     pub(crate) last_signature: (i64, i64),
 }
@@ -128,6 +129,10 @@ impl Transport for SqliteTransport {
 
     async fn send(&mut self, data: &[u8]) -> Result<(), Self::Error> {
         let wire: ClientWire<SqliteIntent> = serde_json::from_slice(data)?;
+        if let ClientWire::Ack { seq } = wire {
+            self.deltas.ack(seq);
+            return Ok(());
+        }
 
         let intent = match wire {
             ClientWire::Intent(i) => i,
@@ -213,10 +218,7 @@ impl Transport for SqliteTransport {
 
             if let Some((new_sig, snapshot)) = result {
                 self.last_signature = new_sig;
-                let wire = ServerWire::<SqliteSnapshot>::Snapshot {
-                    seq: self.seq,
-                    data: snapshot,
-                };
+                let wire = self.deltas.encode(self.seq, snapshot)?;
                 self.seq += 1;
                 return Ok(Some(serde_json::to_vec(&wire)?));
             }
@@ -3,7 +3,7 @@
 use crate::transport::TelegramTransport;
 use crate::types::{TelegramError, TelegramMessage, TelegramSnapshot};
 use interconnect_client::Connection;
-use interconnect_core::{Identity, Manifest};
+use interconnect_core::{DeltaEncoder, Identity, Manifest};
 use reqwest::Client;
 use std::collections::VecDeque;
 
@@ -68,20 +68,17 @@ pub async fn connect(
         chat_title: chat_title.clone(),
         messages: messages.clone(),
         seq: 0,
+        deltas: DeltaEncoder::new(),
         update_offset,
     };
 
     let initial_snapshot = transport.current_snapshot();
 
-    let manifest = Manifest {
-        identity: Identity::local(format!("telegram:{chat_id}")),
-        name: chat_title,
-        substrate: None,
-        metadata: serde_json::json!({
+    let manifest = Manifest::new(Identity::local(format!("telegram:{chat_id}")), chat_title)
+        .metadata(serde_json::json!({
             "type": "telegram",
             "chat_id": chat_id,
-        }),
-    };
+        }));
 
     let conn = TelegramConnection::established(transport, manifest);
     Ok((conn, initial_snapshot))
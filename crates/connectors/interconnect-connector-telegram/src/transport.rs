@@ -5,7 +5,7 @@
 //! `ClientWire<TelegramIntent>` bytes become `sendMessage` API calls.
 
 use crate::types::{TelegramError, TelegramIntent, TelegramMessage, TelegramSnapshot};
-use interconnect_core::{ClientWire, ServerWire, Transport};
+use interconnect_core::{ClientWire, DeltaEncoder, Transport};
 use reqwest::Client;
 use std::collections::VecDeque;
 
@@ -20,6 +20,7 @@ pub struct TelegramTransport {
     pub(crate) chat_title: String,
     pub(crate) messages: VecDeque<TelegramMessage>,
     pub(crate) seq: u64,
+    pub(crate) deltas: DeltaEncoder,
     /// The `offset` passed to getUpdates (next expected update_id).
     pub(crate) update_offset: i64,
 }
@@ -53,6 +54,10 @@ impl Transport for TelegramTransport {
 
     async fn send(&mut self, data: &[u8]) -> Result<(), Self::Error> {
         let wire: ClientWire<TelegramIntent> = serde_json::from_slice(data)?;
+        if let ClientWire::Ack { seq } = wire {
+            self.deltas.ack(seq);
+            return Ok(());
+        }
         // Auth, Ping, TransferRequest — not applicable for platform connectors.
         if let ClientWire::Intent(TelegramIntent::SendMessage { text }) = wire {
             let body = serde_json::json!({
@@ -152,10 +157,7 @@ impl Transport for TelegramTransport {
                 });
 
                 let snapshot = self.current_snapshot();
-                let wire = ServerWire::<TelegramSnapshot>::Snapshot {
-                    seq: self.seq,
-                    data: snapshot,
-                };
+                let wire = self.deltas.encode(self.seq, snapshot)?;
                 self.seq += 1;
                 // Serialize the last emitted snapshot; deliver after processing all updates.
                 emitted = Some(serde_json::to_vec(&wire)?);
@@ -67,16 +67,15 @@ pub async fn connect(
         seq: 0,
     };
 
-    let manifest = Manifest {
-        identity: Identity::local(format!("whatsapp:{phone_number_id}:{recipient}")),
-        name: display_name,
-        substrate: None,
-        metadata: serde_json::json!({
-            "type": "whatsapp",
-            "phone_number_id": phone_number_id,
-            "recipient": recipient,
-        }),
-    };
+    let manifest = Manifest::new(
+        Identity::local(format!("whatsapp:{phone_number_id}:{recipient}")),
+        display_name,
+    )
+    .metadata(serde_json::json!({
+        "type": "whatsapp",
+        "phone_number_id": phone_number_id,
+        "recipient": recipient,
+    }));
 
     let conn = WhatsAppConnection::established(transport, manifest);
     Ok((conn, snapshot))
@@ -3,7 +3,7 @@
 use crate::transport::{ZulipTransport, register_event_queue};
 use crate::types::{ZulipError, ZulipIntent, ZulipSnapshot};
 use interconnect_client::Connection;
-use interconnect_core::{Identity, Manifest};
+use interconnect_core::{DeltaEncoder, Identity, Manifest};
 use reqwest::Client;
 use std::collections::VecDeque;
 
@@ -57,21 +57,21 @@ pub async fn connect(
         last_event_id,
         messages: VecDeque::new(),
         seq: 0,
+        deltas: DeltaEncoder::new(),
     };
 
     let initial_snapshot = transport.fetch_initial_snapshot().await?;
 
-    let manifest = Manifest {
-        identity: Identity::local(format!("zulip:{realm}/{stream}/{topic}")),
-        name: format!("{stream} > {topic}"),
-        substrate: None,
-        metadata: serde_json::json!({
-            "type": "zulip",
-            "realm": realm,
-            "stream": stream,
-            "topic": topic,
-        }),
-    };
+    let manifest = Manifest::new(
+        Identity::local(format!("zulip:{realm}/{stream}/{topic}")),
+        format!("{stream} > {topic}"),
+    )
+    .metadata(serde_json::json!({
+        "type": "zulip",
+        "realm": realm,
+        "stream": stream,
+        "topic": topic,
+    }));
 
     let conn = ZulipConnection::established(transport, manifest);
     Ok((conn, initial_snapshot))
@@ -5,7 +5,7 @@
 //! bytes become Zulip HTTP API calls.
 
 use crate::types::{ZulipError, ZulipIntent, ZulipMessage, ZulipSnapshot};
-use interconnect_core::{ClientWire, ServerWire, Transport};
+use interconnect_core::{ClientWire, DeltaEncoder, Transport};
 use reqwest::Client;
 use serde::Deserialize;
 use std::collections::VecDeque;
@@ -23,6 +23,7 @@ pub struct ZulipTransport {
     pub(crate) last_event_id: i64,
     pub(crate) messages: VecDeque<ZulipMessage>,
     pub(crate) seq: u64,
+    pub(crate) deltas: DeltaEncoder,
 }
 
 // --- Zulip API response types ---
@@ -146,6 +147,10 @@ impl Transport for ZulipTransport {
 
     async fn send(&mut self, data: &[u8]) -> Result<(), Self::Error> {
         let wire: ClientWire<ZulipIntent> = serde_json::from_slice(data)?;
+        if let ClientWire::Ack { seq } = wire {
+            self.deltas.ack(seq);
+            return Ok(());
+        }
         // Auth, Ping, TransferRequest — not applicable for platform connectors.
         if let ClientWire::Intent(ZulipIntent::SendMessage { content }) = wire {
             self.client
@@ -200,10 +205,7 @@ impl Transport for ZulipTransport {
             if let Some(msg) = new_message {
                 self.push_message(msg);
                 let snapshot = self.current_snapshot();
-                let wire = ServerWire::<ZulipSnapshot>::Snapshot {
-                    seq: self.seq,
-                    data: snapshot,
-                };
+                let wire = self.deltas.encode(self.seq, snapshot)?;
                 self.seq += 1;
                 return Ok(Some(serde_json::to_vec(&wire)?));
             }
@@ -1,15 +1,135 @@
 //! Typed connection to an Interconnect authority.
 
-use crate::ClientError;
+use crate::heartbeat::{Beat, Pulse};
+use crate::{ClientError, ConnectionReceiver, ConnectionSender, Heartbeat};
 use interconnect_core::{
-    ClientWire, Identity, Manifest, ServerWire, Transport, Wire, from_json, to_json,
+    Capabilities, ClientWire, Codec, ConnectionState, DeltaDecoder, DeltaError, Identity,
+    IntentOutcome, KnownAuthorities, Manifest, Protocol, Resume, ServerWire, Signer,
+    SplitTransport, SubstrateDownload, SubstrateStore, Transport, TrustError, Wire,
+    challenge_message, manifest_nonce,
 };
+use std::collections::VecDeque;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Callback for connection state changes.
+pub type StateObserver = Arc<dyn Fn(ConnectionState) + Send + Sync>;
+
+/// Opens a transport to a transfer destination, for
+/// [`Connection::follow_transfers`].
+pub type TransferResolver<T> = Arc<
+    dyn Fn(String) -> Pin<Box<dyn Future<Output = Result<T, ClientError>> + Send>> + Send + Sync,
+>;
+
+/// Opens a connection to a transfer destination, presenting a passport.
+type Follower<T, I, S> = Arc<dyn Fn(String, Login, Vec<u8>) -> Arriving<T, I, S> + Send + Sync>;
+
+/// A connection to a transfer destination, on its way.
+type Arriving<T, I, S> =
+    Pin<Box<dyn Future<Output = Result<(Connection<T, I, S>, S), ClientError>> + Send>>;
+
+/// Options for [`Connection::connect_with`].
+#[derive(Clone, Default)]
+pub struct ConnectOptions {
+    /// Wire codec. The authority detects it from the `Auth` message.
+    pub codec: Codec,
+    /// Answers `ServerWire::Challenge` when the authority asks the client to
+    /// prove it holds the key behind its identity.
+    pub signer: Option<Arc<dyn Signer>>,
+    /// Substrate cache. When set and the manifest names a substrate, it is
+    /// loaded from here or fetched from the authority and cached.
+    pub substrate_store: Option<SubstrateStore>,
+    /// Called as the connection moves through its states.
+    pub on_state: Option<StateObserver>,
+    /// Protocol versions and features offered in `Auth`. Defaults to
+    /// everything this crate supports.
+    pub capabilities: Capabilities,
+    /// Authorities met before. When set, the manifest must be signed and its
+    /// identity must match the entry for `authority_address`.
+    pub known_authorities: Option<KnownAuthorities>,
+    /// The address dialled (e.g. the URL), which `known_authorities` is
+    /// keyed by.
+    pub authority_address: Option<String>,
+    /// Keepalive pings while receiving, and how long the authority may be
+    /// silent. `None`, the default, waits on the transport forever.
+    pub heartbeat: Option<Heartbeat>,
+}
+
+impl ConnectOptions {
+    /// Use `codec` on the wire.
+    pub fn codec(mut self, codec: Codec) -> Self {
+        self.codec = codec;
+        self
+    }
+
+    /// Answer identity challenges with `signer`.
+    pub fn signer(mut self, signer: impl Signer + 'static) -> Self {
+        self.signer = Some(Arc::new(signer));
+        self
+    }
+
+    /// Cache substrates in `store`.
+    pub fn substrate_store(mut self, store: SubstrateStore) -> Self {
+        self.substrate_store = Some(store);
+        self
+    }
+
+    /// Report state changes to `f`.
+    pub fn on_state(mut self, f: impl Fn(ConnectionState) + Send + Sync + 'static) -> Self {
+        self.on_state = Some(Arc::new(f));
+        self
+    }
+
+    /// Offer `capabilities` instead of everything this crate supports.
+    pub fn capabilities(mut self, capabilities: Capabilities) -> Self {
+        self.capabilities = capabilities;
+        self
+    }
+
+    /// Check the authority at `address` against `known`.
+    pub fn known_authorities(
+        mut self,
+        known: KnownAuthorities,
+        address: impl Into<String>,
+    ) -> Self {
+        self.known_authorities = Some(known);
+        self.authority_address = Some(address.into());
+        self
+    }
+
+    /// Keep the connection alive with `heartbeat`.
+    pub fn heartbeat(mut self, heartbeat: Heartbeat) -> Self {
+        self.heartbeat = Some(heartbeat);
+        self
+    }
+
+    fn report(&self, state: ConnectionState) {
+        if let Some(f) = &self.on_state {
+            f(state);
+        }
+    }
+}
 
 /// A typed connection to an authority.
 ///
 /// Generic over the transport `T`, intent type `I`, and snapshot type `S`.
-/// For multi-authority use, hold two `Connection` instances and `select!`
-/// between their `recv()` futures:
+///
+/// Every snapshot is acknowledged, so authorities that support it switch to
+/// `ServerWire::Delta`. Deltas are applied internally: [`recv`](Self::recv)
+/// always yields full `Snapshot`s.
+///
+/// When the transport closes or fails, the connection enters
+/// `ConnectionState::Ghost`: the last snapshot and the substrate stay
+/// readable, and sending fails with [`ClientError::Ghost`].
+///
+/// With [`ConnectOptions::heartbeat`], `recv` pings the authority while it
+/// waits, and an authority silent for the whole timeout is given up on the
+/// same way, with [`ClientError::Timeout`].
+///
+/// `recv` is cancel-safe. For multi-authority use, hold two `Connection`
+/// instances and `select!` between their `recv()` futures:
 ///
 /// ```ignore
 /// tokio::select! {
@@ -20,9 +140,39 @@ use interconnect_core::{
 pub struct Connection<T, I, S> {
     transport: T,
     manifest: Manifest,
+    codec: Codec,
+    deltas: DeltaDecoder,
+    substrate: Option<Vec<u8>>,
+    state: ConnectionState,
+    on_state: Option<StateObserver>,
+    /// Messages that arrived while [`request`](Self::request) waited for its
+    /// result, in order, for [`recv`](Self::recv) to hand out.
+    pending: VecDeque<ServerWire<S>>,
+    /// A snapshot acknowledgement a cancelled `recv` left unsent.
+    ack_due: Option<u64>,
+    next_request_id: u64,
+    /// How this connection was made, to make the next one on transfer.
+    /// `None` for [`established`](Self::established) connections.
+    login: Option<Login>,
+    /// Why the authority refused the passport presented on connect.
+    passport_rejected: Option<String>,
+    /// From the authority's last `ServerWire::Resume`, for
+    /// [`resume`](Self::resume).
+    resume_token: Option<Vec<u8>>,
+    follower: Option<Follower<T, I, S>>,
+    /// The transfer being followed, kept across cancelled calls to `recv`.
+    following: Option<(String, Arriving<T, I, S>)>,
+    pulse: Option<Pulse>,
     _phantom: std::marker::PhantomData<(I, S)>,
 }
 
+#[derive(Clone)]
+struct Login {
+    identity: Identity,
+    name: Option<String>,
+    options: ConnectOptions,
+}
+
 impl<T, I, S> Connection<T, I, S>
 where
     T: Transport,
@@ -35,21 +185,179 @@ where
     /// Sends `Auth`, waits for `Manifest` then the initial `Snapshot`.
     /// Returns the connection and the initial snapshot.
     pub async fn connect(
-        mut transport: T,
+        transport: T,
         identity: Identity,
         name: Option<String>,
         passport: Option<Vec<u8>>,
     ) -> Result<(Self, S), ClientError> {
-        let auth: ClientWire<I> = ClientWire::Auth { identity, name, passport };
-        transport.send(&to_json(&auth)?).await.map_err(Into::into)?;
+        Self::connect_with_codec(transport, Codec::Json, identity, name, passport).await
+    }
+
+    /// Like [`connect`](Self::connect), but speaks `codec` on the wire.
+    ///
+    /// The authority detects the codec from the `Auth` message and replies
+    /// in kind, so binary codecs need no extra negotiation.
+    pub async fn connect_with_codec(
+        transport: T,
+        codec: Codec,
+        identity: Identity,
+        name: Option<String>,
+        passport: Option<Vec<u8>>,
+    ) -> Result<(Self, S), ClientError> {
+        let options = ConnectOptions::default().codec(codec);
+        Self::connect_with(transport, identity, name, passport, options).await
+    }
 
-        // Wait for Manifest. Skip System messages (unlikely but possible).
+    /// Like [`connect`](Self::connect), with explicit [`ConnectOptions`].
+    ///
+    /// If the authority sends a `Challenge`, it is answered with
+    /// `options.signer`; without a signer the handshake fails.
+    ///
+    /// If the manifest names a substrate and `options.substrate_store` is
+    /// set, the substrate is read from the store, or on a miss fetched in
+    /// chunks (`ConnectionState::LoadingSubstrate`), verified and cached.
+    ///
+    /// A signed manifest is verified against its identity. With
+    /// `options.known_authorities`, it must be signed, and an identity that
+    /// differs from the one on record fails with `TrustError::Changed`.
+    pub async fn connect_with(
+        transport: T,
+        identity: Identity,
+        name: Option<String>,
+        passport: Option<Vec<u8>>,
+        options: ConnectOptions,
+    ) -> Result<(Self, S), ClientError> {
+        let login = Login {
+            identity,
+            name,
+            options,
+        };
+        let (conn, initial, _) = Self::handshake(transport, login, passport, None).await?;
+        Ok((conn, initial))
+    }
+
+    /// Connect again over `transport` after the old one dropped, resuming
+    /// the session if the authority still holds it.
+    ///
+    /// The authority is shown the token from its last `ServerWire::Resume`
+    /// and the last acknowledged snapshot. Within its grace window it
+    /// carries on from there, with a delta where it can, and neither
+    /// `on_disconnect` nor `on_connect` runs. Otherwise a new session starts,
+    /// as with [`connect_with`](Self::connect_with) and the same identity
+    /// and options. Either way [`recv`](Self::recv) next yields the current
+    /// `Snapshot`. Returns whether the session was resumed.
+    ///
+    /// On failure the connection is left as it was, so this can be retried.
+    /// [`established`](Self::established) connections have no handshake to
+    /// repeat and fail with [`ClientError::Handshake`].
+    pub async fn resume(&mut self, transport: T) -> Result<bool, ClientError> {
+        let mut login = self.login.clone().ok_or_else(|| {
+            ClientError::Handshake("established connections cannot reconnect".to_string())
+        })?;
+        login.options.on_state = self.on_state.clone();
+        let resume = match (&self.resume_token, self.deltas.latest()) {
+            (Some(token), Some((seq, _))) => Some((
+                Resume {
+                    token: token.clone(),
+                    seq,
+                },
+                self.deltas.clone(),
+            )),
+            _ => None,
+        };
+
+        let (mut next, data, resumed) = Self::handshake(transport, login, None, resume).await?;
+        next.follower = self.follower.take();
+        next.next_request_id = self.next_request_id;
+        next.pending = std::mem::take(&mut self.pending);
+        *self = next;
+        let seq = self.deltas.latest().map_or(0, |(seq, _)| seq);
+        self.pending.push_back(ServerWire::Snapshot { seq, data });
+        Ok(resumed)
+    }
+
+    /// Authenticate over `transport`, resuming with `resume` if the
+    /// authority allows it. Also returns whether it did.
+    async fn handshake(
+        mut transport: T,
+        login: Login,
+        passport: Option<Vec<u8>>,
+        resume: Option<(Resume, DeltaDecoder)>,
+    ) -> Result<(Self, S, bool), ClientError> {
+        let options = login.options.clone();
+        let codec = options.codec;
+        options.report(ConnectionState::Connecting);
+        let nonce = manifest_nonce().map_err(|e| ClientError::Handshake(e.to_string()))?;
+        let (resume, decoder) = resume.unzip();
+        let auth: ClientWire<I> = ClientWire::Auth {
+            identity: login.identity.clone(),
+            name: login.name.clone(),
+            passport,
+            capabilities: Some(options.capabilities.clone()),
+            nonce: Some(nonce.clone()),
+            resume,
+        };
+        transport
+            .send(&codec.encode(&auth)?)
+            .await
+            .map_err(Into::into)?;
+
+        // Wait for Manifest, answering an identity challenge if one comes
+        // first. Skip System messages (unlikely but possible).
+        let mut passport_rejected = None;
+        let mut resume_token = None;
+        let mut resumed = false;
+        let mut challenger = None;
         let manifest = loop {
-            let raw = transport.recv().await.map_err(Into::into)?.ok_or(ClientError::Closed)?;
-            let msg: ServerWire<S> = from_json(&raw)?;
+            let raw = transport
+                .recv()
+                .await
+                .map_err(Into::into)?
+                .ok_or(ClientError::Closed)?;
+            let msg: ServerWire<S> = codec.decode(&raw)?;
             match msg {
                 ServerWire::Manifest(m) => break m,
+                ServerWire::Challenge { nonce, authority } => {
+                    let signer = options.signer.as_ref().ok_or_else(|| {
+                        ClientError::Handshake(
+                            "authority requested proof of identity but no signer is configured"
+                                .to_string(),
+                        )
+                    })?;
+                    // Never sign for an authority other than the one we know
+                    // at this address.
+                    if let (Some(known), Some(address)) =
+                        (&options.known_authorities, &options.authority_address)
+                        && let Some(identity) = known.get(address)
+                        && identity != authority
+                    {
+                        return Err(TrustError::Changed {
+                            address: address.clone(),
+                            known: identity,
+                            presented: authority,
+                        }
+                        .into());
+                    }
+                    let signature = signer.sign(&challenge_message(&authority, &nonce));
+                    challenger = Some(authority);
+                    let response: ClientWire<I> = ClientWire::ChallengeResponse { signature };
+                    transport
+                        .send(&codec.encode(&response)?)
+                        .await
+                        .map_err(Into::into)?;
+                }
+                ServerWire::Resume { token, resumed: r } => {
+                    resume_token = Some(token);
+                    resumed = r;
+                }
                 ServerWire::System { .. } => continue,
+                // The session goes ahead without the passport.
+                ServerWire::Error { code, message } if code == "passport_rejected" => {
+                    passport_rejected = Some(message);
+                }
+                ServerWire::Error { code, message } if code == "incompatible_version" => {
+                    return Err(ClientError::Handshake(message));
+                }
                 ServerWire::Error { code, message } => {
                     return Err(ClientError::Server { code, message });
                 }
@@ -62,55 +370,379 @@ where
             }
         };
 
-        // Wait for initial Snapshot. System broadcasts may arrive first.
-        let initial = loop {
-            let raw = transport.recv().await.map_err(Into::into)?.ok_or(ClientError::Closed)?;
-            let msg: ServerWire<S> = from_json(&raw)?;
-            match msg {
-                ServerWire::Snapshot { data, .. } => break data,
-                ServerWire::System { .. } => continue,
-                ServerWire::Error { code, message } => {
-                    return Err(ClientError::Server { code, message });
+        // Authorities that predate negotiation confirm nothing and speak v1.
+        if let Some(protocol) = &manifest.protocol
+            && !options.capabilities.versions.contains(&protocol.version)
+        {
+            return Err(ClientError::Handshake(format!(
+                "incompatible protocol: authority chose version {}, we speak versions {:?}",
+                protocol.version, options.capabilities.versions
+            )));
+        }
+
+        // The manifest must come from whoever we proved our identity to.
+        if let Some(challenger) = challenger
+            && challenger != manifest.identity
+        {
+            return Err(ClientError::Handshake(format!(
+                "challenged by {challenger} but the manifest is {}'s",
+                manifest.identity
+            )));
+        }
+
+        // A bad signature is refused even by clients that keep no store.
+        if manifest.signature.is_some() || options.known_authorities.is_some() {
+            manifest.verify(&nonce)?;
+        }
+        if let Some(known) = &options.known_authorities {
+            let address = options.authority_address.as_deref().ok_or_else(|| {
+                ClientError::Handshake("known authorities need the authority's address".into())
+            })?;
+            known.check(address, &manifest.identity)?;
+        }
+
+        // A resumed session carries on from snapshots we already have.
+        let mut deltas = decoder.filter(|_| resumed).unwrap_or_default();
+
+        // The authority sends the initial snapshot without waiting, so it may
+        // arrive while the substrate is still loading.
+        let mut early = None;
+        let substrate = match (&manifest.substrate, &options.substrate_store) {
+            (Some(hash), Some(store)) => match store.get(hash)? {
+                Some(data) => Some(data),
+                None => {
+                    options.report(ConnectionState::LoadingSubstrate);
+                    let data = fetch_substrate::<T, I, S>(
+                        &mut transport,
+                        codec,
+                        hash,
+                        &mut deltas,
+                        &mut early,
+                    )
+                    .await?;
+                    store.insert(hash, &data)?;
+                    Some(data)
                 }
-                other => {
-                    return Err(ClientError::Handshake(format!(
-                        "expected Snapshot, got discriminant {:?}",
-                        std::mem::discriminant(&other)
-                    )));
+            },
+            _ => None,
+        };
+        options.report(ConnectionState::Syncing);
+
+        // Wait for initial Snapshot. System broadcasts may arrive first.
+        let (seq, initial) = if let Some(snapshot) = early {
+            snapshot
+        } else {
+            loop {
+                let raw = transport
+                    .recv()
+                    .await
+                    .map_err(Into::into)?
+                    .ok_or(ClientError::Closed)?;
+                let msg: ServerWire<S> = codec.decode(&raw)?;
+                match msg {
+                    ServerWire::Snapshot { seq, data } => {
+                        deltas.snapshot(seq, &data)?;
+                        break (seq, data);
+                    }
+                    ServerWire::Delta {
+                        base_seq,
+                        seq,
+                        patch,
+                    } => break (seq, deltas.delta(base_seq, seq, &patch)?),
+                    ServerWire::System { .. } => continue,
+                    ServerWire::Error { code, message } => {
+                        return Err(ClientError::Server { code, message });
+                    }
+                    other => {
+                        return Err(ClientError::Handshake(format!(
+                            "expected Snapshot, got discriminant {:?}",
+                            std::mem::discriminant(&other)
+                        )));
+                    }
                 }
             }
         };
 
-        Ok((Self { transport, manifest, _phantom: std::marker::PhantomData }, initial))
+        let mut conn = Self {
+            transport,
+            manifest,
+            codec,
+            deltas,
+            substrate,
+            state: ConnectionState::Syncing,
+            on_state: options.on_state,
+            pending: VecDeque::new(),
+            ack_due: None,
+            next_request_id: 0,
+            login: Some(login),
+            passport_rejected,
+            resume_token,
+            follower: None,
+            following: None,
+            pulse: options.heartbeat.map(Pulse::new),
+            _phantom: std::marker::PhantomData,
+        };
+        conn.ack(seq).await?;
+        conn.set_state(ConnectionState::Live);
+        Ok((conn, initial, resumed))
     }
 
     /// Send an intent to the authority.
+    ///
+    /// Fails with [`ClientError::Ghost`] once the authority is lost.
     pub async fn send_intent(&mut self, intent: I) -> Result<(), ClientError> {
-        let msg: ClientWire<I> = ClientWire::Intent(intent);
-        self.transport.send(&to_json(&msg)?).await.map_err(Into::into)
+        self.send(ClientWire::Intent(intent)).await
+    }
+
+    /// Send an intent and wait for the authority's verdict on it.
+    ///
+    /// Messages that arrive in the meantime (snapshots, other results) are
+    /// kept for [`recv`](Self::recv). Fails with [`ClientError::Closed`] if
+    /// the authority is lost before answering, in which case the intent may
+    /// or may not have been applied.
+    pub async fn request(&mut self, intent: I) -> Result<IntentOutcome, ClientError> {
+        let id = self.next_request_id;
+        self.next_request_id += 1;
+        self.send(ClientWire::Request { id, intent }).await?;
+        loop {
+            match self.recv_transport().await? {
+                Some(ServerWire::IntentResult { id: got, outcome }) if got == id => {
+                    return Ok(outcome);
+                }
+                Some(msg) => self.pending.push_back(msg),
+                None => return Err(ClientError::Closed),
+            }
+        }
     }
 
     /// Receive the next message from the authority.
     ///
-    /// Returns `None` when the connection is closed.
+    /// Snapshots are acknowledged, and deltas are rebuilt into full
+    /// `Snapshot`s. Returns `None` when the connection is closed, and from
+    /// then on; the connection is then in ghost mode. A transport error also
+    /// enters ghost mode before it is returned.
+    ///
+    /// With [`follow_transfers`](Self::follow_transfers), a `Transfer` is
+    /// followed rather than returned; see there for what `recv` yields.
+    ///
+    /// Cancel-safe: a message taken off the transport is kept until it is
+    /// returned, its acknowledgement goes out on the next call, and a
+    /// transfer being followed carries on where it left off.
     pub async fn recv(&mut self) -> Result<Option<ServerWire<S>>, ClientError> {
-        let raw = match self.transport.recv().await.map_err(Into::into)? {
-            Some(b) => b,
-            None => return Ok(None),
-        };
-        Ok(Some(from_json(&raw)?))
+        if self.following.is_none() {
+            self.settle().await?;
+            let msg = match self.pending.pop_front() {
+                Some(msg) => Some(msg),
+                None => self.recv_transport().await?,
+            };
+            let Some(ServerWire::Transfer {
+                destination,
+                passport,
+            }) = msg
+            else {
+                return Ok(msg);
+            };
+            let (Some(follower), Some(login)) = (&self.follower, &self.login) else {
+                return Ok(Some(ServerWire::Transfer {
+                    destination,
+                    passport,
+                }));
+            };
+            let mut login = login.clone();
+            // Known authorities are checked against the address actually
+            // dialled.
+            if login.options.known_authorities.is_some() {
+                login.options.authority_address = Some(destination.clone());
+            }
+            login.options.on_state = self.on_state.clone();
+            let arriving = follower(destination.clone(), login, passport);
+            self.following = Some((destination, arriving));
+        }
+        Ok(self.follow().await)
+    }
+
+    /// Follow `ServerWire::Transfer`s automatically, opening a transport to
+    /// each destination with `resolve`.
+    ///
+    /// The connection then re-authenticates at the destination with the same
+    /// identity and options, presenting the passport. On arrival it switches
+    /// over: [`recv`](Self::recv) yields whatever the old authority sent
+    /// after the transfer, then the destination's `ServerWire::Manifest`
+    /// and its initial `Snapshot`, and the old authority is disconnected.
+    /// If the destination cannot be reached or refuses the passport, the
+    /// connection stays with the old authority and `recv` yields a
+    /// `ServerWire::Error` with code `transfer_failed` or
+    /// `transfer_refused`.
+    ///
+    /// Has no effect on [`established`](Self::established) connections,
+    /// which have no handshake to repeat.
+    pub fn follow_transfers<F, Fut>(&mut self, resolve: F)
+    where
+        F: Fn(String) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<T, ClientError>> + Send + 'static,
+        T: 'static,
+    {
+        let resolve: TransferResolver<T> =
+            Arc::new(move |destination| Box::pin(resolve(destination)));
+        self.follower = Some(Arc::new(move |destination, login: Login, passport| {
+            let transport = resolve(destination);
+            Box::pin(async move {
+                Self::connect_with(
+                    transport.await?,
+                    login.identity,
+                    login.name,
+                    Some(passport),
+                    login.options,
+                )
+                .await
+            })
+        }));
     }
 
-    /// Send a ping.
+    /// Finish the transfer under way, returning what `recv` should.
+    async fn follow(&mut self) -> Option<ServerWire<S>> {
+        let (_, arriving) = self.following.as_mut()?;
+        let arrived = arriving.await;
+        let (destination, _) = self.following.take()?;
+        Some(match arrived {
+            Ok((next, _)) if next.passport_rejected.is_some() => {
+                let reason = next.passport_rejected.unwrap_or_default();
+                ServerWire::error("transfer_refused", format!("{destination}: {reason}"))
+            }
+            Ok((mut next, snapshot)) => {
+                next.follower = self.follower.take();
+                next.next_request_id = self.next_request_id;
+                next.pending = std::mem::take(&mut self.pending);
+                // Dropping the old connection disconnects from its authority.
+                *self = next;
+                let seq = self.deltas.latest().map_or(0, |(seq, _)| seq);
+                let manifest = ServerWire::Manifest(self.manifest.clone());
+                let snapshot = ServerWire::Snapshot {
+                    seq,
+                    data: snapshot,
+                };
+                match self.pending.pop_front() {
+                    Some(earlier) => {
+                        self.pending.extend([manifest, snapshot]);
+                        earlier
+                    }
+                    None => {
+                        self.pending.push_back(snapshot);
+                        manifest
+                    }
+                }
+            }
+            Err(e) => ServerWire::error("transfer_failed", format!("{destination}: {e}")),
+        })
+    }
+
+    /// Why the authority refused the passport presented when connecting, if
+    /// it did. The session then started as if no passport had been given.
+    pub fn passport_rejected(&self) -> Option<&str> {
+        self.passport_rejected.as_deref()
+    }
+
+    /// The round trip of the last heartbeat ping the authority answered.
+    pub fn latency(&self) -> Option<Duration> {
+        self.pulse.as_ref().and_then(Pulse::latency)
+    }
+
+    async fn recv_transport(&mut self) -> Result<Option<ServerWire<S>>, ClientError> {
+        loop {
+            if self.state == ConnectionState::Ghost {
+                return Ok(None);
+            }
+            let raw = match self.recv_frame().await {
+                Ok(Some(b)) => b,
+                Ok(None) => {
+                    self.set_state(ConnectionState::Ghost);
+                    return Ok(None);
+                }
+                Err(e) => {
+                    self.set_state(ConnectionState::Ghost);
+                    return Err(e);
+                }
+            };
+            let (msg, ack) = rebuild(&mut self.deltas, self.codec.decode(&raw)?)?;
+            // The heartbeat's own business.
+            if let (ServerWire::Pong, Some(pulse)) = (&msg, &mut self.pulse) {
+                pulse.ponged();
+                continue;
+            }
+            // Held until acknowledged, so a cancelled call loses neither.
+            self.pending.push_back(msg);
+            self.ack_due = ack.or(self.ack_due);
+            self.settle().await?;
+            return Ok(self.pending.pop_back());
+        }
+    }
+
+    /// Send the acknowledgement a cancelled call left owing.
+    async fn settle(&mut self) -> Result<(), ClientError> {
+        if self.is_ghost() {
+            self.ack_due = None;
+        }
+        if let Some(seq) = self.ack_due {
+            self.ack(seq).await?;
+            self.ack_due = None;
+        }
+        Ok(())
+    }
+
+    /// The next frame, pinging on the heartbeat while waiting.
+    async fn recv_frame(&mut self) -> Result<Option<Vec<u8>>, ClientError> {
+        loop {
+            let Some(pulse) = &mut self.pulse else {
+                return self.transport.recv().await.map_err(Into::into);
+            };
+            match pulse.wait(self.transport.recv()).await {
+                Ok(frame) => return frame.map_err(Into::into),
+                Err(Beat::Silent(timeout)) => return Err(ClientError::Timeout(timeout)),
+                // A ping cut short stays due, and goes out on the next call.
+                Err(Beat::Ping) => self.ping().await?,
+            }
+        }
+    }
+
+    async fn ack(&mut self, seq: u64) -> Result<(), ClientError> {
+        self.send(ClientWire::Ack { seq }).await
+    }
+
+    /// Send a ping. With a heartbeat, the `Pong` goes to it rather than to
+    /// [`recv`](Self::recv).
     pub async fn ping(&mut self) -> Result<(), ClientError> {
-        let msg: ClientWire<I> = ClientWire::Ping;
-        self.transport.send(&to_json(&msg)?).await.map_err(Into::into)
+        self.send(ClientWire::Ping).await?;
+        if let Some(pulse) = &mut self.pulse {
+            pulse.pinged();
+        }
+        Ok(())
     }
 
     /// Request transfer to another authority.
     pub async fn request_transfer(&mut self, destination: String) -> Result<(), ClientError> {
-        let msg: ClientWire<I> = ClientWire::TransferRequest { destination };
-        self.transport.send(&to_json(&msg)?).await.map_err(Into::into)
+        self.send(ClientWire::TransferRequest { destination }).await
+    }
+
+    /// Send a message, entering ghost mode if the transport fails.
+    async fn send(&mut self, msg: ClientWire<I>) -> Result<(), ClientError> {
+        if self.state == ConnectionState::Ghost {
+            return Err(ClientError::Ghost);
+        }
+        let bytes = self.codec.encode(&msg)?;
+        if let Err(e) = self.transport.send(&bytes).await {
+            self.set_state(ConnectionState::Ghost);
+            return Err(e.into());
+        }
+        Ok(())
+    }
+
+    fn set_state(&mut self, state: ConnectionState) {
+        if self.state != state {
+            self.state = state;
+            if let Some(f) = &self.on_state {
+                f(state);
+            }
+        }
     }
 
     /// Create a connection where the platform has already handled authentication.
@@ -119,12 +751,420 @@ where
     /// handshake is done natively by the platform SDK rather than via the
     /// Interconnect wire protocol. The caller is responsible for fetching
     /// the initial snapshot separately before constructing the connection.
+    ///
+    /// Connector transports speak JSON, so the connection uses `Codec::Json`.
     pub fn established(transport: T, manifest: Manifest) -> Self {
-        Self { transport, manifest, _phantom: std::marker::PhantomData }
+        Self {
+            transport,
+            manifest,
+            codec: Codec::Json,
+            deltas: DeltaDecoder::new(),
+            substrate: None,
+            state: ConnectionState::Live,
+            on_state: None,
+            pending: VecDeque::new(),
+            ack_due: None,
+            next_request_id: 0,
+            login: None,
+            passport_rejected: None,
+            resume_token: None,
+            follower: None,
+            following: None,
+            pulse: None,
+            _phantom: std::marker::PhantomData,
+        }
+    }
+
+    /// Report state changes to `f`, replacing any observer set through
+    /// [`ConnectOptions::on_state`].
+    pub fn on_state(&mut self, f: impl Fn(ConnectionState) + Send + Sync + 'static) {
+        self.on_state = Some(Arc::new(f));
+    }
+
+    /// The current connection state: `Live`, or `Ghost` once the authority
+    /// is lost.
+    pub fn state(&self) -> ConnectionState {
+        self.state
+    }
+
+    /// Whether the authority has been lost.
+    pub fn is_ghost(&self) -> bool {
+        self.state == ConnectionState::Ghost
+    }
+
+    /// The most recent snapshot and its sequence number, if one has been
+    /// received. Still available in ghost mode.
+    pub fn last_snapshot(&self) -> Result<Option<(u64, S)>, serde_json::Error> {
+        self.deltas
+            .latest()
+            .map(|(seq, state)| Ok((seq, S::deserialize(state)?)))
+            .transpose()
     }
 
     /// The manifest received during the handshake.
     pub fn manifest(&self) -> &Manifest {
         &self.manifest
     }
+
+    /// The protocol version and features negotiated in the handshake, or
+    /// `None` if the authority predates negotiation.
+    pub fn protocol(&self) -> Option<&Protocol> {
+        self.manifest.protocol.as_ref()
+    }
+
+    /// The codec used on the wire.
+    pub fn codec(&self) -> Codec {
+        self.codec
+    }
+
+    /// The room's substrate, if the manifest named one and a substrate store
+    /// was configured.
+    pub fn substrate(&self) -> Option<&[u8]> {
+        self.substrate.as_deref()
+    }
+}
+
+impl<T, I, S> Connection<T, I, S>
+where
+    T: SplitTransport,
+    T::Error: Into<ClientError>,
+    I: Wire,
+    S: Wire,
+{
+    /// Split into a [`ConnectionSender`] and a [`ConnectionReceiver`], so
+    /// intents can be sent from any task while another waits in `recv`.
+    ///
+    /// The sender is cheap to clone. The receiver still acknowledges
+    /// snapshots, through the shared sending half. Split connections do not
+    /// follow transfers (a `Transfer` is received like any other message) or
+    /// resume, and have no [`request`](Self::request), whose result would
+    /// arrive at the receiver.
+    pub fn split(self) -> (ConnectionSender<T, I>, ConnectionReceiver<T, I, S>) {
+        let (tx, rx) = self.transport.split();
+        let ghost = self.state == ConnectionState::Ghost;
+        let sender = ConnectionSender::new(tx, self.codec, ghost);
+        let receiver = ConnectionReceiver {
+            receiver: rx,
+            sender: sender.clone(),
+            manifest: self.manifest,
+            deltas: self.deltas,
+            substrate: self.substrate,
+            state: self.state,
+            on_state: self.on_state,
+            pending: self.pending,
+            ack_due: self.ack_due,
+            pulse: self.pulse,
+        };
+        (sender, receiver)
+    }
+}
+
+/// Record a snapshot, or rebuild one from a delta. Also returns the sequence
+/// number to acknowledge, if any.
+pub(crate) fn rebuild<S: Wire>(
+    deltas: &mut DeltaDecoder,
+    msg: ServerWire<S>,
+) -> Result<(ServerWire<S>, Option<u64>), DeltaError> {
+    match msg {
+        ServerWire::Snapshot { seq, data } => {
+            deltas.snapshot(seq, &data)?;
+            Ok((ServerWire::Snapshot { seq, data }, Some(seq)))
+        }
+        ServerWire::Delta {
+            base_seq,
+            seq,
+            patch,
+        } => {
+            let data = deltas.delta(base_seq, seq, &patch)?;
+            Ok((ServerWire::Snapshot { seq, data }, Some(seq)))
+        }
+        other => Ok((other, None)),
+    }
+}
+
+/// Request a substrate and collect its chunks, keeping any snapshot that
+/// arrives in the meantime.
+async fn fetch_substrate<T, I, S>(
+    transport: &mut T,
+    codec: Codec,
+    hash: &str,
+    deltas: &mut DeltaDecoder,
+    early: &mut Option<(u64, S)>,
+) -> Result<Vec<u8>, ClientError>
+where
+    T: Transport,
+    T::Error: Into<ClientError>,
+    I: Wire,
+    S: Wire,
+{
+    let request: ClientWire<I> = ClientWire::SubstrateRequest {
+        hash: hash.to_string(),
+    };
+    transport
+        .send(&codec.encode(&request)?)
+        .await
+        .map_err(Into::into)?;
+
+    let mut download = SubstrateDownload::new(hash);
+    loop {
+        let raw = transport
+            .recv()
+            .await
+            .map_err(Into::into)?
+            .ok_or(ClientError::Closed)?;
+        let msg: ServerWire<S> = codec.decode(&raw)?;
+        match msg {
+            ServerWire::SubstrateChunk {
+                hash: h,
+                offset,
+                total,
+                data,
+            } if h == hash => {
+                if let Some(substrate) = download.push(offset, total, &data)? {
+                    return Ok(substrate);
+                }
+            }
+            // Nothing is acked yet, so updates are full snapshots, or deltas
+            // against the base a resume started from; keep the latest.
+            ServerWire::Snapshot { seq, data } => {
+                deltas.snapshot(seq, &data)?;
+                *early = Some((seq, data));
+            }
+            ServerWire::Delta {
+                base_seq,
+                seq,
+                patch,
+            } => *early = Some((seq, deltas.delta(base_seq, seq, &patch)?)),
+            ServerWire::System { .. } => continue,
+            ServerWire::Error { code, message } => {
+                return Err(ClientError::Server { code, message });
+            }
+            other => {
+                return Err(ClientError::Handshake(format!(
+                    "expected SubstrateChunk, got discriminant {:?}",
+                    std::mem::discriminant(&other)
+                )));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::VecDeque;
+    use std::sync::Mutex;
+
+    /// Replays scripted server frames, then reports the connection closed.
+    struct Scripted {
+        incoming: VecDeque<Vec<u8>>,
+    }
+
+    impl Transport for Scripted {
+        type Error = ClientError;
+
+        async fn send(&mut self, _data: &[u8]) -> Result<(), ClientError> {
+            Ok(())
+        }
+
+        async fn recv(&mut self) -> Result<Option<Vec<u8>>, ClientError> {
+            Ok(self.incoming.pop_front())
+        }
+    }
+
+    fn frame(msg: &ServerWire<Vec<String>>) -> Vec<u8> {
+        Codec::Json.encode(msg).unwrap()
+    }
+
+    #[tokio::test]
+    async fn ghost_after_authority_lost() {
+        let manifest = Manifest::new(Identity::local("room"), "room");
+        let transport = Scripted {
+            incoming: VecDeque::from([
+                frame(&ServerWire::Manifest(manifest)),
+                frame(&ServerWire::Snapshot {
+                    seq: 1,
+                    data: vec!["hello".into()],
+                }),
+            ]),
+        };
+
+        let states = Arc::new(Mutex::new(Vec::new()));
+        let seen = states.clone();
+        let options = ConnectOptions::default().on_state(move |s| seen.lock().unwrap().push(s));
+        let (mut conn, _): (Connection<_, String, Vec<String>>, _) =
+            Connection::connect_with(transport, Identity::local("alice"), None, None, options)
+                .await
+                .unwrap();
+        assert_eq!(conn.state(), ConnectionState::Live);
+
+        assert!(conn.recv().await.unwrap().is_none());
+        assert!(conn.is_ghost());
+        assert!(matches!(
+            conn.send_intent("hi".into()).await,
+            Err(ClientError::Ghost)
+        ));
+        assert_eq!(
+            conn.last_snapshot().unwrap(),
+            Some((1, vec!["hello".to_string()]))
+        );
+        assert_eq!(
+            *states.lock().unwrap(),
+            [
+                ConnectionState::Connecting,
+                ConnectionState::Syncing,
+                ConnectionState::Live,
+                ConnectionState::Ghost,
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn request_keeps_other_messages() {
+        let manifest = Manifest::new(Identity::local("room"), "room");
+        let transport = Scripted {
+            incoming: VecDeque::from([
+                frame(&ServerWire::Manifest(manifest)),
+                frame(&ServerWire::Snapshot {
+                    seq: 1,
+                    data: vec![],
+                }),
+                frame(&ServerWire::system("bob joined")),
+                frame(&ServerWire::IntentResult {
+                    id: 0,
+                    outcome: IntentOutcome::Accepted,
+                }),
+            ]),
+        };
+        let (mut conn, _): (Connection<_, String, Vec<String>>, _) =
+            Connection::connect(transport, Identity::local("alice"), None, None)
+                .await
+                .unwrap();
+
+        assert!(conn.request("hi".into()).await.unwrap().is_accepted());
+        assert!(matches!(
+            conn.recv().await.unwrap(),
+            Some(ServerWire::System { .. })
+        ));
+        assert!(matches!(
+            conn.request("again".into()).await,
+            Err(ClientError::Closed)
+        ));
+    }
+
+    #[tokio::test]
+    async fn transfers_keep_queued_messages() {
+        let room = |name: &str, after: Vec<ServerWire<Vec<String>>>| {
+            let manifest = Manifest::new(Identity::local(name), name);
+            let mut incoming = vec![
+                ServerWire::Manifest(manifest),
+                ServerWire::Snapshot {
+                    seq: 1,
+                    data: vec![name.to_string()],
+                },
+            ];
+            incoming.extend(after);
+            Scripted {
+                incoming: incoming.iter().map(frame).collect(),
+            }
+        };
+        let accepted = |id| ServerWire::IntentResult {
+            id,
+            outcome: IntentOutcome::Accepted,
+        };
+        let transport = room(
+            "a",
+            vec![
+                ServerWire::Transfer {
+                    destination: "b".into(),
+                    passport: vec![],
+                },
+                ServerWire::system("bob left"),
+                accepted(0),
+            ],
+        );
+        let (mut conn, _): (Connection<_, String, Vec<String>>, _) =
+            Connection::connect(transport, Identity::local("alice"), None, None)
+                .await
+                .unwrap();
+        let destination = room("b", vec![accepted(1)]);
+        let destination = Mutex::new(Some(destination));
+        conn.follow_transfers(move |_| {
+            let transport = destination.lock().unwrap().take();
+            async move { transport.ok_or(ClientError::Closed) }
+        });
+
+        // The transfer arrives while a request waits, with a message behind it.
+        assert!(conn.request("hi".into()).await.unwrap().is_accepted());
+        assert!(matches!(
+            conn.recv().await.unwrap(),
+            Some(ServerWire::System { .. })
+        ));
+        assert!(matches!(
+            conn.recv().await.unwrap(),
+            Some(ServerWire::Manifest(manifest)) if manifest.name == "b"
+        ));
+        assert!(matches!(
+            conn.recv().await.unwrap(),
+            Some(ServerWire::Snapshot { data, .. }) if data == ["b"]
+        ));
+        // Request ids carry on from the old connection.
+        assert!(conn.request("again".into()).await.unwrap().is_accepted());
+    }
+
+    #[tokio::test]
+    async fn incompatible_authority_is_a_handshake_error() {
+        let transport = Scripted {
+            incoming: VecDeque::from([frame(&ServerWire::error(
+                "incompatible_version",
+                "incompatible protocol: we speak versions [2], peer speaks [1]",
+            ))]),
+        };
+        let result: Result<(Connection<_, String, Vec<String>>, _), _> =
+            Connection::connect(transport, Identity::local("alice"), None, None).await;
+        assert!(matches!(
+            result,
+            Err(ClientError::Handshake(message)) if message.contains("incompatible protocol")
+        ));
+    }
+
+    #[tokio::test]
+    async fn silent_authority_times_out() {
+        let manifest = Manifest::new(Identity::local("room"), "room");
+        // Half-open: the authority's end stays up but never says a word more.
+        let (transport, mut authority) = interconnect_core::loopback();
+        authority
+            .send(&frame(&ServerWire::Manifest(manifest)))
+            .await
+            .unwrap();
+        authority
+            .send(&frame(&ServerWire::Snapshot {
+                seq: 1,
+                data: vec![],
+            }))
+            .await
+            .unwrap();
+        let heartbeat = Heartbeat::new(Duration::from_millis(10), Duration::from_millis(50));
+        let options = ConnectOptions::default().heartbeat(heartbeat);
+        let (mut conn, _): (Connection<_, String, Vec<String>>, _) =
+            Connection::connect_with(transport, Identity::local("alice"), None, None, options)
+                .await
+                .unwrap();
+
+        assert!(matches!(
+            conn.recv().await,
+            Err(ClientError::Timeout(timeout)) if timeout == heartbeat.timeout
+        ));
+        assert!(conn.is_ghost());
+        assert_eq!(conn.latency(), None);
+
+        drop(conn);
+        let mut pings = 0;
+        while let Some(raw) = authority.recv().await.unwrap() {
+            if let ClientWire::<String>::Ping = Codec::Json.decode(&raw).unwrap() {
+                pings += 1;
+            }
+        }
+        assert!(pings >= 2, "sent {pings} pings");
+    }
 }
@@ -4,16 +4,53 @@ pub enum ClientError {
     #[error("websocket error: {0}")]
     WebSocket(#[from] tokio_tungstenite::tungstenite::Error),
 
+    /// A stream transport (TCP, Unix socket, stdio) failed.
+    #[error("io error: {0}")]
+    Io(#[from] std::io::Error),
+
+    /// The HTTP transport failed.
+    #[error(transparent)]
+    Http(#[from] crate::HttpError),
+
+    /// A transport layer refused a frame.
+    #[error("frame error: {0}")]
+    Frame(#[from] interconnect_core::FrameError),
+
     #[error("connection closed")]
     Closed,
 
     #[error("codec error: {0}")]
-    Codec(#[from] serde_json::Error),
+    Codec(#[from] interconnect_core::CodecError),
 
     /// Received an unexpected message type during the auth handshake.
     #[error("handshake error: {0}")]
     Handshake(String),
 
+    /// A `ServerWire::Delta` could not be applied.
+    #[error("delta error: {0}")]
+    Delta(#[from] interconnect_core::DeltaError),
+
+    /// The substrate could not be fetched, verified or cached.
+    #[error("substrate error: {0}")]
+    Substrate(#[from] interconnect_core::SubstrateError),
+
+    /// The authority was lost; the connection is read-only.
+    #[error("authority lost; connection is in ghost mode")]
+    Ghost,
+
+    /// The authority sent nothing within the heartbeat timeout; the
+    /// connection is now in ghost mode.
+    #[error("authority silent for {0:?}")]
+    Timeout(std::time::Duration),
+
+    /// The authority's manifest signature or identity was refused.
+    #[error("untrusted authority: {0}")]
+    Trust(#[from] interconnect_core::TrustError),
+
+    /// A `RoomSet` has no room of this name.
+    #[error("no room named {0}")]
+    UnknownRoom(String),
+
     /// The server sent a `ServerWire::Error` message.
     #[error("server error {code}: {message}")]
     Server { code: String, message: String },
@@ -22,3 +59,24 @@ pub enum ClientError {
     #[error("{0}")]
     Other(Box<dyn std::error::Error + Send + Sync + 'static>),
 }
+
+impl From<serde_json::Error> for ClientError {
+    fn from(e: serde_json::Error) -> Self {
+        ClientError::Codec(e.into())
+    }
+}
+
+impl From<interconnect_core::LoopbackClosed> for ClientError {
+    fn from(_: interconnect_core::LoopbackClosed) -> Self {
+        ClientError::Closed
+    }
+}
+
+impl<E: Into<ClientError>> From<interconnect_core::LayerError<E>> for ClientError {
+    fn from(e: interconnect_core::LayerError<E>) -> Self {
+        match e {
+            interconnect_core::LayerError::Inner(e) => e.into(),
+            interconnect_core::LayerError::Frame(e) => e.into(),
+        }
+    }
+}
@@ -0,0 +1,97 @@
+//! Keepalive: periodic pings, and giving up on a silent authority.
+//!
+//! A half-open connection (the peer gone without a close) never fails a
+//! read, so without a heartbeat `recv` would wait on it forever.
+
+use std::collections::VecDeque;
+use std::future::Future;
+use std::time::Duration;
+use tokio::time::Instant;
+
+/// Keepalive settings for [`ConnectOptions::heartbeat`](crate::ConnectOptions::heartbeat).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Heartbeat {
+    /// Time between pings.
+    pub interval: Duration,
+    /// How long the authority may send nothing before it is taken as lost.
+    pub timeout: Duration,
+}
+
+impl Heartbeat {
+    /// Ping every `interval`, and give up after `timeout` of silence.
+    pub fn new(interval: Duration, timeout: Duration) -> Self {
+        Self { interval, timeout }
+    }
+}
+
+impl Default for Heartbeat {
+    /// Ping every 15 seconds; give up after 45.
+    fn default() -> Self {
+        Self::new(Duration::from_secs(15), Duration::from_secs(45))
+    }
+}
+
+/// What interrupted a wait in [`Pulse::wait`].
+pub(crate) enum Beat {
+    /// A ping is due.
+    Ping,
+    /// Nothing was heard for the whole timeout.
+    Silent(Duration),
+}
+
+/// A heartbeat's progress on one connection.
+pub(crate) struct Pulse {
+    heartbeat: Heartbeat,
+    /// When the authority last sent anything.
+    heard: Instant,
+    next_ping: Instant,
+    /// When each unanswered ping was sent, oldest first.
+    pings: VecDeque<Instant>,
+    latency: Option<Duration>,
+}
+
+impl Pulse {
+    pub(crate) fn new(heartbeat: Heartbeat) -> Self {
+        let now = Instant::now();
+        Self {
+            heartbeat,
+            heard: now,
+            next_ping: now + heartbeat.interval,
+            pings: VecDeque::new(),
+            latency: None,
+        }
+    }
+
+    /// Wait for `recv`, unless a ping falls due or the authority has been
+    /// silent too long first. `recv` must be cancel-safe.
+    pub(crate) async fn wait<F: Future>(&mut self, recv: F) -> Result<F::Output, Beat> {
+        let deadline = self.heard + self.heartbeat.timeout;
+        match tokio::time::timeout_at(deadline.min(self.next_ping), recv).await {
+            Ok(output) => {
+                self.heard = Instant::now();
+                Ok(output)
+            }
+            Err(_) if Instant::now() >= deadline => Err(Beat::Silent(self.heartbeat.timeout)),
+            Err(_) => Err(Beat::Ping),
+        }
+    }
+
+    /// A ping went out.
+    pub(crate) fn pinged(&mut self) {
+        let now = Instant::now();
+        self.pings.push_back(now);
+        self.next_ping = now + self.heartbeat.interval;
+    }
+
+    /// A pong came back, answering the oldest ping.
+    pub(crate) fn ponged(&mut self) {
+        if let Some(sent) = self.pings.pop_front() {
+            self.latency = Some(sent.elapsed());
+        }
+    }
+
+    /// The round trip of the last answered ping.
+    pub(crate) fn latency(&self) -> Option<Duration> {
+        self.latency
+    }
+}
@@ -0,0 +1,354 @@
+//! HTTP transport, for networks whose proxies break WebSockets.
+//!
+//! Talks to the endpoint served by `interconnect_server::http_router`:
+//! frames go up by POST and come down by Server-Sent Events or long-poll.
+//! A dropped stream is resumed from the last frame received, so a flaky
+//! proxy costs a reconnect but no messages.
+
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use interconnect_core::{RecvHalf, SendHalf, SplitTransport, Transport};
+use reqwest::{Client, Response, StatusCode};
+use serde::Deserialize;
+use std::collections::VecDeque;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Acknowledge streamed frames at least this often, well inside the 4096 the
+/// server holds before giving up on a session that only listens.
+const ACK_EVERY: u64 = 256;
+
+/// How server frames are fetched.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum HttpMode {
+    /// One long-lived Server-Sent Events response.
+    #[default]
+    Sse,
+    /// Repeated requests that each wait for frames. Works through proxies
+    /// that buffer streaming responses.
+    LongPoll,
+}
+
+/// Errors from [`HttpTransport`].
+#[derive(Debug, thiserror::Error)]
+pub enum HttpError {
+    #[error("http error: {0}")]
+    Request(#[from] reqwest::Error),
+
+    /// The server discarded frames this client had not received.
+    #[error("session frames lost; the session cannot be resumed")]
+    Gone,
+
+    /// The server no longer knows the session.
+    #[error("session closed")]
+    Closed,
+
+    #[error("bad frame: {0}")]
+    Frame(String),
+}
+
+/// A transport over HTTP requests.
+pub struct HttpTransport {
+    sender: HttpSender,
+    receiver: HttpReceiver,
+}
+
+/// The sending half of a split [`HttpTransport`].
+pub struct HttpSender {
+    client: Client,
+    /// `{base}/sessions/{id}`.
+    session: String,
+    /// Shared with the receiver.
+    cursor: Arc<AtomicU64>,
+}
+
+/// The receiving half of a split [`HttpTransport`].
+pub struct HttpReceiver {
+    client: Client,
+    session: String,
+    mode: HttpMode,
+    /// Number of the next frame wanted.
+    cursor: Arc<AtomicU64>,
+    /// Cursor last acknowledged while streaming.
+    acked: u64,
+    received: VecDeque<Vec<u8>>,
+    events: Option<EventStream>,
+    closed: bool,
+}
+
+#[derive(Deserialize)]
+struct Opened {
+    session: String,
+}
+
+#[derive(Deserialize)]
+struct Batch {
+    cursor: u64,
+    frames: Vec<String>,
+    closed: bool,
+}
+
+impl HttpTransport {
+    /// Open a session with the endpoint at `url`, receiving by SSE.
+    pub async fn connect(url: &str) -> Result<Self, HttpError> {
+        Self::connect_with(url, HttpMode::default()).await
+    }
+
+    /// Open a session with the endpoint at `url`, receiving by `mode`.
+    pub async fn connect_with(url: &str, mode: HttpMode) -> Result<Self, HttpError> {
+        let client = Client::new();
+        let base = url.trim_end_matches('/');
+        let opened: Opened = client
+            .post(format!("{base}/sessions"))
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        let session = format!("{base}/sessions/{}", opened.session);
+        let cursor = Arc::new(AtomicU64::new(0));
+        Ok(Self {
+            sender: HttpSender {
+                client: client.clone(),
+                session: session.clone(),
+                cursor: cursor.clone(),
+            },
+            receiver: HttpReceiver {
+                client,
+                session,
+                mode,
+                cursor,
+                acked: 0,
+                received: VecDeque::new(),
+                events: None,
+                closed: false,
+            },
+        })
+    }
+
+    /// End the session.
+    pub async fn close(self) -> Result<(), HttpError> {
+        self.sender.close().await
+    }
+}
+
+impl HttpSender {
+    /// End the session.
+    pub async fn close(self) -> Result<(), HttpError> {
+        let response = self.client.delete(&self.session).send().await?;
+        check(response).await.map(drop)
+    }
+}
+
+impl HttpReceiver {
+    fn cursor(&self) -> u64 {
+        self.cursor.load(Ordering::Relaxed)
+    }
+
+    async fn poll(&mut self) -> Result<(), HttpError> {
+        let response = self
+            .client
+            .get(format!("{}/poll", self.session))
+            .query(&[("cursor", self.cursor())])
+            .send()
+            .await?;
+        let batch: Batch = match check(response).await {
+            Ok(response) => response.json().await?,
+            Err(HttpError::Closed) => {
+                self.closed = true;
+                return Ok(());
+            }
+            Err(e) => return Err(e),
+        };
+        for frame in batch.frames {
+            self.received.push_back(decode(&frame)?);
+        }
+        self.cursor.store(batch.cursor, Ordering::Relaxed);
+        self.closed = batch.closed;
+        Ok(())
+    }
+
+    async fn next_event(&mut self) -> Result<(), HttpError> {
+        let events = match &mut self.events {
+            Some(events) => events,
+            None => {
+                let response = self
+                    .client
+                    .get(format!("{}/events", self.session))
+                    .query(&[("cursor", self.cursor())])
+                    .send()
+                    .await?;
+                match check(response).await {
+                    Ok(response) => self.events.insert(EventStream::new(response)),
+                    Err(HttpError::Closed) => {
+                        self.closed = true;
+                        return Ok(());
+                    }
+                    Err(e) => return Err(e),
+                }
+            }
+        };
+        match events.next().await {
+            Ok(Some(event)) if event.kind == "close" => self.closed = true,
+            Ok(Some(event)) => {
+                let n = event
+                    .id
+                    .parse::<u64>()
+                    .map_err(|_| HttpError::Frame(format!("bad event id {:?}", event.id)))?;
+                // Resent after a reconnect we had already counted.
+                if n >= self.cursor() {
+                    self.received.push_back(decode(&event.data)?);
+                    self.cursor.store(n + 1, Ordering::Relaxed);
+                }
+                if self.cursor() - self.acked >= ACK_EVERY {
+                    self.ack().await;
+                }
+            }
+            // The stream broke; resume from the cursor on the next call.
+            Ok(None) | Err(_) => self.events = None,
+        }
+        Ok(())
+    }
+
+    /// Let the server discard what the stream has delivered. A failure is
+    /// left for the next ack, or for the stream to report.
+    async fn ack(&mut self) {
+        let cursor = self.cursor();
+        let response = self
+            .client
+            .post(format!("{}/ack", self.session))
+            .query(&[("cursor", cursor)])
+            .send()
+            .await;
+        if response.is_ok_and(|r| r.status().is_success()) {
+            self.acked = cursor;
+        }
+    }
+}
+
+impl Transport for HttpTransport {
+    type Error = HttpError;
+
+    async fn send(&mut self, data: &[u8]) -> Result<(), HttpError> {
+        self.sender.send(data).await
+    }
+
+    async fn recv(&mut self) -> Result<Option<Vec<u8>>, HttpError> {
+        self.receiver.recv().await
+    }
+}
+
+impl SplitTransport for HttpTransport {
+    type Sender = HttpSender;
+    type Receiver = HttpReceiver;
+
+    fn split(self) -> (HttpSender, HttpReceiver) {
+        (self.sender, self.receiver)
+    }
+}
+
+impl SendHalf for HttpSender {
+    type Error = HttpError;
+
+    async fn send(&mut self, data: &[u8]) -> Result<(), HttpError> {
+        // The cursor lets the server discard frames streamed to us.
+        let response = self
+            .client
+            .post(format!("{}/send", self.session))
+            .query(&[("cursor", self.cursor.load(Ordering::Relaxed))])
+            .header("content-type", "application/octet-stream")
+            .body(data.to_vec())
+            .send()
+            .await?;
+        check(response).await.map(drop)
+    }
+}
+
+impl RecvHalf for HttpReceiver {
+    type Error = HttpError;
+
+    async fn recv(&mut self) -> Result<Option<Vec<u8>>, HttpError> {
+        loop {
+            if let Some(frame) = self.received.pop_front() {
+                return Ok(Some(frame));
+            }
+            if self.closed {
+                return Ok(None);
+            }
+            match self.mode {
+                HttpMode::Sse => self.next_event().await?,
+                HttpMode::LongPoll => self.poll().await?,
+            }
+        }
+    }
+}
+
+async fn check(response: Response) -> Result<Response, HttpError> {
+    match response.status() {
+        StatusCode::NOT_FOUND => Err(HttpError::Closed),
+        StatusCode::GONE => Err(HttpError::Gone),
+        _ => Ok(response.error_for_status()?),
+    }
+}
+
+fn decode(frame: &str) -> Result<Vec<u8>, HttpError> {
+    BASE64
+        .decode(frame)
+        .map_err(|e| HttpError::Frame(e.to_string()))
+}
+
+/// One Server-Sent Event.
+#[derive(Default)]
+struct SseEvent {
+    id: String,
+    kind: String,
+    data: String,
+}
+
+/// Parses events out of a streaming response as its chunks arrive.
+struct EventStream {
+    response: Response,
+    buf: Vec<u8>,
+    event: SseEvent,
+}
+
+impl EventStream {
+    fn new(response: Response) -> Self {
+        Self {
+            response,
+            buf: Vec::new(),
+            event: SseEvent::default(),
+        }
+    }
+
+    /// The next event, or `None` when the response ends.
+    async fn next(&mut self) -> Result<Option<SseEvent>, HttpError> {
+        loop {
+            while let Some(end) = self.buf.iter().position(|&b| b == b'\n') {
+                let line: Vec<u8> = self.buf.drain(..=end).collect();
+                let line = String::from_utf8_lossy(&line);
+                let line = line.trim_end_matches(['\n', '\r']);
+                if line.is_empty() {
+                    // Comments (keep-alives) leave an empty event behind.
+                    let event = std::mem::take(&mut self.event);
+                    if !event.id.is_empty() || !event.kind.is_empty() {
+                        return Ok(Some(event));
+                    }
+                    continue;
+                }
+                let (field, value) = line.split_once(':').unwrap_or((line, ""));
+                let value = value.strip_prefix(' ').unwrap_or(value);
+                match field {
+                    "id" => self.event.id = value.to_string(),
+                    "event" => self.event.kind = value.to_string(),
+                    "data" => self.event.data.push_str(value),
+                    _ => {}
+                }
+            }
+            match self.response.chunk().await? {
+                Some(chunk) => self.buf.extend_from_slice(&chunk),
+                None => return Ok(None),
+            }
+        }
+    }
+}
@@ -21,6 +21,54 @@
 //! }
 //! ```
 //!
+//! Any [`Transport`](interconnect_core::Transport) works: besides
+//! [`WsTransport`], `interconnect_core` has length-prefixed `TcpTransport`,
+//! `UnixTransport` and `ChildTransport` (a room spawned as a subprocess).
+//! Where proxies break WebSockets, [`HttpTransport`] posts intents and
+//! receives by Server-Sent Events or long-poll.
+//!
+//! # Sending While Receiving
+//!
+//! `recv` and `send_intent` both borrow the connection mutably. To send from
+//! one task while another waits for messages, split it:
+//!
+//! ```ignore
+//! let (tx, mut rx) = conn.split();
+//! tokio::spawn(async move { tx.send_intent(MyIntent::Hello).await });
+//! while let Some(msg) = rx.recv().await? {
+//!     // ...
+//! }
+//! ```
+//!
+//! The halves also convert into a `Sink` of intents and a `Stream` of
+//! messages. Splitting needs a [`SplitTransport`]: WebSocket, HTTP, the
+//! framed transports and `Loopback` all are.
+//!
+//! [`SplitTransport`]: interconnect_core::SplitTransport
+//!
+//! # Staying Connected
+//!
+//! [`ReconnectingConnection`] opens its connection with a factory (a
+//! platform connector's `connect()` works as is) and calls it again with
+//! backoff whenever the connection is lost:
+//!
+//! ```ignore
+//! let (mut conn, snapshot) = ReconnectingConnection::connect(
+//!     move || slack::connect(bot_token.clone(), app_token.clone(), channel.clone()),
+//!     Backoff::default(),
+//! ).await?;
+//!
+//! while let Some(event) = conn.recv().await? {
+//!     match event {
+//!         ReconnectEvent::Message(msg) => { /* as from Connection::recv */ }
+//!         ReconnectEvent::Reconnecting { delay, .. } => { /* show "offline" */ }
+//!         ReconnectEvent::Reconnected { snapshot } => { /* start over from here */ }
+//!     }
+//! }
+//! ```
+//!
+//! Intents sent while it is down are queued for the next connection.
+//!
 //! # Multiple Authorities
 //!
 //! A client can be connected to multiple authorities simultaneously.
@@ -34,14 +82,37 @@
 //!     }
 //! }
 //! ```
+//!
+//! For a changing set of rooms of different types, a [`RoomSet`] holds them
+//! by name, merges their messages into one stream and routes intents:
+//!
+//! ```ignore
+//! let mut rooms = RoomSet::new();
+//! rooms.insert("general", general);
+//! rooms.insert("issues", github);
+//! rooms.send_intent("general", MyIntent::Hello).await?;
+//! while let Some((room, event)) = rooms.recv().await {
+//!     // RoomEvent::Message, Reconnecting, Reconnected or Closed
+//! }
+//! ```
 
 mod connection;
 mod error;
+mod heartbeat;
+mod http;
+mod reconnect;
+mod rooms;
+mod split;
 mod transport;
 
-pub use connection::Connection;
+pub use connection::{ConnectOptions, Connection, StateObserver, TransferResolver};
 pub use error::ClientError;
-pub use transport::WsTransport;
+pub use heartbeat::Heartbeat;
+pub use http::{HttpError, HttpMode, HttpReceiver, HttpSender, HttpTransport};
+pub use reconnect::{Backoff, ConnectFactory, ReconnectEvent, ReconnectingConnection};
+pub use rooms::{Room, RoomEvent, RoomSet};
+pub use split::{ConnectionReceiver, ConnectionSender};
+pub use transport::{WsReceiver, WsSender, WsTransport};
 
 /// Convenience type alias for a WebSocket-backed connection.
 pub type WsConnection<I, S> = Connection<WsTransport, I, S>;
@@ -0,0 +1,527 @@
+//! A connection that comes back by itself.
+//!
+//! [`ReconnectingConnection`] wraps whatever opens a [`Connection`] (a
+//! platform connector's `connect()`, or [`Connection::connect`] over a fresh
+//! transport) and calls it again, with backoff, whenever the connection is
+//! lost.
+
+use crate::{ClientError, Connection};
+use interconnect_core::{ServerWire, Transport, Wire};
+use std::collections::VecDeque;
+use std::error::Error;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::time::Instant;
+
+/// Opens a connection and returns it with its initial snapshot, for
+/// [`ReconnectingConnection`].
+pub type ConnectFactory<T, I, S> = Arc<dyn Fn() -> Opening<T, I, S> + Send + Sync>;
+
+/// An attempt to open a connection, in flight.
+type Opening<T, I, S> =
+    Pin<Box<dyn Future<Output = Result<(Connection<T, I, S>, S), ClientError>> + Send>>;
+
+/// Exponential backoff with jitter between reconnection attempts.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Backoff {
+    /// Delay before the first attempt.
+    pub initial: Duration,
+    /// Longest delay between attempts.
+    pub max: Duration,
+    /// Growth of the delay with each failed attempt.
+    pub multiplier: f64,
+    /// Share of each delay taken off at random, from 0 to 1, so that
+    /// clients dropped together do not all come back together.
+    pub jitter: f64,
+    /// Attempts before giving up. `None`, the default, tries forever.
+    pub max_attempts: Option<u32>,
+}
+
+impl Default for Backoff {
+    fn default() -> Self {
+        Self {
+            initial: Duration::from_millis(500),
+            max: Duration::from_secs(30),
+            multiplier: 2.0,
+            jitter: 0.5,
+            max_attempts: None,
+        }
+    }
+}
+
+impl Backoff {
+    /// Wait `initial` before the first attempt.
+    pub fn initial(mut self, initial: Duration) -> Self {
+        self.initial = initial;
+        self
+    }
+
+    /// Never wait longer than `max`.
+    pub fn max(mut self, max: Duration) -> Self {
+        self.max = max;
+        self
+    }
+
+    /// Multiply the delay by `multiplier` after each failed attempt.
+    pub fn multiplier(mut self, multiplier: f64) -> Self {
+        self.multiplier = multiplier;
+        self
+    }
+
+    /// Take up to `jitter` of each delay off at random.
+    pub fn jitter(mut self, jitter: f64) -> Self {
+        self.jitter = jitter;
+        self
+    }
+
+    /// Give up after `attempts` failed attempts in a row.
+    pub fn max_attempts(mut self, attempts: u32) -> Self {
+        self.max_attempts = Some(attempts);
+        self
+    }
+
+    /// The delay before attempt number `attempt`, counting from 1.
+    pub fn delay(&self, attempt: u32) -> Duration {
+        let exponent = attempt.saturating_sub(1).min(64) as i32;
+        let base = (self.initial.as_secs_f64() * self.multiplier.max(0.0).powi(exponent))
+            .min(self.max.as_secs_f64());
+        let jitter = self.jitter.clamp(0.0, 1.0) * random_unit();
+        // Past what a `Duration` holds only when `max` is near its limit.
+        Duration::try_from_secs_f64(base * (1.0 - jitter)).unwrap_or(self.max)
+    }
+}
+
+/// A random number in `[0, 1)`, or 0 without a source of randomness.
+fn random_unit() -> f64 {
+    getrandom::u32().map_or(0.0, |r| f64::from(r) / (f64::from(u32::MAX) + 1.0))
+}
+
+/// What [`ReconnectingConnection::recv`] yields.
+#[derive(Debug)]
+pub enum ReconnectEvent<S> {
+    /// A message from the authority, as from [`Connection::recv`].
+    Message(ServerWire<S>),
+    /// The connection is down, and attempt number `attempt` to bring it back
+    /// comes after `delay`. `error` is why the connection or the last
+    /// attempt failed; `None` if the authority simply closed it.
+    Reconnecting {
+        attempt: u32,
+        delay: Duration,
+        error: Option<ClientError>,
+    },
+    /// A new connection is up, with `snapshot` as its initial snapshot.
+    /// Queued intents have been sent on it.
+    Reconnected { snapshot: S },
+}
+
+/// A [`Connection`] that reconnects when it is lost.
+///
+/// [`recv`](Self::recv) reports the loss as
+/// [`ReconnectEvent::Reconnecting`], waits out the backoff, and opens a new
+/// connection with the factory, reporting it as
+/// [`ReconnectEvent::Reconnected`] with the fresh initial snapshot. Intents
+/// sent in the meantime are queued and sent, in order, on the new
+/// connection.
+///
+/// `recv` must be called for reconnection to happen. It is cancel-safe,
+/// like [`Connection::recv`] beneath it, so it can sit in a `select!` with
+/// other work; an attempt cut short carries on where it left off at the
+/// next call.
+pub struct ReconnectingConnection<T, I, S> {
+    connect: ConnectFactory<T, I, S>,
+    backoff: Backoff,
+    conn: Option<Connection<T, I, S>>,
+    /// Intents waiting for a connection, oldest first.
+    queue: VecDeque<I>,
+    /// Attempts since the connection was lost.
+    attempt: u32,
+    /// When the announced attempt is due.
+    retry_at: Option<Instant>,
+    /// The attempt under way, kept across cancelled calls to `recv`.
+    connecting: Option<Opening<T, I, S>>,
+    /// The new connection's snapshot, until the queue is flushed and it is
+    /// returned.
+    reconnected: Option<S>,
+    /// Why the connection or the last attempt failed, until announced.
+    error: Option<ClientError>,
+    gave_up: bool,
+}
+
+impl<T, I, S> ReconnectingConnection<T, I, S>
+where
+    T: Transport,
+    T::Error: Into<ClientError>,
+    I: Wire + Clone,
+    S: Wire,
+{
+    /// Connect with `connect`, and reconnect with it whenever the connection
+    /// is lost, waiting as `backoff` says between attempts.
+    ///
+    /// The first connection is not retried: its error is returned as is, so
+    /// a misconfigured room fails at once.
+    pub async fn connect<F, Fut, E>(connect: F, backoff: Backoff) -> Result<(Self, S), ClientError>
+    where
+        F: Fn() -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<(Connection<T, I, S>, S), E>> + Send + 'static,
+        E: Into<Box<dyn Error + Send + Sync>>,
+    {
+        let connect: ConnectFactory<T, I, S> = Arc::new(move || {
+            let opened = connect();
+            Box::pin(async move { opened.await.map_err(|e| client_error(e.into())) })
+        });
+        let (conn, snapshot) = connect().await?;
+        let conn = Self {
+            connect,
+            backoff,
+            conn: Some(conn),
+            queue: VecDeque::new(),
+            attempt: 0,
+            retry_at: None,
+            connecting: None,
+            reconnected: None,
+            error: None,
+            gave_up: false,
+        };
+        Ok((conn, snapshot))
+    }
+
+    /// Receive the next message or reconnection event.
+    ///
+    /// Returns `None` once the backoff's attempts have run out, having
+    /// returned the last attempt's error first.
+    pub async fn recv(&mut self) -> Result<Option<ReconnectEvent<S>>, ClientError> {
+        loop {
+            if self.reconnected.is_some() {
+                self.flush().await;
+                if let Some(snapshot) = self.reconnected.take() {
+                    return Ok(Some(ReconnectEvent::Reconnected { snapshot }));
+                }
+            }
+            if let Some(conn) = &mut self.conn {
+                match conn.recv().await {
+                    Ok(Some(msg)) => return Ok(Some(ReconnectEvent::Message(msg))),
+                    Ok(None) => {}
+                    Err(e) => self.error = Some(e),
+                }
+                self.conn = None;
+            }
+            if self.gave_up {
+                return Ok(None);
+            }
+
+            let Some(retry_at) = self.retry_at else {
+                self.attempt += 1;
+                let error = self.error.take();
+                if self
+                    .backoff
+                    .max_attempts
+                    .is_some_and(|max| self.attempt > max)
+                {
+                    self.gave_up = true;
+                    return Err(error.unwrap_or(ClientError::Closed));
+                }
+                let delay = self.backoff.delay(self.attempt);
+                self.retry_at = Some(Instant::now() + delay);
+                return Ok(Some(ReconnectEvent::Reconnecting {
+                    attempt: self.attempt,
+                    delay,
+                    error,
+                }));
+            };
+            if self.connecting.is_none() {
+                tokio::time::sleep_until(retry_at).await;
+            }
+            let connecting = self.connecting.get_or_insert_with(|| (self.connect)());
+            let opened = connecting.await;
+            self.connecting = None;
+            self.retry_at = None;
+            match opened {
+                Ok((conn, snapshot)) => {
+                    self.attempt = 0;
+                    self.conn = Some(conn);
+                    self.reconnected = Some(snapshot);
+                }
+                Err(e) => self.error = Some(e),
+            }
+        }
+    }
+
+    /// Send an intent to the authority, or queue it until the connection is
+    /// back.
+    pub async fn send_intent(&mut self, intent: I) -> Result<(), ClientError> {
+        if let Some(conn) = &mut self.conn
+            && self.queue.is_empty()
+        {
+            match conn.send_intent(intent.clone()).await {
+                Ok(()) => return Ok(()),
+                // Lost; `recv` will find out and reconnect.
+                Err(_) if conn.is_ghost() => {}
+                Err(e) => return Err(e),
+            }
+        }
+        self.queue.push_back(intent);
+        Ok(())
+    }
+
+    /// Send queued intents, stopping if the connection is lost. Intents it
+    /// refuses outright are dropped. Each leaves the queue only once sent,
+    /// so a cancelled flush loses none.
+    async fn flush(&mut self) {
+        let Some(conn) = &mut self.conn else {
+            return;
+        };
+        while let Some(intent) = self.queue.front() {
+            if conn.send_intent(intent.clone()).await.is_err() && conn.is_ghost() {
+                return;
+            }
+            self.queue.pop_front();
+        }
+    }
+
+    /// The current connection, unless it is down.
+    pub fn connection(&self) -> Option<&Connection<T, I, S>> {
+        self.conn.as_ref()
+    }
+
+    /// Whether a live connection is up.
+    pub fn is_connected(&self) -> bool {
+        self.conn.as_ref().is_some_and(|conn| !conn.is_ghost())
+    }
+
+    /// Number of intents waiting for a connection.
+    pub fn queued(&self) -> usize {
+        self.queue.len()
+    }
+}
+
+/// Unwrap factory errors that are already client errors.
+fn client_error(e: Box<dyn Error + Send + Sync>) -> ClientError {
+    match e.downcast::<ClientError>() {
+        Ok(e) => *e,
+        Err(e) => ClientError::Other(e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use interconnect_core::{ClientWire, Codec, Identity, Loopback, Manifest, loopback};
+    use std::sync::Mutex;
+
+    /// Intents must be maps to sit inside the tagged `ClientWire`.
+    #[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+    struct Say {
+        text: String,
+    }
+
+    fn frame(msg: &ServerWire<String>) -> Vec<u8> {
+        Codec::Json.encode(msg).unwrap()
+    }
+
+    #[test]
+    fn delays_grow_to_the_cap() {
+        let backoff = Backoff::default()
+            .initial(Duration::from_millis(100))
+            .max(Duration::from_secs(1))
+            .jitter(0.0);
+        let delays: Vec<_> = (1..=6).map(|n| backoff.delay(n).as_millis()).collect();
+        assert_eq!(delays, [100, 200, 400, 800, 1000, 1000]);
+
+        let jittered = backoff.jitter(0.5);
+        for _ in 0..20 {
+            let delay = jittered.delay(3);
+            assert!(delay > Duration::from_millis(200) && delay <= Duration::from_millis(400));
+        }
+
+        let unbounded = Backoff::default()
+            .max(Duration::MAX)
+            .multiplier(1e10)
+            .jitter(0.0);
+        assert_eq!(unbounded.delay(64), Duration::MAX);
+    }
+
+    #[tokio::test]
+    async fn reconnects_and_sends_queued_intents() {
+        // Each connection's authority end, for the test to drop or read.
+        let authorities: Arc<Mutex<Vec<Option<Loopback>>>> = Arc::default();
+        let ends = authorities.clone();
+        let connect = move || {
+            let ends = ends.clone();
+            async move {
+                let (client, mut authority) = loopback();
+                let n = ends.lock().unwrap().len();
+                let manifest = Manifest::new(Identity::local("room"), "room");
+                authority
+                    .send(&frame(&ServerWire::Manifest(manifest)))
+                    .await?;
+                let snapshot = ServerWire::Snapshot {
+                    seq: 0,
+                    data: format!("connection {n}"),
+                };
+                authority.send(&frame(&snapshot)).await?;
+                ends.lock().unwrap().push(Some(authority));
+                Connection::<_, Say, String>::connect(client, Identity::local("alice"), None, None)
+                    .await
+            }
+        };
+        let backoff = Backoff::default().initial(Duration::from_millis(1));
+        let (mut conn, snapshot) = ReconnectingConnection::connect(connect, backoff)
+            .await
+            .unwrap();
+        assert_eq!(snapshot, "connection 0");
+
+        // The authority goes away.
+        drop(authorities.lock().unwrap()[0].take());
+        assert!(matches!(
+            conn.recv().await.unwrap(),
+            Some(ReconnectEvent::Reconnecting {
+                attempt: 1,
+                error: None,
+                ..
+            })
+        ));
+        assert!(!conn.is_connected());
+        let hello = Say {
+            text: "hello".into(),
+        };
+        conn.send_intent(hello.clone()).await.unwrap();
+        assert_eq!(conn.queued(), 1);
+
+        assert!(matches!(
+            conn.recv().await.unwrap(),
+            Some(ReconnectEvent::Reconnected { snapshot }) if snapshot == "connection 1"
+        ));
+        assert!(conn.is_connected());
+        assert_eq!(conn.queued(), 0);
+
+        let mut authority = authorities.lock().unwrap()[1].take().unwrap();
+        let mut intents = Vec::new();
+        drop(conn);
+        while let Some(raw) = authority.recv().await.unwrap() {
+            if let ClientWire::Intent(intent) = Codec::Json.decode::<ClientWire<Say>>(&raw).unwrap()
+            {
+                intents.push(intent);
+            }
+        }
+        assert_eq!(intents, [hello]);
+    }
+
+    #[tokio::test]
+    async fn cancelled_recv_resumes_the_attempt() {
+        let authorities: Arc<Mutex<Vec<Option<Loopback>>>> = Arc::default();
+        let ends = authorities.clone();
+        let connect = move || {
+            let ends = ends.clone();
+            async move {
+                let n = ends.lock().unwrap().len();
+                if n > 0 {
+                    // Slow enough to be cut short.
+                    tokio::time::sleep(Duration::from_millis(100)).await;
+                }
+                let (client, mut authority) = loopback();
+                let manifest = Manifest::new(Identity::local("room"), "room");
+                authority
+                    .send(&frame(&ServerWire::Manifest(manifest)))
+                    .await?;
+                let snapshot = ServerWire::Snapshot {
+                    seq: 0,
+                    data: format!("connection {n}"),
+                };
+                authority.send(&frame(&snapshot)).await?;
+                ends.lock().unwrap().push(Some(authority));
+                Connection::<_, Say, String>::connect(client, Identity::local("alice"), None, None)
+                    .await
+            }
+        };
+        let backoff = Backoff::default().initial(Duration::from_millis(1));
+        let (mut conn, _) = ReconnectingConnection::connect(connect, backoff)
+            .await
+            .unwrap();
+
+        drop(authorities.lock().unwrap()[0].take());
+        assert!(matches!(
+            conn.recv().await.unwrap(),
+            Some(ReconnectEvent::Reconnecting { attempt: 1, .. })
+        ));
+        let hello = Say {
+            text: "hello".into(),
+        };
+        conn.send_intent(hello.clone()).await.unwrap();
+
+        // Cancel mid-attempt, as losing a `select!` would.
+        let cut_short = tokio::time::timeout(Duration::from_millis(20), conn.recv()).await;
+        assert!(cut_short.is_err());
+
+        // The same attempt finishes rather than a new one being announced.
+        assert!(matches!(
+            conn.recv().await.unwrap(),
+            Some(ReconnectEvent::Reconnected { snapshot }) if snapshot == "connection 1"
+        ));
+        assert_eq!(conn.queued(), 0);
+
+        let mut authority = authorities.lock().unwrap()[1].take().unwrap();
+        drop(conn);
+        let mut intents = Vec::new();
+        while let Some(raw) = authority.recv().await.unwrap() {
+            if let ClientWire::Intent(intent) = Codec::Json.decode::<ClientWire<Say>>(&raw).unwrap()
+            {
+                intents.push(intent);
+            }
+        }
+        assert_eq!(intents, [hello]);
+        assert_eq!(authorities.lock().unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn gives_up_after_max_attempts() {
+        let calls = Arc::new(Mutex::new(0));
+        let count = calls.clone();
+        let connect = move || {
+            let count = count.clone();
+            async move {
+                let n = {
+                    let mut calls = count.lock().unwrap();
+                    *calls += 1;
+                    *calls
+                };
+                if n > 1 {
+                    return Err(ClientError::Closed);
+                }
+                // Closes straight after the initial snapshot.
+                let (client, mut authority) = loopback();
+                let manifest = Manifest::new(Identity::local("room"), "room");
+                authority
+                    .send(&frame(&ServerWire::Manifest(manifest)))
+                    .await?;
+                let snapshot = ServerWire::Snapshot {
+                    seq: 0,
+                    data: String::new(),
+                };
+                authority.send(&frame(&snapshot)).await?;
+                Connection::<_, Say, String>::connect(client, Identity::local("alice"), None, None)
+                    .await
+            }
+        };
+        let backoff = Backoff::default()
+            .initial(Duration::from_millis(1))
+            .max_attempts(2);
+        let (mut conn, _) = ReconnectingConnection::connect(connect, backoff)
+            .await
+            .unwrap();
+
+        let mut attempts = Vec::new();
+        let error = loop {
+            match conn.recv().await {
+                Ok(Some(ReconnectEvent::Reconnecting { attempt, .. })) => attempts.push(attempt),
+                Ok(other) => panic!("unexpected {other:?}"),
+                Err(e) => break e,
+            }
+        };
+        assert_eq!(attempts, [1, 2]);
+        assert!(matches!(error, ClientError::Closed));
+        assert!(conn.recv().await.unwrap().is_none());
+        assert_eq!(*calls.lock().unwrap(), 3);
+    }
+}
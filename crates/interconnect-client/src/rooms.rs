@@ -0,0 +1,507 @@
+//! Many rooms behind one handle.
+//!
+//! A [`RoomSet`] holds connections of any intent and snapshot types, keyed
+//! by name. Each runs in its own task; their messages come out of one
+//! stream tagged by room, and intents go in as JSON, routed by room name.
+
+use crate::{ClientError, Connection, ReconnectEvent, ReconnectingConnection};
+use interconnect_core::{ServerWire, Transport, Wire};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::future::Future;
+use std::time::Duration;
+use tokio::sync::{mpsc, oneshot};
+use tokio::task::JoinHandle;
+
+/// A connection a [`RoomSet`] can hold.
+///
+/// Implemented by [`Connection`] and [`ReconnectingConnection`].
+pub trait Room: Send + 'static {
+    type Intent: Wire;
+
+    /// The next event, or `None` when the room has closed. An error also
+    /// closes the room.
+    ///
+    /// Must be cancel-safe: the set drops it to send an intent.
+    fn next_event(&mut self)
+    -> impl Future<Output = Result<Option<RoomEvent>, ClientError>> + Send;
+
+    /// Send an intent to the room's authority.
+    fn send(
+        &mut self,
+        intent: Self::Intent,
+    ) -> impl Future<Output = Result<(), ClientError>> + Send;
+}
+
+/// Something that happened in one room of a [`RoomSet`].
+#[derive(Debug)]
+pub enum RoomEvent {
+    /// A message from the room's authority, with snapshots as JSON.
+    Message(ServerWire<serde_json::Value>),
+    /// See [`ReconnectEvent::Reconnecting`].
+    Reconnecting {
+        attempt: u32,
+        delay: Duration,
+        error: Option<ClientError>,
+    },
+    /// See [`ReconnectEvent::Reconnected`].
+    Reconnected { snapshot: serde_json::Value },
+    /// The room's connection ended, with the error that ended it if any.
+    /// The room has left the set.
+    Closed { error: Option<ClientError> },
+}
+
+impl<T, I, S> Room for Connection<T, I, S>
+where
+    T: Transport + 'static,
+    T::Error: Into<ClientError>,
+    I: Wire,
+    S: Wire,
+{
+    type Intent = I;
+
+    async fn next_event(&mut self) -> Result<Option<RoomEvent>, ClientError> {
+        match self.recv().await? {
+            Some(msg) => Ok(Some(RoomEvent::Message(to_json(&msg)?))),
+            None => Ok(None),
+        }
+    }
+
+    async fn send(&mut self, intent: I) -> Result<(), ClientError> {
+        self.send_intent(intent).await
+    }
+}
+
+impl<T, I, S> Room for ReconnectingConnection<T, I, S>
+where
+    T: Transport + 'static,
+    T::Error: Into<ClientError>,
+    I: Wire + Clone,
+    S: Wire,
+{
+    type Intent = I;
+
+    async fn next_event(&mut self) -> Result<Option<RoomEvent>, ClientError> {
+        let event = match self.recv().await? {
+            Some(ReconnectEvent::Message(msg)) => RoomEvent::Message(to_json(&msg)?),
+            Some(ReconnectEvent::Reconnecting {
+                attempt,
+                delay,
+                error,
+            }) => RoomEvent::Reconnecting {
+                attempt,
+                delay,
+                error,
+            },
+            Some(ReconnectEvent::Reconnected { snapshot }) => RoomEvent::Reconnected {
+                snapshot: to_json(&snapshot)?,
+            },
+            None => return Ok(None),
+        };
+        Ok(Some(event))
+    }
+
+    async fn send(&mut self, intent: I) -> Result<(), ClientError> {
+        self.send_intent(intent).await
+    }
+}
+
+/// Re-type `value` through JSON.
+fn to_json<V: Serialize, W: serde::de::DeserializeOwned>(value: &V) -> serde_json::Result<W> {
+    serde_json::from_value(serde_json::to_value(value)?)
+}
+
+/// An intent on its way to a room's task.
+struct Command {
+    intent: serde_json::Value,
+    reply: oneshot::Sender<Result<(), ClientError>>,
+}
+
+/// An event on its way out of a room's task.
+struct Tagged {
+    /// Tells a room from an earlier one of the same name.
+    id: u64,
+    room: String,
+    event: RoomEvent,
+}
+
+struct Entry {
+    id: u64,
+    commands: mpsc::UnboundedSender<Command>,
+    task: JoinHandle<()>,
+}
+
+impl Drop for Entry {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+/// Connections to any number of rooms, keyed by name.
+///
+/// Rooms can be added and removed at any time. [`recv`](Self::recv) yields
+/// what happens in all of them, tagged with the room's name, and
+/// [`send_intent`](Self::send_intent) routes an intent to a room by name.
+/// A room added with [`insert`](Self::insert) starts with the next message
+/// after its initial snapshot, which its caller already has.
+///
+/// Must be used within a Tokio runtime.
+pub struct RoomSet {
+    rooms: HashMap<String, Entry>,
+    next_id: u64,
+    events_tx: mpsc::UnboundedSender<Tagged>,
+    events_rx: mpsc::UnboundedReceiver<Tagged>,
+}
+
+impl Default for RoomSet {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RoomSet {
+    pub fn new() -> Self {
+        let (events_tx, events_rx) = mpsc::unbounded_channel();
+        Self {
+            rooms: HashMap::new(),
+            next_id: 0,
+            events_tx,
+            events_rx,
+        }
+    }
+
+    /// Add `room` as `name`, replacing and disconnecting any room of that
+    /// name. Returns whether one was replaced.
+    pub fn insert<R: Room>(&mut self, name: impl Into<String>, room: R) -> bool {
+        let name = name.into();
+        let id = self.next_id;
+        self.next_id += 1;
+        let (commands, rx) = mpsc::unbounded_channel();
+        let task = tokio::spawn(run(room, id, name.clone(), rx, self.events_tx.clone()));
+        let entry = Entry { id, commands, task };
+        self.rooms.insert(name, entry).is_some()
+    }
+
+    /// Disconnect and forget the room `name`. Returns whether there was one.
+    pub fn remove(&mut self, name: &str) -> bool {
+        self.rooms.remove(name).is_some()
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.rooms.contains_key(name)
+    }
+
+    /// The names of the rooms in the set, in no particular order.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.rooms.keys().map(String::as_str)
+    }
+
+    pub fn len(&self) -> usize {
+        self.rooms.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rooms.is_empty()
+    }
+
+    /// Send `intent` to the room `name`, as its intent type.
+    ///
+    /// Fails with [`ClientError::UnknownRoom`] if there is no such room, and
+    /// with a codec error if `intent` is not one of the room's intents.
+    pub async fn send_intent(&self, name: &str, intent: impl Serialize) -> Result<(), ClientError> {
+        let entry = self
+            .rooms
+            .get(name)
+            .ok_or_else(|| ClientError::UnknownRoom(name.to_string()))?;
+        let (reply, result) = oneshot::channel();
+        let command = Command {
+            intent: serde_json::to_value(intent)?,
+            reply,
+        };
+        entry
+            .commands
+            .send(command)
+            .map_err(|_| ClientError::Closed)?;
+        result.await.map_err(|_| ClientError::Closed)?
+    }
+
+    /// The next event in any room, with the room's name. Returns `None`
+    /// when the set is empty.
+    ///
+    /// Cancel-safe, so rooms can be added and removed from the same
+    /// `select!` loop.
+    pub async fn recv(&mut self) -> Option<(String, RoomEvent)> {
+        loop {
+            if self.rooms.is_empty() {
+                return None;
+            }
+            let Tagged { id, room, event } = self.events_rx.recv().await?;
+            // From a room since removed or replaced.
+            if self.rooms.get(&room).is_none_or(|entry| entry.id != id) {
+                continue;
+            }
+            if let RoomEvent::Closed { .. } = event {
+                self.rooms.remove(&room);
+            }
+            return Some((room, event));
+        }
+    }
+}
+
+/// Drive `room`: events out, intents in, until it closes or leaves the set.
+async fn run<R: Room>(
+    mut room: R,
+    id: u64,
+    name: String,
+    mut commands: mpsc::UnboundedReceiver<Command>,
+    events: mpsc::UnboundedSender<Tagged>,
+) {
+    let error = loop {
+        tokio::select! {
+            event = room.next_event() => match event {
+                Ok(Some(event)) => {
+                    let _ = events.send(Tagged { id, room: name.clone(), event });
+                }
+                Ok(None) => break None,
+                Err(e) => break Some(e),
+            },
+            command = commands.recv() => {
+                let Some(Command { intent, reply }) = command else {
+                    return;
+                };
+                let result = match serde_json::from_value(intent) {
+                    Ok(intent) => room.send(intent).await,
+                    Err(e) => Err(e.into()),
+                };
+                let _ = reply.send(result);
+            }
+        }
+    };
+    let event = RoomEvent::Closed { error };
+    let _ = events.send(Tagged {
+        id,
+        room: name,
+        event,
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use interconnect_core::{ClientWire, Codec, Identity, Loopback, Manifest, loopback};
+    use std::sync::{Arc, Mutex};
+
+    /// Intents must be maps to sit inside the tagged `ClientWire`.
+    #[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+    struct Say {
+        text: String,
+    }
+
+    /// A connection to a room named `name`, and the authority's end of it.
+    async fn room(name: &str) -> (Connection<Loopback, Say, Vec<String>>, Loopback) {
+        let (client, mut authority) = loopback();
+        let manifest = Manifest::new(Identity::local(name), name);
+        let frames: [ServerWire<Vec<String>>; 2] = [
+            ServerWire::Manifest(manifest),
+            ServerWire::Snapshot {
+                seq: 0,
+                data: vec![],
+            },
+        ];
+        for frame in frames {
+            authority
+                .send(&Codec::Json.encode(&frame).unwrap())
+                .await
+                .unwrap();
+        }
+        let (conn, _) = Connection::connect(client, Identity::local("alice"), None, None)
+            .await
+            .unwrap();
+        (conn, authority)
+    }
+
+    #[tokio::test]
+    async fn events_are_tagged_and_intents_routed() {
+        let mut rooms = RoomSet::new();
+        let (general, mut general_end) = room("general").await;
+        let (random, mut random_end) = room("random").await;
+        rooms.insert("general", general);
+        rooms.insert("random", random);
+        assert_eq!(rooms.len(), 2);
+
+        let update: ServerWire<Vec<String>> = ServerWire::Snapshot {
+            seq: 1,
+            data: vec!["hi".into()],
+        };
+        random_end
+            .send(&Codec::Json.encode(&update).unwrap())
+            .await
+            .unwrap();
+        let (name, event) = rooms.recv().await.unwrap();
+        assert_eq!(name, "random");
+        assert!(matches!(
+            event,
+            RoomEvent::Message(ServerWire::Snapshot { data, .. }) if data == serde_json::json!(["hi"])
+        ));
+
+        rooms
+            .send_intent("general", Say { text: "yo".into() })
+            .await
+            .unwrap();
+        let intent = loop {
+            let raw = general_end.recv().await.unwrap().unwrap();
+            if let ClientWire::Intent(intent) = Codec::Json.decode::<ClientWire<Say>>(&raw).unwrap()
+            {
+                break intent;
+            }
+        };
+        assert_eq!(intent.text, "yo");
+        assert!(matches!(
+            rooms.send_intent("nowhere", Say { text: "?".into() }).await,
+            Err(ClientError::UnknownRoom(_))
+        ));
+        assert!(matches!(
+            rooms.send_intent("general", "not an intent").await,
+            Err(ClientError::Codec(_))
+        ));
+
+        // Removing a room disconnects it; closing one takes it out.
+        assert!(rooms.remove("general"));
+        assert!(general_end.recv().await.unwrap().is_none());
+        drop(random_end);
+        let (name, event) = rooms.recv().await.unwrap();
+        assert_eq!(name, "random");
+        assert!(matches!(event, RoomEvent::Closed { error: None }));
+        assert!(rooms.is_empty());
+        assert!(rooms.recv().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn intents_do_not_interrupt_reconnects() {
+        // Each connection's authority end; those after the first are slow.
+        let authorities: Arc<Mutex<Vec<Option<Loopback>>>> = Arc::default();
+        let ends = authorities.clone();
+        let connect = move || {
+            let ends = ends.clone();
+            async move {
+                if !ends.lock().unwrap().is_empty() {
+                    tokio::time::sleep(Duration::from_millis(100)).await;
+                }
+                let (conn, authority) = room("lobby").await;
+                ends.lock().unwrap().push(Some(authority));
+                Ok::<_, ClientError>((conn, vec![]))
+            }
+        };
+        let backoff = crate::Backoff::default().initial(Duration::from_millis(1));
+        let (conn, _) = ReconnectingConnection::connect(connect, backoff)
+            .await
+            .unwrap();
+        let mut rooms = RoomSet::new();
+        rooms.insert("lobby", conn);
+
+        drop(authorities.lock().unwrap()[0].take());
+        assert!(matches!(
+            rooms.recv().await.unwrap().1,
+            RoomEvent::Reconnecting { attempt: 1, .. }
+        ));
+        // Routed while the attempt is under way.
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        rooms
+            .send_intent("lobby", Say { text: "yo".into() })
+            .await
+            .unwrap();
+
+        assert!(matches!(
+            rooms.recv().await.unwrap().1,
+            RoomEvent::Reconnected { .. }
+        ));
+        let mut authority = authorities.lock().unwrap()[1].take().unwrap();
+        let intent = loop {
+            let raw = authority.recv().await.unwrap().unwrap();
+            if let ClientWire::Intent(intent) = Codec::Json.decode::<ClientWire<Say>>(&raw).unwrap()
+            {
+                break intent;
+            }
+        };
+        assert_eq!(intent.text, "yo");
+        assert_eq!(authorities.lock().unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn intents_do_not_interrupt_acknowledgements() {
+        /// Takes its time over every send.
+        struct Slow(Loopback);
+
+        impl Transport for Slow {
+            type Error = <Loopback as Transport>::Error;
+
+            async fn send(&mut self, data: &[u8]) -> Result<(), Self::Error> {
+                tokio::time::sleep(Duration::from_millis(50)).await;
+                self.0.send(data).await
+            }
+
+            async fn recv(&mut self) -> Result<Option<Vec<u8>>, Self::Error> {
+                self.0.recv().await
+            }
+        }
+
+        let (client, mut authority) = loopback();
+        let manifest = Manifest::new(Identity::local("lobby"), "lobby");
+        let frames: [ServerWire<Vec<String>>; 2] = [
+            ServerWire::Manifest(manifest),
+            ServerWire::Snapshot {
+                seq: 0,
+                data: vec![],
+            },
+        ];
+        for frame in frames {
+            authority
+                .send(&Codec::Json.encode(&frame).unwrap())
+                .await
+                .unwrap();
+        }
+        let (conn, _) = Connection::<_, Say, Vec<String>>::connect(
+            Slow(client),
+            Identity::local("alice"),
+            None,
+            None,
+        )
+        .await
+        .unwrap();
+        let mut rooms = RoomSet::new();
+        rooms.insert("lobby", conn);
+
+        let update: ServerWire<Vec<String>> = ServerWire::Snapshot {
+            seq: 1,
+            data: vec!["hi".into()],
+        };
+        authority
+            .send(&Codec::Json.encode(&update).unwrap())
+            .await
+            .unwrap();
+        // Routed while the snapshot's acknowledgement is going out.
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        rooms
+            .send_intent("lobby", Say { text: "yo".into() })
+            .await
+            .unwrap();
+
+        let (_, event) = tokio::time::timeout(Duration::from_secs(1), rooms.recv())
+            .await
+            .expect("the snapshot was lost")
+            .unwrap();
+        assert!(matches!(
+            event,
+            RoomEvent::Message(ServerWire::Snapshot { seq: 1, .. })
+        ));
+        let (mut acked, mut said) = (false, false);
+        while !(acked && said) {
+            let raw = authority.recv().await.unwrap().unwrap();
+            match Codec::Json.decode::<ClientWire<Say>>(&raw).unwrap() {
+                ClientWire::Ack { seq: 1 } => acked = true,
+                ClientWire::Intent(Say { text }) => said = text == "yo",
+                _ => {}
+            }
+        }
+    }
+}
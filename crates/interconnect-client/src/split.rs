@@ -0,0 +1,250 @@
+//! A [`Connection`](crate::Connection) split into halves for separate tasks.
+
+use crate::connection::rebuild;
+use crate::heartbeat::{Beat, Pulse};
+use crate::{ClientError, StateObserver};
+use futures_util::{Sink, Stream};
+use interconnect_core::{
+    ClientWire, Codec, ConnectionState, DeltaDecoder, Manifest, Protocol, RecvHalf, SendHalf,
+    ServerWire, SplitTransport, Wire,
+};
+use std::collections::VecDeque;
+use std::marker::PhantomData;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+use tokio::sync::Mutex;
+
+/// The sending half of a split connection, from
+/// [`Connection::split`](crate::Connection::split).
+///
+/// Cheap to clone; clones share the transport.
+pub struct ConnectionSender<T: SplitTransport, I> {
+    sender: Arc<Mutex<T::Sender>>,
+    pub(crate) codec: Codec,
+    /// Set once either half has lost the authority.
+    ghost: Arc<AtomicBool>,
+    _phantom: PhantomData<fn(I)>,
+}
+
+impl<T: SplitTransport, I> Clone for ConnectionSender<T, I> {
+    fn clone(&self) -> Self {
+        Self {
+            sender: self.sender.clone(),
+            codec: self.codec,
+            ghost: self.ghost.clone(),
+            _phantom: PhantomData,
+        }
+    }
+}
+
+impl<T, I> ConnectionSender<T, I>
+where
+    T: SplitTransport,
+    T::Error: Into<ClientError>,
+    I: Wire,
+{
+    pub(crate) fn new(sender: T::Sender, codec: Codec, ghost: bool) -> Self {
+        Self {
+            sender: Arc::new(Mutex::new(sender)),
+            codec,
+            ghost: Arc::new(AtomicBool::new(ghost)),
+            _phantom: PhantomData,
+        }
+    }
+
+    /// Send an intent to the authority.
+    ///
+    /// Fails with [`ClientError::Ghost`] once the authority is lost.
+    pub async fn send_intent(&self, intent: I) -> Result<(), ClientError> {
+        self.send(ClientWire::Intent(intent)).await
+    }
+
+    /// Send a ping.
+    pub async fn ping(&self) -> Result<(), ClientError> {
+        self.send(ClientWire::Ping).await
+    }
+
+    /// Request transfer to another authority.
+    pub async fn request_transfer(&self, destination: String) -> Result<(), ClientError> {
+        self.send(ClientWire::TransferRequest { destination }).await
+    }
+
+    /// Whether the authority has been lost.
+    pub fn is_ghost(&self) -> bool {
+        self.ghost.load(Ordering::Relaxed)
+    }
+
+    /// A [`Sink`] of intents, each sent with
+    /// [`send_intent`](Self::send_intent). Pin it to use `SinkExt`.
+    pub fn into_sink(self) -> impl Sink<I, Error = ClientError> {
+        futures_util::sink::unfold(self, |sender, intent| async move {
+            sender.send_intent(intent).await?;
+            Ok(sender)
+        })
+    }
+
+    pub(crate) async fn send(&self, msg: ClientWire<I>) -> Result<(), ClientError> {
+        if self.is_ghost() {
+            return Err(ClientError::Ghost);
+        }
+        let bytes = self.codec.encode(&msg)?;
+        if let Err(e) = self.sender.lock().await.send(&bytes).await {
+            self.ghost.store(true, Ordering::Relaxed);
+            return Err(e.into());
+        }
+        Ok(())
+    }
+}
+
+/// The receiving half of a split connection, from
+/// [`Connection::split`](crate::Connection::split).
+///
+/// Behaves like [`Connection::recv`](crate::Connection::recv): snapshots are
+/// acknowledged and deltas rebuilt, the heartbeat pings through the sender,
+/// and the connection enters ghost mode when the transport closes or fails
+/// or the authority falls silent. `recv` is cancel-safe.
+pub struct ConnectionReceiver<T: SplitTransport, I, S> {
+    pub(crate) receiver: T::Receiver,
+    /// Acknowledges snapshots.
+    pub(crate) sender: ConnectionSender<T, I>,
+    pub(crate) manifest: Manifest,
+    pub(crate) deltas: DeltaDecoder,
+    pub(crate) substrate: Option<Vec<u8>>,
+    pub(crate) state: ConnectionState,
+    pub(crate) on_state: Option<StateObserver>,
+    pub(crate) pending: VecDeque<ServerWire<S>>,
+    /// A snapshot acknowledgement a cancelled `recv` left unsent.
+    pub(crate) ack_due: Option<u64>,
+    pub(crate) pulse: Option<Pulse>,
+}
+
+impl<T, I, S> ConnectionReceiver<T, I, S>
+where
+    T: SplitTransport,
+    T::Error: Into<ClientError>,
+    I: Wire,
+    S: Wire,
+{
+    /// Receive the next message from the authority. Returns `None` when the
+    /// connection is closed, and from then on.
+    pub async fn recv(&mut self) -> Result<Option<ServerWire<S>>, ClientError> {
+        loop {
+            if self.state == ConnectionState::Ghost || self.sender.is_ghost() {
+                self.ack_due = None;
+            }
+            if let Some(seq) = self.ack_due {
+                self.sender.send(ClientWire::Ack { seq }).await?;
+                self.ack_due = None;
+            }
+            if let Some(msg) = self.pending.pop_front() {
+                return Ok(Some(msg));
+            }
+            if self.state == ConnectionState::Ghost {
+                return Ok(None);
+            }
+            let raw = match self.recv_frame().await {
+                Ok(Some(raw)) => raw,
+                Ok(None) => {
+                    self.set_ghost();
+                    return Ok(None);
+                }
+                Err(e) => {
+                    self.set_ghost();
+                    return Err(e);
+                }
+            };
+            let (msg, ack) = rebuild(&mut self.deltas, self.sender.codec.decode(&raw)?)?;
+            if let (ServerWire::Pong, Some(pulse)) = (&msg, &mut self.pulse) {
+                pulse.ponged();
+                continue;
+            }
+            // Held until acknowledged, so a cancelled call loses neither.
+            self.pending.push_back(msg);
+            self.ack_due = ack.or(self.ack_due);
+        }
+    }
+
+    async fn recv_frame(&mut self) -> Result<Option<Vec<u8>>, ClientError> {
+        loop {
+            let Some(pulse) = &mut self.pulse else {
+                return self.receiver.recv().await.map_err(Into::into);
+            };
+            match pulse.wait(self.receiver.recv()).await {
+                Ok(frame) => return frame.map_err(Into::into),
+                Err(Beat::Silent(timeout)) => return Err(ClientError::Timeout(timeout)),
+                Err(Beat::Ping) => {
+                    self.sender.send(ClientWire::Ping).await?;
+                    pulse.pinged();
+                }
+            }
+        }
+    }
+
+    /// The round trip of the last heartbeat ping the authority answered.
+    pub fn latency(&self) -> Option<Duration> {
+        self.pulse.as_ref().and_then(Pulse::latency)
+    }
+
+    /// A [`Stream`] of what [`recv`](Self::recv) returns, ending when the
+    /// connection closes. Pin it to use `StreamExt`.
+    pub fn into_stream(self) -> impl Stream<Item = Result<ServerWire<S>, ClientError>> {
+        futures_util::stream::unfold(self, |mut receiver| async move {
+            match receiver.recv().await {
+                Ok(Some(msg)) => Some((Ok(msg), receiver)),
+                Ok(None) => None,
+                Err(e) => Some((Err(e), receiver)),
+            }
+        })
+    }
+
+    fn set_ghost(&mut self) {
+        self.sender.ghost.store(true, Ordering::Relaxed);
+        if self.state != ConnectionState::Ghost {
+            self.state = ConnectionState::Ghost;
+            if let Some(f) = &self.on_state {
+                f(ConnectionState::Ghost);
+            }
+        }
+    }
+
+    /// Report state changes to `f`.
+    pub fn on_state(&mut self, f: impl Fn(ConnectionState) + Send + Sync + 'static) {
+        self.on_state = Some(Arc::new(f));
+    }
+
+    /// The current connection state: `Live`, or `Ghost` once the authority
+    /// is lost.
+    pub fn state(&self) -> ConnectionState {
+        self.state
+    }
+
+    /// Whether the authority has been lost.
+    pub fn is_ghost(&self) -> bool {
+        self.state == ConnectionState::Ghost
+    }
+
+    /// The most recent snapshot and its sequence number, if one has been
+    /// received. Still available in ghost mode.
+    pub fn last_snapshot(&self) -> Result<Option<(u64, S)>, serde_json::Error> {
+        self.deltas
+            .latest()
+            .map(|(seq, state)| Ok((seq, S::deserialize(state)?)))
+            .transpose()
+    }
+
+    /// The manifest received during the handshake.
+    pub fn manifest(&self) -> &Manifest {
+        &self.manifest
+    }
+
+    /// The protocol version and features negotiated in the handshake.
+    pub fn protocol(&self) -> Option<&Protocol> {
+        self.manifest.protocol.as_ref()
+    }
+
+    /// The room's substrate, if one was loaded.
+    pub fn substrate(&self) -> Option<&[u8]> {
+        self.substrate.as_deref()
+    }
+}
@@ -1,14 +1,28 @@
 //! WebSocket transport implementation.
 
 use crate::ClientError;
-use futures_util::{SinkExt, StreamExt};
-use interconnect_core::Transport;
+use futures_util::stream::{SplitSink, SplitStream};
+use futures_util::{SinkExt, Stream, StreamExt};
+use interconnect_core::{RecvHalf, SendHalf, SplitTransport, Transport};
 use tokio::net::TcpStream;
-use tokio_tungstenite::{MaybeTlsStream, WebSocketStream, connect_async, tungstenite::Message};
+use tokio_tungstenite::tungstenite::{Error, Message};
+use tokio_tungstenite::{MaybeTlsStream, WebSocketStream, connect_async};
+
+type Ws = WebSocketStream<MaybeTlsStream<TcpStream>>;
 
 /// A WebSocket-backed transport.
 pub struct WsTransport {
-    inner: WebSocketStream<MaybeTlsStream<TcpStream>>,
+    inner: Ws,
+}
+
+/// The sending half of a split [`WsTransport`].
+pub struct WsSender {
+    inner: SplitSink<Ws, Message>,
+}
+
+/// The receiving half of a split [`WsTransport`].
+pub struct WsReceiver {
+    inner: SplitStream<Ws>,
 }
 
 impl WsTransport {
@@ -20,25 +34,65 @@ impl WsTransport {
 }
 
 impl Transport for WsTransport {
-    type Error = tokio_tungstenite::tungstenite::Error;
-
-    async fn send(&mut self, data: &[u8]) -> Result<(), Self::Error> {
-        let text = String::from_utf8_lossy(data).into_owned();
-        self.inner.send(Message::Text(text.into())).await
-    }
-
-    async fn recv(&mut self) -> Result<Option<Vec<u8>>, Self::Error> {
-        loop {
-            match self.inner.next().await {
-                None => return Ok(None),
-                Some(Err(e)) => return Err(e),
-                Some(Ok(Message::Text(t))) => return Ok(Some(t.as_bytes().to_vec())),
-                Some(Ok(Message::Binary(b))) => return Ok(Some(b.into())),
-                // tungstenite handles Ping/Pong automatically; skip control frames.
-                Some(Ok(Message::Ping(_) | Message::Pong(_))) => continue,
-                Some(Ok(Message::Close(_))) => return Ok(None),
-                Some(Ok(_)) => continue,
-            }
+    type Error = Error;
+
+    async fn send(&mut self, data: &[u8]) -> Result<(), Error> {
+        self.inner.send(message(data)).await
+    }
+
+    async fn recv(&mut self) -> Result<Option<Vec<u8>>, Error> {
+        next_frame(&mut self.inner).await
+    }
+}
+
+impl SplitTransport for WsTransport {
+    type Sender = WsSender;
+    type Receiver = WsReceiver;
+
+    fn split(self) -> (WsSender, WsReceiver) {
+        let (sink, stream) = self.inner.split();
+        (WsSender { inner: sink }, WsReceiver { inner: stream })
+    }
+}
+
+impl SendHalf for WsSender {
+    type Error = Error;
+
+    async fn send(&mut self, data: &[u8]) -> Result<(), Error> {
+        self.inner.send(message(data)).await
+    }
+}
+
+impl RecvHalf for WsReceiver {
+    type Error = Error;
+
+    async fn recv(&mut self) -> Result<Option<Vec<u8>>, Error> {
+        next_frame(&mut self.inner).await
+    }
+}
+
+/// JSON goes out as text frames (what browsers expect); binary codecs as
+/// binary frames.
+fn message(data: &[u8]) -> Message {
+    match std::str::from_utf8(data) {
+        Ok(text) => Message::Text(text.into()),
+        Err(_) => Message::Binary(data.to_vec().into()),
+    }
+}
+
+async fn next_frame(
+    stream: &mut (impl Stream<Item = Result<Message, Error>> + Unpin),
+) -> Result<Option<Vec<u8>>, Error> {
+    loop {
+        match stream.next().await {
+            None => return Ok(None),
+            Some(Err(e)) => return Err(e),
+            Some(Ok(Message::Text(t))) => return Ok(Some(t.as_bytes().to_vec())),
+            Some(Ok(Message::Binary(b))) => return Ok(Some(b.into())),
+            // tungstenite handles Ping/Pong automatically; skip control frames.
+            Some(Ok(Message::Ping(_) | Message::Pong(_))) => continue,
+            Some(Ok(Message::Close(_))) => return Ok(None),
+            Some(Ok(_)) => continue,
         }
     }
 }
@@ -0,0 +1,180 @@
+//! Challenge-response authentication.
+//!
+//! `ClientWire::Auth` only *claims* an identity. For cryptographic identities
+//! the authority can ask the client to prove it holds the key:
+//!
+//! ```text
+//! Client → Authority: Auth { identity: "ed25519:…" }
+//! Authority → Client: Challenge { nonce, authority }
+//! Client → Authority: ChallengeResponse { signature }
+//! Authority → Client: Manifest
+//! ```
+//!
+//! The client signs [`challenge_message`]`(authority, nonce)`, never the raw
+//! nonce, so a challenge signature cannot be replayed as a signature over
+//! anything else. Naming the authority keeps a malicious authority from
+//! relaying another's challenge: the signature only verifies at the authority
+//! the client thought it was answering.
+
+use crate::{Identity, KeyError};
+
+/// Domain separator prepended to the nonce before signing.
+const CHALLENGE_CONTEXT: &[u8] = b"interconnect-auth-challenge:";
+
+/// The bytes a client signs to answer `authority`'s challenge.
+pub fn challenge_message(authority: &Identity, nonce: &[u8]) -> Vec<u8> {
+    let authority = authority.to_string();
+    let mut msg = Vec::with_capacity(CHALLENGE_CONTEXT.len() + authority.len() + 1 + nonce.len());
+    msg.extend_from_slice(CHALLENGE_CONTEXT);
+    msg.extend_from_slice(authority.as_bytes());
+    // Identities never contain a newline, so the nonce starts unambiguously.
+    msg.push(b'\n');
+    msg.extend_from_slice(nonce);
+    msg
+}
+
+/// A pending challenge issued by an authority.
+#[derive(Debug, Clone)]
+pub struct Challenge {
+    nonce: [u8; 32],
+}
+
+impl Challenge {
+    /// Create a challenge with a fresh random nonce.
+    pub fn generate() -> Result<Self, KeyError> {
+        let mut nonce = [0u8; 32];
+        getrandom::fill(&mut nonce).map_err(|e| KeyError::Random(e.to_string()))?;
+        Ok(Self { nonce })
+    }
+
+    /// The nonce to send in `ServerWire::Challenge`.
+    pub fn nonce(&self) -> &[u8] {
+        &self.nonce
+    }
+
+    /// Check the client's `ChallengeResponse` to `authority` against the
+    /// claimed identity.
+    pub fn verify(
+        &self,
+        authority: &Identity,
+        identity: &Identity,
+        signature: &[u8],
+    ) -> Result<(), KeyError> {
+        identity.verify(&challenge_message(authority, &self.nonce), signature)
+    }
+}
+
+/// How an authority treats the identity a client claims in `Auth`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum AuthPolicy {
+    /// Believe every claimed identity. Dev/LAN only.
+    TrustAll,
+    /// Challenge cryptographic identities; accept others as claimed.
+    #[default]
+    VerifyCryptographic,
+    /// Reject non-cryptographic identities; challenge the rest.
+    RequireCryptographic,
+}
+
+impl AuthPolicy {
+    /// Decide what to do with a claimed identity.
+    ///
+    /// Returns `Ok(Some(challenge))` when the client must prove the identity,
+    /// `Ok(None)` when it can be admitted as claimed.
+    pub fn check(&self, identity: &Identity) -> Result<Option<Challenge>, AuthError> {
+        match self {
+            AuthPolicy::TrustAll => Ok(None),
+            _ if identity.is_cryptographic() => Ok(Some(Challenge::generate()?)),
+            AuthPolicy::VerifyCryptographic => Ok(None),
+            AuthPolicy::RequireCryptographic => {
+                Err(AuthError::Unverifiable(identity.scheme().to_string()))
+            }
+        }
+    }
+}
+
+/// Why an authority refused a client's identity.
+#[derive(Debug, thiserror::Error)]
+pub enum AuthError {
+    #[error("identity scheme '{0}' is not accepted here")]
+    Unverifiable(String),
+    #[error("identity proof failed: {0}")]
+    Key(#[from] KeyError),
+    #[error("expected challenge response")]
+    NoResponse,
+}
+
+impl AuthError {
+    /// Error code for `ServerWire::Error`.
+    pub fn code(&self) -> &'static str {
+        match self {
+            AuthError::Unverifiable(_) => "identity_rejected",
+            AuthError::Key(_) | AuthError::NoResponse => "auth_failed",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Keypair;
+
+    #[test]
+    fn keypair_answers_challenge() {
+        let keypair = Keypair::generate().unwrap();
+        let challenge = AuthPolicy::default()
+            .check(&keypair.identity())
+            .unwrap()
+            .expect("cryptographic identities are challenged");
+
+        let authority = Identity::local("room");
+        let signature = keypair.sign(&challenge_message(&authority, challenge.nonce()));
+        challenge
+            .verify(&authority, &keypair.identity(), &signature)
+            .unwrap();
+    }
+
+    #[test]
+    fn raw_nonce_signature_rejected() {
+        let keypair = Keypair::generate().unwrap();
+        let challenge = Challenge::generate().unwrap();
+        let signature = keypair.sign(challenge.nonce());
+        let authority = Identity::local("room");
+        assert!(
+            challenge
+                .verify(&authority, &keypair.identity(), &signature)
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn relayed_challenge_rejected() {
+        // A client answering what it took for Mallory's challenge...
+        let keypair = Keypair::generate().unwrap();
+        let challenge = Challenge::generate().unwrap();
+        let mallory = Identity::local("mallory");
+        let signature = keypair.sign(&challenge_message(&mallory, challenge.nonce()));
+        // ...cannot be used to log in to the room that issued it.
+        let room = Identity::local("room");
+        assert!(
+            challenge
+                .verify(&room, &keypair.identity(), &signature)
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn policies() {
+        let local = Identity::local("alice");
+        assert!(AuthPolicy::TrustAll.check(&local).unwrap().is_none());
+        assert!(AuthPolicy::VerifyCryptographic.check(&local).unwrap().is_none());
+        assert!(matches!(
+            AuthPolicy::RequireCryptographic.check(&local),
+            Err(AuthError::Unverifiable(_))
+        ));
+
+        let keyed = Keypair::from_seed([1; 32]).identity();
+        assert!(AuthPolicy::TrustAll.check(&keyed).unwrap().is_none());
+        assert!(AuthPolicy::RequireCryptographic.check(&keyed).unwrap().is_some());
+    }
+}
@@ -202,3 +202,110 @@ where
         SimpleAuthority::validate_destination(self, destination)
     }
 }
+
+/// Trait for authorities whose hooks need to await I/O.
+///
+/// The same lifecycle as [`Authority`], but every method returns a future, so
+/// `handle_intent` can write to a subprocess or query a database. Every
+/// [`Authority`] (and so every [`SimpleAuthority`]) is also an
+/// `AsyncAuthority`, so runtimes only need to drive this trait.
+///
+/// Runtimes hold an exclusive lock on the authority while a `&mut self`
+/// method runs; keep those awaits short.
+pub trait AsyncAuthority: Send + Sync {
+    /// Intent type (client requests).
+    type Intent: Send;
+    /// Snapshot type (server broadcasts).
+    type Snapshot: Send;
+    /// Passport type (transfer data).
+    type Passport: Send;
+    /// Error type.
+    type Error: std::error::Error + Send + Sync + 'static;
+
+    /// Called when a new session connects (without transfer).
+    fn on_connect(
+        &mut self,
+        session: &Session,
+    ) -> impl Future<Output = Result<(), Self::Error>> + Send;
+
+    /// Called when a session transfers in from another server.
+    fn on_transfer_in(
+        &mut self,
+        session: &Session,
+        passport: Self::Passport,
+    ) -> impl Future<Output = Result<ImportResult<Self::Passport>, Self::Error>> + Send;
+
+    /// Called when a session disconnects.
+    fn on_disconnect(&mut self, session: &Session) -> impl Future<Output = ()> + Send;
+
+    /// Handle an intent from a session.
+    fn handle_intent(
+        &mut self,
+        session: &Session,
+        intent: Self::Intent,
+    ) -> impl Future<Output = Result<(), Self::Error>> + Send;
+
+    /// Generate a snapshot for a specific session.
+    fn snapshot_for(&self, session: &Session) -> impl Future<Output = Self::Snapshot> + Send;
+
+    /// Generate a passport for a session that's transferring out.
+    fn emit_passport(&self, session: &Session) -> impl Future<Output = Self::Passport> + Send;
+
+    /// Check if a transfer destination is valid.
+    fn validate_destination(&self, destination: &str) -> impl Future<Output = bool> + Send;
+}
+
+// Blanket implementation: Authority -> AsyncAuthority. Each hook runs to
+// completion when called and returns a ready future.
+impl<T> AsyncAuthority for T
+where
+    T: Authority,
+    T::Intent: Send,
+    T::Snapshot: Send,
+    T::Passport: Send,
+{
+    type Intent = T::Intent;
+    type Snapshot = T::Snapshot;
+    type Passport = T::Passport;
+    type Error = T::Error;
+
+    fn on_connect(
+        &mut self,
+        session: &Session,
+    ) -> impl Future<Output = Result<(), Self::Error>> + Send {
+        std::future::ready(Authority::on_connect(self, session))
+    }
+
+    fn on_transfer_in(
+        &mut self,
+        session: &Session,
+        passport: Self::Passport,
+    ) -> impl Future<Output = Result<ImportResult<Self::Passport>, Self::Error>> + Send {
+        std::future::ready(Authority::on_transfer_in(self, session, passport))
+    }
+
+    fn on_disconnect(&mut self, session: &Session) -> impl Future<Output = ()> + Send {
+        Authority::on_disconnect(self, session);
+        std::future::ready(())
+    }
+
+    fn handle_intent(
+        &mut self,
+        session: &Session,
+        intent: Self::Intent,
+    ) -> impl Future<Output = Result<(), Self::Error>> + Send {
+        std::future::ready(Authority::handle_intent(self, session, intent))
+    }
+
+    fn snapshot_for(&self, session: &Session) -> impl Future<Output = Self::Snapshot> + Send {
+        std::future::ready(Authority::snapshot_for(self, session))
+    }
+
+    fn emit_passport(&self, session: &Session) -> impl Future<Output = Self::Passport> + Send {
+        std::future::ready(Authority::emit_passport(self, session))
+    }
+
+    fn validate_destination(&self, destination: &str) -> impl Future<Output = bool> + Send {
+        std::future::ready(Authority::validate_destination(self, destination))
+    }
+}
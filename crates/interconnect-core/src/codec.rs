@@ -0,0 +1,252 @@
+//! Wire codecs.
+//!
+//! A codec turns `ClientWire`/`ServerWire` messages into the bytes a
+//! [`Transport`](crate::Transport) moves, and back. JSON is the default and
+//! what browsers speak; MessagePack and CBOR are compact binary alternatives
+//! for byte-heavy payloads (passports) and high-rate snapshots.
+//!
+//! Codec choice happens at handshake time: the client encodes its `Auth`
+//! message with the codec it wants, the authority recognises it with
+//! [`Codec::detect`], and both sides use that codec for the rest of the
+//! connection.
+
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+use std::fmt;
+use std::str::FromStr;
+
+/// A wire encoding.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Codec {
+    /// JSON (text). Human-readable, browser-friendly.
+    #[default]
+    Json,
+    /// MessagePack (binary).
+    #[serde(rename = "msgpack")]
+    MessagePack,
+    /// CBOR, RFC 8949 (binary).
+    Cbor,
+}
+
+impl Codec {
+    /// Every supported codec.
+    pub const ALL: [Codec; 3] = [Codec::Json, Codec::MessagePack, Codec::Cbor];
+
+    /// Short name used in config and negotiation (`json`, `msgpack`, `cbor`).
+    pub fn name(self) -> &'static str {
+        match self {
+            Codec::Json => "json",
+            Codec::MessagePack => "msgpack",
+            Codec::Cbor => "cbor",
+        }
+    }
+
+    /// Whether encoded messages are binary (not valid UTF-8 text in general).
+    ///
+    /// Text-framed transports (WebSocket text frames, line-delimited streams)
+    /// should only carry non-binary codecs.
+    pub fn is_binary(self) -> bool {
+        !matches!(self, Codec::Json)
+    }
+
+    /// Serialize a message.
+    pub fn encode<T: Serialize>(self, msg: &T) -> Result<Vec<u8>, CodecError> {
+        match self {
+            Codec::Json => Ok(serde_json::to_vec(msg)?),
+            // Named (map) encoding: wire enums are internally tagged, which
+            // needs field names to round-trip.
+            Codec::MessagePack => {
+                rmp_serde::to_vec_named(msg).map_err(|e| CodecError::Encode(self, e.to_string()))
+            }
+            Codec::Cbor => {
+                let mut buf = Vec::new();
+                ciborium::into_writer(msg, &mut buf)
+                    .map_err(|e| CodecError::Encode(self, e.to_string()))?;
+                Ok(buf)
+            }
+        }
+    }
+
+    /// Deserialize a message.
+    pub fn decode<T: DeserializeOwned>(self, data: &[u8]) -> Result<T, CodecError> {
+        match self {
+            Codec::Json => Ok(serde_json::from_slice(data)?),
+            Codec::MessagePack => {
+                rmp_serde::from_slice(data).map_err(|e| CodecError::Decode(self, e.to_string()))
+            }
+            Codec::Cbor => {
+                ciborium::from_reader(data).map_err(|e| CodecError::Decode(self, e.to_string()))
+            }
+        }
+    }
+
+    /// Guess the codec of an encoded wire message from its first byte.
+    ///
+    /// Every wire message is a map, so the leading byte is unambiguous:
+    /// `{` (or whitespace) for JSON, a map marker for MessagePack or CBOR.
+    /// Authorities call this on the client's first frame to pick a codec.
+    pub fn detect(data: &[u8]) -> Option<Codec> {
+        match *data.first()? {
+            b'{' | b' ' | b'\t' | b'\r' | b'\n' => Some(Codec::Json),
+            // fixmap, map16, map32
+            0x80..=0x8f | 0xde | 0xdf => Some(Codec::MessagePack),
+            // major type 5 (map), including indefinite-length
+            0xa0..=0xbb | 0xbf => Some(Codec::Cbor),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Codec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for Codec {
+    type Err = CodecError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "json" => Ok(Codec::Json),
+            "msgpack" | "messagepack" => Ok(Codec::MessagePack),
+            "cbor" => Ok(Codec::Cbor),
+            other => Err(CodecError::Unknown(other.to_string())),
+        }
+    }
+}
+
+/// Error encoding or decoding a wire message.
+#[derive(Debug, thiserror::Error)]
+pub enum CodecError {
+    #[error("json: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("{0} encode: {1}")]
+    Encode(Codec, String),
+    #[error("{0} decode: {1}")]
+    Decode(Codec, String),
+    #[error("unknown codec: {0}")]
+    Unknown(String),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ClientWire, Identity, Manifest, ServerWire};
+
+    #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+    #[serde(tag = "action", rename_all = "snake_case")]
+    enum TestIntent {
+        Move { x: i32, y: i32 },
+    }
+
+    #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+    struct TestSnapshot {
+        tick: u64,
+        players: Vec<String>,
+    }
+
+    #[test]
+    fn auth_with_passport_roundtrips() {
+        for codec in Codec::ALL {
+            let msg: ClientWire<TestIntent> = ClientWire::Auth {
+                identity: Identity::local("alice"),
+                name: Some("Alice".into()),
+                passport: Some(vec![0, 1, 2, 255]),
+                capabilities: Some(crate::Capabilities::current()),
+                nonce: None,
+                resume: Some(crate::Resume {
+                    token: vec![9; 32],
+                    seq: 7,
+                }),
+            };
+            let bytes = codec.encode(&msg).unwrap();
+            assert_eq!(Codec::detect(&bytes), Some(codec));
+
+            match codec.decode::<ClientWire<TestIntent>>(&bytes).unwrap() {
+                ClientWire::Auth {
+                    identity,
+                    passport,
+                    capabilities,
+                    resume,
+                    ..
+                } => {
+                    assert_eq!(identity, Identity::local("alice"));
+                    assert_eq!(passport, Some(vec![0, 1, 2, 255]));
+                    assert_eq!(capabilities, Some(crate::Capabilities::current()));
+                    assert_eq!(resume.map(|r| r.seq), Some(7));
+                }
+                _ => panic!("wrong variant for {codec}"),
+            }
+        }
+    }
+
+    #[test]
+    fn intent_and_snapshot_roundtrip() {
+        for codec in Codec::ALL {
+            let msg: ClientWire<TestIntent> = ClientWire::Intent(TestIntent::Move { x: -1, y: 2 });
+            let parsed: ClientWire<TestIntent> =
+                codec.decode(&codec.encode(&msg).unwrap()).unwrap();
+            assert!(matches!(
+                parsed,
+                ClientWire::Intent(TestIntent::Move { x: -1, y: 2 })
+            ));
+
+            let msg: ServerWire<TestSnapshot> = ServerWire::Snapshot {
+                seq: 7,
+                data: TestSnapshot {
+                    tick: 3,
+                    players: vec!["bob".into()],
+                },
+            };
+            let parsed: ServerWire<TestSnapshot> =
+                codec.decode(&codec.encode(&msg).unwrap()).unwrap();
+            match parsed {
+                ServerWire::Snapshot { seq, data } => {
+                    assert_eq!(seq, 7);
+                    assert_eq!(data.players, vec!["bob".to_string()]);
+                }
+                _ => panic!("wrong variant for {codec}"),
+            }
+        }
+    }
+
+    #[test]
+    fn manifest_metadata_roundtrips() {
+        for codec in Codec::ALL {
+            let msg: ServerWire<TestSnapshot> = ServerWire::Manifest(
+                Manifest::new(Identity::local("room"), "Room")
+                    .metadata(serde_json::json!({ "type": "chat", "limit": 50 })),
+            );
+            let parsed: ServerWire<TestSnapshot> =
+                codec.decode(&codec.encode(&msg).unwrap()).unwrap();
+            match parsed {
+                ServerWire::Manifest(m) => assert_eq!(m.metadata["limit"], 50),
+                _ => panic!("wrong variant for {codec}"),
+            }
+        }
+    }
+
+    #[test]
+    fn binary_codecs_are_smaller_for_passports() {
+        let msg: ClientWire<TestIntent> = ClientWire::Auth {
+            identity: Identity::local("alice"),
+            name: None,
+            passport: Some(vec![200; 256]),
+            capabilities: None,
+            nonce: None,
+            resume: None,
+        };
+        let json = Codec::Json.encode(&msg).unwrap().len();
+        assert!(Codec::MessagePack.encode(&msg).unwrap().len() < json / 2);
+        assert!(Codec::Cbor.encode(&msg).unwrap().len() < json / 2);
+    }
+
+    #[test]
+    fn parse_names() {
+        for codec in Codec::ALL {
+            assert_eq!(codec.name().parse::<Codec>().unwrap(), codec);
+        }
+        assert!("xml".parse::<Codec>().is_err());
+    }
+}
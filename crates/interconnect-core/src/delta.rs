@@ -0,0 +1,274 @@
+//! Delta snapshots.
+//!
+//! Instead of re-sending the whole state on every change, an authority can
+//! send `ServerWire::Delta { base_seq, seq, patch }`: an RFC 6902 JSON Patch
+//! from a snapshot the client has acknowledged to the new one.
+//!
+//! ```text
+//! Authority → Client: Snapshot { seq: 1, data }
+//! Client → Authority: Ack { seq: 1 }
+//! Authority → Client: Delta { base_seq: 1, seq: 2, patch }
+//! Authority → Client: Delta { base_seq: 1, seq: 3, patch }
+//! Client → Authority: Ack { seq: 3 }
+//! Authority → Client: Delta { base_seq: 3, seq: 4, patch }
+//! ```
+//!
+//! Deltas only start once a client acks, so clients that never send `Ack`
+//! keep receiving full snapshots. Authorities keep one [`DeltaEncoder`] per
+//! session; clients rebuild state with a [`DeltaDecoder`].
+
+use crate::{Patch, ServerWire};
+use serde::{Serialize, de::DeserializeOwned};
+use serde_json::Value;
+use std::collections::{BTreeMap, VecDeque};
+
+/// Unacknowledged snapshots an encoder remembers as potential bases.
+const MAX_UNACKED: usize = 64;
+
+/// Snapshots a decoder keeps. Larger than [`MAX_UNACKED`] so a base the
+/// authority can still pick is never one the client has dropped.
+const MAX_RETAINED: usize = 256;
+
+/// Authority-side, per-session delta state.
+#[derive(Debug, Clone, Default)]
+pub struct DeltaEncoder {
+    base: Option<(u64, Value)>,
+    sent: VecDeque<(u64, Value)>,
+}
+
+impl DeltaEncoder {
+    /// An encoder with no acknowledged base; the first message is a full
+    /// snapshot.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Handle `ClientWire::Ack { seq }`.
+    ///
+    /// Acks for snapshots this encoder no longer remembers (or never sent)
+    /// are ignored and the current base is kept.
+    pub fn ack(&mut self, seq: u64) {
+        if let Some(pos) = self.sent.iter().position(|(s, _)| *s == seq) {
+            self.base = self.sent.drain(..=pos).next_back();
+        }
+    }
+
+    /// Forget the acknowledged base, so the next message is a full snapshot.
+    pub fn reset(&mut self) {
+        self.base = None;
+        self.sent.clear();
+    }
+
+    /// The sequence number of the acknowledged base, if any.
+    pub fn base_seq(&self) -> Option<u64> {
+        self.base.as_ref().map(|(seq, _)| *seq)
+    }
+
+    /// Encode snapshot `seq`: a `Delta` against the acknowledged base, or a
+    /// full `Snapshot` when there is none.
+    pub fn encode<S: Serialize>(
+        &mut self,
+        seq: u64,
+        data: S,
+    ) -> Result<ServerWire<S>, serde_json::Error> {
+        let value = serde_json::to_value(&data)?;
+        let msg = match &self.base {
+            Some((base_seq, base)) => ServerWire::Delta {
+                base_seq: *base_seq,
+                seq,
+                patch: json_patch::diff(base, &value),
+            },
+            None => ServerWire::Snapshot { seq, data },
+        };
+
+        self.sent.push_back((seq, value));
+        if self.sent.len() > MAX_UNACKED {
+            self.sent.pop_front();
+        }
+        Ok(msg)
+    }
+}
+
+/// Client-side state reconstruction.
+#[derive(Debug, Clone, Default)]
+pub struct DeltaDecoder {
+    states: BTreeMap<u64, Value>,
+}
+
+impl DeltaDecoder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record a full snapshot as a possible base.
+    pub fn snapshot<S: Serialize>(&mut self, seq: u64, data: &S) -> Result<(), serde_json::Error> {
+        self.insert(seq, serde_json::to_value(data)?);
+        Ok(())
+    }
+
+    /// Rebuild snapshot `seq` by applying `patch` to `base_seq`.
+    pub fn delta<S: DeserializeOwned>(
+        &mut self,
+        base_seq: u64,
+        seq: u64,
+        patch: &Patch,
+    ) -> Result<S, DeltaError> {
+        let mut state = self
+            .states
+            .get(&base_seq)
+            .cloned()
+            .ok_or(DeltaError::UnknownBase(base_seq))?;
+        json_patch::patch(&mut state, patch)?;
+        let data = S::deserialize(&state)?;
+
+        // The authority only moves its base forward, so older states are dead.
+        self.states = self.states.split_off(&base_seq);
+        self.insert(seq, state);
+        Ok(data)
+    }
+
+    /// The newest snapshot, as JSON.
+    pub fn latest(&self) -> Option<(u64, &Value)> {
+        self.states
+            .last_key_value()
+            .map(|(seq, state)| (*seq, state))
+    }
+
+    fn insert(&mut self, seq: u64, state: Value) {
+        self.states.insert(seq, state);
+        while self.states.len() > MAX_RETAINED {
+            self.states.pop_first();
+        }
+    }
+}
+
+/// Error rebuilding state from a delta.
+#[derive(Debug, thiserror::Error)]
+pub enum DeltaError {
+    #[error("delta against unknown base snapshot {0}")]
+    UnknownBase(u64),
+    #[error("patch failed: {0}")]
+    Patch(#[from] json_patch::PatchError),
+    #[error("patched state does not match snapshot type: {0}")]
+    Json(#[from] serde_json::Error),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+
+    #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+    struct Room {
+        messages: Vec<String>,
+        users: Vec<String>,
+    }
+
+    fn room(messages: &[&str]) -> Room {
+        Room {
+            messages: messages.iter().map(|m| m.to_string()).collect(),
+            users: vec!["alice".into(), "bob".into()],
+        }
+    }
+
+    /// Decode like a client would, returning the rebuilt state.
+    fn receive(decoder: &mut DeltaDecoder, msg: ServerWire<Room>) -> (u64, Room) {
+        match msg {
+            ServerWire::Snapshot { seq, data } => {
+                decoder.snapshot(seq, &data).unwrap();
+                (seq, data)
+            }
+            ServerWire::Delta {
+                base_seq,
+                seq,
+                patch,
+            } => (seq, decoder.delta(base_seq, seq, &patch).unwrap()),
+            _ => panic!("unexpected message"),
+        }
+    }
+
+    #[test]
+    fn full_until_acked() {
+        let mut encoder = DeltaEncoder::new();
+        let first = encoder.encode(1, room(&["hi"])).unwrap();
+        let second = encoder.encode(2, room(&["hi", "yo"])).unwrap();
+        assert!(matches!(first, ServerWire::Snapshot { seq: 1, .. }));
+        assert!(matches!(second, ServerWire::Snapshot { seq: 2, .. }));
+
+        encoder.ack(1);
+        assert_eq!(encoder.base_seq(), Some(1));
+        let third = encoder.encode(3, room(&["hi", "yo", "sup"])).unwrap();
+        assert!(matches!(
+            third,
+            ServerWire::Delta {
+                base_seq: 1,
+                seq: 3,
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn client_rebuilds_state() {
+        let mut encoder = DeltaEncoder::new();
+        let mut decoder = DeltaDecoder::new();
+        let mut history = vec!["m0".to_string()];
+
+        let first = encoder.encode(0, room(&["m0"])).unwrap();
+        receive(&mut decoder, first);
+
+        for seq in 1..20u64 {
+            history.push(format!("m{seq}"));
+            if history.len() > 5 {
+                history.remove(0);
+            }
+            let state = Room {
+                messages: history.clone(),
+                users: vec!["alice".into(), "bob".into()],
+            };
+            let (got_seq, rebuilt) =
+                receive(&mut decoder, encoder.encode(seq, state.clone()).unwrap());
+            assert_eq!(got_seq, seq);
+            assert_eq!(rebuilt, state);
+
+            // Acks arrive late and only every few messages.
+            if seq % 3 == 0 {
+                encoder.ack(seq - 1);
+            }
+        }
+    }
+
+    #[test]
+    fn delta_is_smaller_than_snapshot() {
+        let mut encoder = DeltaEncoder::new();
+        let messages: Vec<String> = (0..50).map(|i| format!("message number {i}")).collect();
+        let refs: Vec<&str> = messages.iter().map(String::as_str).collect();
+        encoder.encode(1, room(&refs)).unwrap();
+        encoder.ack(1);
+
+        let mut next = refs.clone();
+        next.push("one more");
+        let full = serde_json::to_vec(&ServerWire::Snapshot {
+            seq: 2,
+            data: room(&next),
+        })
+        .unwrap();
+        let delta = serde_json::to_vec(&encoder.encode(2, room(&next)).unwrap()).unwrap();
+        assert!(delta.len() * 10 < full.len());
+    }
+
+    #[test]
+    fn unknown_base() {
+        let mut decoder = DeltaDecoder::new();
+        let err = decoder.delta::<Room>(7, 8, &Patch(Vec::new())).unwrap_err();
+        assert!(matches!(err, DeltaError::UnknownBase(7)));
+
+        // Acks for unknown snapshots leave the encoder sending full state.
+        let mut encoder = DeltaEncoder::new();
+        encoder.ack(42);
+        assert!(matches!(
+            encoder.encode(1, room(&[])).unwrap(),
+            ServerWire::Snapshot { .. }
+        ));
+    }
+}
@@ -0,0 +1,268 @@
+//! Length-prefixed transports over byte streams.
+//!
+//! Each message is sent as a 4-byte big-endian length followed by that many
+//! bytes, so binary codecs frame as well as JSON. Works over anything
+//! `AsyncRead`/`AsyncWrite`: TCP and Unix sockets, and the stdio of a child
+//! process or of the current one (a sidecar room needs no server at all).
+
+use crate::{RecvHalf, SendHalf, SplitTransport, Transport};
+use std::io;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+/// Largest frame accepted, to bound memory from a misbehaving peer.
+pub const MAX_FRAME_LEN: usize = 16 * 1024 * 1024;
+
+/// A transport over a byte stream, framed with length prefixes.
+#[derive(Debug)]
+pub struct FramedTransport<R, W> {
+    reader: FrameReader<R>,
+    writer: W,
+}
+
+/// Framed transport over a TCP connection.
+pub type TcpTransport =
+    FramedTransport<tokio::net::tcp::OwnedReadHalf, tokio::net::tcp::OwnedWriteHalf>;
+
+/// Framed transport over a Unix socket connection.
+#[cfg(unix)]
+pub type UnixTransport =
+    FramedTransport<tokio::net::unix::OwnedReadHalf, tokio::net::unix::OwnedWriteHalf>;
+
+/// Framed transport over a child process's stdout and stdin.
+pub type ChildTransport = FramedTransport<tokio::process::ChildStdout, tokio::process::ChildStdin>;
+
+/// Framed transport over this process's own stdin and stdout.
+pub type StdioTransport = FramedTransport<tokio::io::Stdin, tokio::io::Stdout>;
+
+impl<R, W> FramedTransport<R, W>
+where
+    R: AsyncRead + Unpin + Send,
+    W: AsyncWrite + Unpin + Send,
+{
+    /// Read frames from `reader` and write them to `writer`.
+    pub fn new(reader: R, writer: W) -> Self {
+        Self {
+            reader: FrameReader::new(reader),
+            writer,
+        }
+    }
+}
+
+impl TcpTransport {
+    /// Connect to an authority listening on `addr`.
+    pub async fn connect(addr: impl tokio::net::ToSocketAddrs) -> io::Result<Self> {
+        Ok(Self::tcp(tokio::net::TcpStream::connect(addr).await?))
+    }
+
+    /// Frame an established TCP connection.
+    pub fn tcp(stream: tokio::net::TcpStream) -> Self {
+        let (reader, writer) = stream.into_split();
+        Self::new(reader, writer)
+    }
+}
+
+#[cfg(unix)]
+impl UnixTransport {
+    /// Connect to an authority listening on the socket at `path`.
+    pub async fn connect(path: impl AsRef<std::path::Path>) -> io::Result<Self> {
+        Ok(Self::unix(tokio::net::UnixStream::connect(path).await?))
+    }
+
+    /// Frame an established Unix socket connection.
+    pub fn unix(stream: tokio::net::UnixStream) -> Self {
+        let (reader, writer) = stream.into_split();
+        Self::new(reader, writer)
+    }
+}
+
+impl ChildTransport {
+    /// Talk to `child` over its stdio, which must have been piped. Takes the
+    /// pipes out of `child`; returns `None` if they are missing.
+    pub fn child(child: &mut tokio::process::Child) -> Option<Self> {
+        Some(Self::new(child.stdout.take()?, child.stdin.take()?))
+    }
+}
+
+impl StdioTransport {
+    /// Talk to whoever spawned this process. Nothing else may use stdout.
+    pub fn stdio() -> Self {
+        Self::new(tokio::io::stdin(), tokio::io::stdout())
+    }
+}
+
+impl<R, W> Transport for FramedTransport<R, W>
+where
+    R: AsyncRead + Unpin + Send,
+    W: AsyncWrite + Unpin + Send,
+{
+    type Error = io::Error;
+
+    async fn send(&mut self, data: &[u8]) -> io::Result<()> {
+        write_frame(&mut self.writer, data).await
+    }
+
+    async fn recv(&mut self) -> io::Result<Option<Vec<u8>>> {
+        self.reader.next().await
+    }
+}
+
+/// The writing half of a split [`FramedTransport`].
+#[derive(Debug)]
+pub struct FramedSender<W> {
+    writer: W,
+}
+
+/// The reading half of a split [`FramedTransport`].
+#[derive(Debug)]
+pub struct FramedReceiver<R> {
+    reader: FrameReader<R>,
+}
+
+impl<R, W> SplitTransport for FramedTransport<R, W>
+where
+    R: AsyncRead + Unpin + Send,
+    W: AsyncWrite + Unpin + Send,
+{
+    type Sender = FramedSender<W>;
+    type Receiver = FramedReceiver<R>;
+
+    fn split(self) -> (FramedSender<W>, FramedReceiver<R>) {
+        (
+            FramedSender {
+                writer: self.writer,
+            },
+            FramedReceiver {
+                reader: self.reader,
+            },
+        )
+    }
+}
+
+impl<W: AsyncWrite + Unpin + Send> SendHalf for FramedSender<W> {
+    type Error = io::Error;
+
+    async fn send(&mut self, data: &[u8]) -> io::Result<()> {
+        write_frame(&mut self.writer, data).await
+    }
+}
+
+impl<R: AsyncRead + Unpin + Send> RecvHalf for FramedReceiver<R> {
+    type Error = io::Error;
+
+    async fn recv(&mut self) -> io::Result<Option<Vec<u8>>> {
+        self.reader.next().await
+    }
+}
+
+async fn write_frame<W: AsyncWrite + Unpin>(writer: &mut W, data: &[u8]) -> io::Result<()> {
+    let len = u32::try_from(data.len())
+        .ok()
+        .filter(|&len| len as usize <= MAX_FRAME_LEN)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "frame too large"))?;
+    writer.write_all(&len.to_be_bytes()).await?;
+    writer.write_all(data).await?;
+    writer.flush().await
+}
+
+/// Reads frames, keeping partial ones buffered so that a cancelled read
+/// loses nothing.
+#[derive(Debug)]
+struct FrameReader<R> {
+    reader: R,
+    buf: Vec<u8>,
+}
+
+impl<R: AsyncRead + Unpin> FrameReader<R> {
+    fn new(reader: R) -> Self {
+        Self {
+            reader,
+            buf: Vec::new(),
+        }
+    }
+
+    async fn next(&mut self) -> io::Result<Option<Vec<u8>>> {
+        loop {
+            if let Some(len) = self.buf.first_chunk::<4>() {
+                let len = u32::from_be_bytes(*len) as usize;
+                if len > MAX_FRAME_LEN {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("frame of {len} bytes exceeds {MAX_FRAME_LEN}"),
+                    ));
+                }
+                if self.buf.len() >= 4 + len {
+                    let frame = self.buf[4..4 + len].to_vec();
+                    self.buf.drain(..4 + len);
+                    return Ok(Some(frame));
+                }
+                self.buf.reserve(4 + len - self.buf.len());
+            }
+            if self.reader.read_buf(&mut self.buf).await? == 0 {
+                // A clean close between frames.
+                if self.buf.is_empty() {
+                    return Ok(None);
+                }
+                return Err(io::ErrorKind::UnexpectedEof.into());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use tokio::io::{DuplexStream, ReadHalf, WriteHalf};
+
+    type Duplex = FramedTransport<ReadHalf<DuplexStream>, WriteHalf<DuplexStream>>;
+
+    fn pair() -> (Duplex, Duplex) {
+        let (a, b) = tokio::io::duplex(64);
+        let (a_read, a_write) = tokio::io::split(a);
+        let (b_read, b_write) = tokio::io::split(b);
+        (
+            FramedTransport::new(a_read, a_write),
+            FramedTransport::new(b_read, b_write),
+        )
+    }
+
+    #[tokio::test]
+    async fn frames_survive_small_buffers() {
+        let (mut a, mut b) = pair();
+        let big = vec![7u8; 1000];
+        let sender = tokio::spawn(async move {
+            a.send(b"hello").await.unwrap();
+            a.send(&[]).await.unwrap();
+            a.send(&big).await.unwrap();
+        });
+        assert_eq!(b.recv().await.unwrap().unwrap(), b"hello");
+        assert_eq!(b.recv().await.unwrap().unwrap(), b"");
+        assert_eq!(b.recv().await.unwrap().unwrap().len(), 1000);
+        sender.await.unwrap();
+        // The sender's end was dropped between frames.
+        assert!(b.recv().await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn cancelled_reads_lose_nothing() {
+        let (mut a, mut b) = pair();
+        // Half a frame arrives, then the read is dropped.
+        a.writer.write_all(&5u32.to_be_bytes()).await.unwrap();
+        a.writer.write_all(b"he").await.unwrap();
+        let read = tokio::time::timeout(std::time::Duration::from_millis(20), b.recv());
+        assert!(read.await.is_err());
+        a.writer.write_all(b"llo").await.unwrap();
+        assert_eq!(b.recv().await.unwrap().unwrap(), b"hello");
+    }
+
+    #[tokio::test]
+    async fn oversized_frames_are_refused() {
+        let (a, mut b) = pair();
+        let mut raw = a.writer;
+        raw.write_all(&u32::MAX.to_be_bytes()).await.unwrap();
+        assert_eq!(
+            b.recv().await.unwrap_err().kind(),
+            io::ErrorKind::InvalidData
+        );
+    }
+}
@@ -5,8 +5,9 @@
 //! Supported schemes:
 //! - `local:name` - Trust the connection (dev/LAN)
 //! - `url:user@server` - Server vouches for user
-//! - `ed25519:fingerprint` - Cryptographic (user holds key)
+//! - `ed25519:fingerprint` - Cryptographic (user holds key, see [`Keypair`](crate::Keypair))
 
+use crate::keys::{self, KeyError};
 use serde::{Deserialize, Serialize};
 use std::fmt;
 use std::str::FromStr;
@@ -38,6 +39,15 @@ impl Identity {
         Self::new("url", user_at_server)
     }
 
+    /// Create a cryptographic identity from an ed25519 public key.
+    pub fn ed25519(public_key: &[u8; 32]) -> Self {
+        use base64::Engine;
+        Self::new(
+            keys::ED25519_SCHEME,
+            base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(public_key),
+        )
+    }
+
     /// The scheme (e.g., "local", "url", "ed25519").
     pub fn scheme(&self) -> &str {
         &self.scheme
@@ -52,6 +62,23 @@ impl Identity {
     pub fn is_local(&self) -> bool {
         self.scheme == "local"
     }
+
+    /// Check if this identity is backed by a key the holder can prove.
+    pub fn is_cryptographic(&self) -> bool {
+        self.scheme == keys::ED25519_SCHEME
+    }
+
+    /// Verify that `signature` over `data` was made by the key behind this
+    /// identity.
+    ///
+    /// Only cryptographic schemes can verify; `local` and `url` identities
+    /// return [`KeyError::UnsupportedScheme`].
+    pub fn verify(&self, data: &[u8], signature: &[u8]) -> Result<(), KeyError> {
+        match self.scheme.as_str() {
+            keys::ED25519_SCHEME => keys::verify_ed25519(&self.payload, data, signature),
+            other => Err(KeyError::UnsupportedScheme(other.to_string())),
+        }
+    }
 }
 
 impl fmt::Display for Identity {
@@ -0,0 +1,620 @@
+//! Declarative import policies.
+//!
+//! An [`ImportPolicy`] is the customs check a destination runs on an incoming
+//! passport: role allowlists, banned capabilities, item allow/deny lists, a
+//! carry limit and numeric field limits. It works on passports as JSON values
+//! and addresses fields with dotted path selectors: `credits`, `stats.level`,
+//! `inventory.0`, and `*` to match every element (`inventory.*.count`).
+//!
+//! Policies are usually loaded from TOML:
+//!
+//! ```toml
+//! [import_policy]
+//! allowed_roles = ["member"]
+//! banned_items = ["debug_tool", "admin_key"]
+//! max_carried_items = 5
+//!
+//! [import_policy.paths]
+//! items = "inventory"
+//! item_id = "kind"
+//!
+//! [import_policy.field_limits]
+//! credits = { min = 0, max = 1000 }
+//! "stats.level" = { min = 1, max = 50 }
+//! ```
+//!
+//! Every change the policy makes is reported as a [`Rejection`]; nothing is
+//! dropped silently.
+
+use crate::{ImportResult, Rejection};
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+use serde_json::Value;
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::Path;
+
+/// A destination's rules for sanitizing incoming passports.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ImportPolicy {
+    /// Where roles, capabilities and items live in the passport.
+    pub paths: PolicyPaths,
+    /// Roles kept on entry.
+    pub allowed_roles: Allowlist,
+    /// Capabilities stripped on entry.
+    pub banned_capabilities: BTreeSet<String>,
+    /// Items that may be carried in.
+    pub allowed_items: Allowlist,
+    /// Items confiscated on entry. Takes precedence over `allowed_items`.
+    pub banned_items: BTreeSet<String>,
+    /// Most items kept; the rest are confiscated in passport order.
+    pub max_carried_items: Option<usize>,
+    /// Numeric limits, keyed by path selector.
+    pub field_limits: BTreeMap<String, RangeLimit>,
+    /// Reset every limited field instead of clamping it.
+    pub reset_stats: bool,
+    /// Confiscate all items.
+    pub reset_inventory: bool,
+}
+
+/// Path selectors for the structured parts of a passport.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PolicyPaths {
+    /// Array of role names.
+    pub roles: String,
+    /// Array of capability names.
+    pub capabilities: String,
+    /// Array of carried items.
+    pub items: String,
+    /// Item id within each item object. Items that are plain strings or
+    /// numbers are their own id.
+    pub item_id: String,
+}
+
+impl Default for PolicyPaths {
+    fn default() -> Self {
+        Self {
+            roles: "roles".to_string(),
+            capabilities: "capabilities".to_string(),
+            items: "items".to_string(),
+            item_id: "id".to_string(),
+        }
+    }
+}
+
+/// Bounds for a numeric field.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RangeLimit {
+    pub min: Option<f64>,
+    pub max: Option<f64>,
+    /// Value used by `reset_stats` and for non-numeric input. Defaults to
+    /// `min`, then zero.
+    pub default: Option<f64>,
+}
+
+impl RangeLimit {
+    /// A limit with both bounds.
+    pub fn new(min: f64, max: f64) -> Self {
+        Self {
+            min: Some(min),
+            max: Some(max),
+            default: None,
+        }
+    }
+
+    fn reset_value(&self) -> f64 {
+        self.default.or(self.min).unwrap_or(0.0)
+    }
+}
+
+/// A set of accepted names, or `"*"` for anything.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "AllowlistRepr", into = "AllowlistRepr")]
+pub struct Allowlist(Option<BTreeSet<String>>);
+
+impl Allowlist {
+    /// Accept anything.
+    pub fn any() -> Self {
+        Self(None)
+    }
+
+    /// Accept only the given names.
+    pub fn only<I: IntoIterator<Item = S>, S: Into<String>>(names: I) -> Self {
+        Self(Some(names.into_iter().map(Into::into).collect()))
+    }
+
+    /// Whether `name` is accepted.
+    pub fn allows(&self, name: &str) -> bool {
+        self.0.as_ref().is_none_or(|set| set.contains(name))
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum AllowlistRepr {
+    Wildcard(String),
+    List(BTreeSet<String>),
+}
+
+impl TryFrom<AllowlistRepr> for Allowlist {
+    type Error = String;
+
+    fn try_from(repr: AllowlistRepr) -> Result<Self, Self::Error> {
+        match repr {
+            AllowlistRepr::Wildcard(s) if s == "*" => Ok(Self::any()),
+            AllowlistRepr::Wildcard(s) => Err(format!("expected \"*\" or a list, got \"{s}\"")),
+            AllowlistRepr::List(set) => Ok(Self(Some(set))),
+        }
+    }
+}
+
+impl From<Allowlist> for AllowlistRepr {
+    fn from(list: Allowlist) -> Self {
+        match list.0 {
+            None => AllowlistRepr::Wildcard("*".to_string()),
+            Some(set) => AllowlistRepr::List(set),
+        }
+    }
+}
+
+impl ImportPolicy {
+    /// Parse a policy from TOML.
+    ///
+    /// Reads the `[import_policy]` table if there is one, so a policy can
+    /// live in a larger room config; otherwise the whole document.
+    pub fn from_toml(text: &str) -> Result<Self, ImportError> {
+        let mut table: toml::Table = toml::from_str(text)?;
+        let value = match table.remove("import_policy") {
+            Some(section) => section,
+            None => toml::Value::Table(table),
+        };
+        Ok(value.try_into()?)
+    }
+
+    /// Load a policy from a TOML file.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, ImportError> {
+        Self::from_toml(&std::fs::read_to_string(path)?)
+    }
+
+    /// Sanitize a JSON passport.
+    pub fn apply(&self, mut passport: Value) -> ImportResult<Value> {
+        let mut rejected = Vec::new();
+
+        for_each_match(&mut passport, &self.paths.roles, &mut |path, roles| {
+            retain_names(path, roles, &mut rejected, |role| {
+                (!self.allowed_roles.allows(role)).then_some("role not accepted here")
+            });
+        });
+
+        for_each_match(
+            &mut passport,
+            &self.paths.capabilities,
+            &mut |path, caps| {
+                retain_names(path, caps, &mut rejected, |cap| {
+                    self.banned_capabilities
+                        .contains(cap)
+                        .then_some("capability banned here")
+                });
+            },
+        );
+
+        for_each_match(&mut passport, &self.paths.items, &mut |_, items| {
+            self.filter_items(items, &mut rejected);
+        });
+
+        for (selector, limit) in &self.field_limits {
+            for_each_match(&mut passport, selector, &mut |path, field| {
+                if let Some(rejection) = self.limit_field(path, field, limit) {
+                    rejected.push(rejection);
+                }
+            });
+        }
+
+        ImportResult { passport, rejected }
+    }
+
+    /// Sanitize a typed passport by round-tripping it through JSON.
+    pub fn apply_to<P: Serialize + DeserializeOwned>(
+        &self,
+        passport: &P,
+    ) -> Result<ImportResult<P>, ImportError> {
+        let result = self.apply(serde_json::to_value(passport)?);
+        Ok(ImportResult {
+            passport: serde_json::from_value(result.passport)?,
+            rejected: result.rejected,
+        })
+    }
+
+    fn filter_items(&self, items: &mut Value, rejected: &mut Vec<Rejection>) {
+        let Value::Array(list) = items else { return };
+        let mut kept = Vec::with_capacity(list.len());
+
+        for item in list.drain(..) {
+            let id = self.item_id(&item);
+            let reason = if self.reset_inventory {
+                Some("inventory reset on entry")
+            } else if self.banned_items.contains(&id) {
+                Some("banned item confiscated")
+            } else if !self.allowed_items.allows(&id) {
+                Some("item not allowed here")
+            } else if self.max_carried_items.is_some_and(|max| kept.len() >= max) {
+                Some("over carry limit")
+            } else {
+                None
+            };
+
+            match reason {
+                Some(reason) => rejected.push(Rejection::new(id, reason)),
+                None => kept.push(item),
+            }
+        }
+
+        *list = kept;
+    }
+
+    fn item_id(&self, item: &Value) -> String {
+        let id = match item {
+            Value::Object(_) => lookup(item, &self.paths.item_id).unwrap_or(&Value::Null),
+            other => other,
+        };
+        match id {
+            Value::String(s) => s.clone(),
+            other => other.to_string(),
+        }
+    }
+
+    fn limit_field(&self, path: &str, field: &mut Value, limit: &RangeLimit) -> Option<Rejection> {
+        let before = field.clone();
+        let integral = if before.is_number() {
+            before.is_i64() || before.is_u64()
+        } else {
+            limit.reset_value().fract() == 0.0
+        };
+        // An integer field is clamped to the nearest integer inside the
+        // bounds; a float one, or one whose bounds hold no integer, to the
+        // bounds themselves.
+        let integral = integral
+            && !limit
+                .min
+                .zip(limit.max)
+                .is_some_and(|(min, max)| min.ceil() > max.floor());
+        let (min, max) = if integral {
+            (limit.min.map(f64::ceil), limit.max.map(f64::floor))
+        } else {
+            (limit.min, limit.max)
+        };
+        let (after, reason) = match field.as_f64() {
+            _ if self.reset_stats => (limit.reset_value(), "reset on entry"),
+            Some(x) => match (min, max) {
+                (Some(min), _) if x < min => (min, "raised to server minimum"),
+                (_, Some(max)) if x > max => (max, "lowered to server maximum"),
+                _ => return None,
+            },
+            None => (limit.reset_value(), "not a number"),
+        };
+
+        *field = if integral {
+            Value::from(after as i64)
+        } else {
+            Value::from(after)
+        };
+        if *field == before {
+            return None;
+        }
+        Some(Rejection::new(
+            path,
+            format!("{reason}: {before} -> {field}"),
+        ))
+    }
+}
+
+/// Drop names from a JSON array of strings when `reject` gives a reason.
+/// Entries that are not strings are dropped too, and anything but an array
+/// at `path` is emptied.
+fn retain_names(
+    path: &str,
+    list: &mut Value,
+    rejected: &mut Vec<Rejection>,
+    reject: impl Fn(&str) -> Option<&'static str>,
+) {
+    let Value::Array(entries) = list else {
+        rejected.push(Rejection::new(path, format!("not a list: {list}")));
+        *list = Value::Array(vec![]);
+        return;
+    };
+    entries.retain(|name| {
+        let Some(name) = name.as_str() else {
+            rejected.push(Rejection::new(name.to_string(), "not a name"));
+            return false;
+        };
+        match reject(name) {
+            Some(reason) => {
+                rejected.push(Rejection::new(name, reason));
+                false
+            }
+            None => true,
+        }
+    });
+}
+
+/// Resolve a selector without wildcards.
+fn lookup<'a>(value: &'a Value, selector: &str) -> Option<&'a Value> {
+    selector
+        .split('.')
+        .filter(|s| !s.is_empty())
+        .try_fold(value, |v, segment| match v {
+            Value::Object(map) => map.get(segment),
+            Value::Array(list) => list.get(segment.parse::<usize>().ok()?),
+            _ => None,
+        })
+}
+
+/// Call `f` with the concrete path and value of every match of `selector`.
+fn for_each_match(value: &mut Value, selector: &str, f: &mut dyn FnMut(&str, &mut Value)) {
+    let segments: Vec<&str> = selector.split('.').filter(|s| !s.is_empty()).collect();
+    if segments.is_empty() {
+        return;
+    }
+    visit(value, &segments, &mut Vec::new(), f);
+}
+
+fn visit(
+    value: &mut Value,
+    segments: &[&str],
+    path: &mut Vec<String>,
+    f: &mut dyn FnMut(&str, &mut Value),
+) {
+    let Some((segment, rest)) = segments.split_first() else {
+        f(&path.join("."), value);
+        return;
+    };
+
+    let mut descend = |key: String, child: &mut Value| {
+        path.push(key);
+        visit(child, rest, path, f);
+        path.pop();
+    };
+
+    match value {
+        Value::Object(map) if *segment == "*" => {
+            for (key, child) in map.iter_mut() {
+                descend(key.clone(), child);
+            }
+        }
+        Value::Object(map) => {
+            if let Some(child) = map.get_mut(*segment) {
+                descend(segment.to_string(), child);
+            }
+        }
+        Value::Array(list) if *segment == "*" => {
+            for (i, child) in list.iter_mut().enumerate() {
+                descend(i.to_string(), child);
+            }
+        }
+        Value::Array(list) => {
+            if let Some(child) = segment.parse::<usize>().ok().and_then(|i| list.get_mut(i)) {
+                descend(segment.to_string(), child);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Error loading or applying an import policy.
+#[derive(Debug, thiserror::Error)]
+pub enum ImportError {
+    #[error("io error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("invalid policy: {0}")]
+    Toml(#[from] toml::de::Error),
+    #[error("passport is not representable as JSON: {0}")]
+    Json(#[from] serde_json::Error),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn items(result: &ImportResult<Value>) -> Vec<&str> {
+        result.passport["inventory"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|i| i["kind"].as_str().unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn loads_docs_examples() {
+        let open = ImportPolicy::from_toml(
+            r#"
+            [import_policy]
+            allowed_items = "*"
+            banned_items = ["debug_tool", "admin_key"]
+            "#,
+        )
+        .unwrap();
+        assert!(open.allowed_items.allows("anything"));
+        assert!(open.banned_items.contains("admin_key"));
+
+        let restricted = ImportPolicy::from_toml(
+            r#"
+            allowed_roles = ["member"]
+            allowed_items = ["avatar", "display_name"]
+            max_carried_items = 5
+            "#,
+        )
+        .unwrap();
+        assert!(!restricted.allowed_roles.allows("admin"));
+        assert_eq!(restricted.max_carried_items, Some(5));
+
+        assert!(ImportPolicy::from_toml("allowed_items = \"all\"").is_err());
+        assert!(ImportPolicy::from_toml("alowed_items = []").is_err());
+    }
+
+    #[test]
+    fn the_admin_passport() {
+        let policy = ImportPolicy::from_toml(
+            r#"
+            allowed_roles = ["member"]
+            banned_capabilities = ["teleport"]
+            banned_items = ["AdminKey"]
+            field_limits = { credits = { min = 0, max = 1000 } }
+            "#,
+        )
+        .unwrap();
+
+        let result = policy.apply(json!({
+            "roles": ["admin", "member"],
+            "capabilities": ["chat", "teleport"],
+            "items": ["GodSword", "AdminKey"],
+            "credits": 999999,
+        }));
+
+        assert_eq!(
+            result.passport,
+            json!({
+                "roles": ["member"],
+                "capabilities": ["chat"],
+                "items": ["GodSword"],
+                "credits": 1000,
+            })
+        );
+        let rejected: Vec<&str> = result.rejected.iter().map(|r| r.item.as_str()).collect();
+        assert_eq!(rejected, ["admin", "teleport", "AdminKey", "credits"]);
+        assert_eq!(
+            result.rejected[3].reason,
+            "lowered to server maximum: 999999 -> 1000"
+        );
+    }
+
+    #[test]
+    fn item_objects_and_carry_limit() {
+        let policy = ImportPolicy::from_toml(
+            r#"
+            allowed_items = ["potion", "gem", "torch"]
+            max_carried_items = 2
+            paths = { items = "inventory", item_id = "kind" }
+            "#,
+        )
+        .unwrap();
+
+        let result = policy.apply(json!({
+            "inventory": [
+                { "kind": "sword", "count": 1 },
+                { "kind": "potion", "count": 3 },
+                { "kind": "gem", "count": 1 },
+                { "kind": "torch", "count": 1 },
+            ]
+        }));
+
+        assert_eq!(items(&result), ["potion", "gem"]);
+        assert_eq!(result.rejected[0].reason, "item not allowed here");
+        assert_eq!(result.rejected[1].item, "torch");
+        assert_eq!(result.rejected[1].reason, "over carry limit");
+    }
+
+    #[test]
+    fn wildcard_limits() {
+        let mut policy = ImportPolicy::default();
+        policy
+            .field_limits
+            .insert("inventory.*.count".into(), RangeLimit::new(1.0, 10.0));
+        policy
+            .field_limits
+            .insert("stats.speed".into(), RangeLimit::new(0.5, 2.0));
+
+        let result = policy.apply(json!({
+            "inventory": [{ "kind": "a", "count": 0 }, { "kind": "b", "count": 99 }],
+            "stats": { "speed": 3.5 },
+        }));
+
+        assert_eq!(result.passport["inventory"][0]["count"], 1);
+        assert_eq!(result.passport["inventory"][1]["count"], 10);
+        assert_eq!(result.passport["stats"]["speed"], 2.0);
+        let paths: Vec<&str> = result.rejected.iter().map(|r| r.item.as_str()).collect();
+        assert_eq!(paths, ["inventory.0.count", "inventory.1.count", "stats.speed"]);
+    }
+
+    #[test]
+    fn fractional_bounds() {
+        let mut policy = ImportPolicy::default();
+        for field in ["speed", "level"] {
+            policy
+                .field_limits
+                .insert(field.into(), RangeLimit::new(0.5, 2.5));
+        }
+
+        let result = policy.apply(json!({ "speed": 0.1, "level": 7 }));
+
+        // Floats clamp to the bounds; integers to the integers inside them.
+        assert_eq!(result.passport["speed"], 0.5);
+        assert_eq!(result.passport["level"], 2);
+        let result = policy.apply(json!({ "speed": 9.0, "level": 0 }));
+        assert_eq!(result.passport["speed"], 2.5);
+        assert_eq!(result.passport["level"], 1);
+
+        // No integer fits, so the float bounds apply.
+        let mut policy = ImportPolicy::default();
+        policy
+            .field_limits
+            .insert("level".into(), RangeLimit::new(1.2, 1.8));
+        assert_eq!(policy.apply(json!({ "level": 1 })).passport["level"], 1.2);
+        assert_eq!(policy.apply(json!({ "level": 2 })).passport["level"], 1.8);
+    }
+
+    #[test]
+    fn malformed_names_are_rejected() {
+        let policy = ImportPolicy::default();
+
+        let result = policy.apply(json!({
+            "roles": ["member", 7, { "name": "admin" }],
+            "capabilities": "teleport",
+        }));
+
+        assert_eq!(result.passport["roles"], json!(["member"]));
+        assert_eq!(result.passport["capabilities"], json!([]));
+        let rejected: Vec<&str> = result.rejected.iter().map(|r| r.item.as_str()).collect();
+        assert_eq!(rejected, ["7", r#"{"name":"admin"}"#, "capabilities"]);
+    }
+
+    #[test]
+    fn fresh_start() {
+        let policy = ImportPolicy::from_toml(
+            r#"
+            reset_stats = true
+            reset_inventory = true
+            [field_limits]
+            level = { min = 1, max = 50 }
+            gold = { max = 100, default = 10 }
+            "#,
+        )
+        .unwrap();
+
+        let result = policy.apply(json!({ "name": "Ann", "level": 1, "gold": 70, "items": ["hat"] }));
+        assert_eq!(
+            result.passport,
+            json!({ "name": "Ann", "level": 1, "gold": 10, "items": [] })
+        );
+        // Unchanged fields are not reported.
+        assert_eq!(result.rejected.len(), 2);
+    }
+
+    #[test]
+    fn typed_passport() {
+        #[derive(Serialize, Deserialize)]
+        struct P {
+            health: u32,
+        }
+
+        let mut policy = ImportPolicy::default();
+        policy
+            .field_limits
+            .insert("health".into(), RangeLimit::new(1.0, 100.0));
+        let result = policy.apply_to(&P { health: 500 }).unwrap();
+        assert_eq!(result.passport.health, 100);
+        assert_eq!(result.rejected.len(), 1);
+    }
+}
@@ -0,0 +1,224 @@
+//! Ed25519 keypairs for cryptographic identities.
+//!
+//! A [`Keypair`] derives an `ed25519:<fingerprint>` [`Identity`]. The
+//! fingerprint is the public key itself (base64url, no padding), so the
+//! identity is self-certifying: anyone holding it can verify signatures
+//! without a key directory.
+//!
+//! Keys are stored on disk as the base64url-encoded 32-byte secret seed.
+
+use crate::Identity;
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use ed25519_dalek::{Signer as _, SigningKey, Verifier, VerifyingKey};
+use std::path::Path;
+
+/// Identity scheme for ed25519 keys.
+pub const ED25519_SCHEME: &str = "ed25519";
+
+/// An ed25519 signing keypair.
+#[derive(Clone)]
+pub struct Keypair {
+    signing: SigningKey,
+}
+
+impl Keypair {
+    /// Generate a fresh keypair from the OS random number generator.
+    pub fn generate() -> Result<Self, KeyError> {
+        let mut seed = [0u8; 32];
+        getrandom::fill(&mut seed).map_err(|e| KeyError::Random(e.to_string()))?;
+        Ok(Self::from_seed(seed))
+    }
+
+    /// Build a keypair from a 32-byte secret seed.
+    pub fn from_seed(seed: [u8; 32]) -> Self {
+        Self {
+            signing: SigningKey::from_bytes(&seed),
+        }
+    }
+
+    /// The 32-byte secret seed. Keep it secret.
+    pub fn seed(&self) -> [u8; 32] {
+        self.signing.to_bytes()
+    }
+
+    /// The 32-byte public key.
+    pub fn public_key(&self) -> [u8; 32] {
+        self.signing.verifying_key().to_bytes()
+    }
+
+    /// The `ed25519:<fingerprint>` identity for this keypair.
+    pub fn identity(&self) -> Identity {
+        Identity::ed25519(&self.public_key())
+    }
+
+    /// Sign `data`, returning a 64-byte signature.
+    pub fn sign(&self, data: &[u8]) -> Vec<u8> {
+        self.signing.sign(data).to_bytes().to_vec()
+    }
+
+    /// Load a keypair previously written by [`save`](Self::save).
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, KeyError> {
+        let text = std::fs::read_to_string(path)?;
+        let bytes = URL_SAFE_NO_PAD
+            .decode(text.trim())
+            .map_err(|e| KeyError::Malformed(e.to_string()))?;
+        let seed: [u8; 32] = bytes.try_into().map_err(|b: Vec<u8>| {
+            KeyError::Malformed(format!("expected 32 bytes, got {}", b.len()))
+        })?;
+        Ok(Self::from_seed(seed))
+    }
+
+    /// Write the secret seed to `path`, creating parent directories.
+    ///
+    /// On Unix the file is created with mode 0600.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), KeyError> {
+        let path = path.as_ref();
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        let mut options = std::fs::OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o600);
+        }
+
+        use std::io::Write;
+        let mut file = options.open(path)?;
+        writeln!(file, "{}", URL_SAFE_NO_PAD.encode(self.seed()))?;
+        Ok(())
+    }
+
+    /// Load the keypair at `path`, or generate and save a new one if the
+    /// file does not exist.
+    pub fn load_or_generate(path: impl AsRef<Path>) -> Result<Self, KeyError> {
+        let path = path.as_ref();
+        match Self::load(path) {
+            Err(KeyError::Io(e)) if e.kind() == std::io::ErrorKind::NotFound => {
+                let keypair = Self::generate()?;
+                keypair.save(path)?;
+                Ok(keypair)
+            }
+            other => other,
+        }
+    }
+}
+
+/// Something that can prove ownership of a cryptographic identity.
+///
+/// Implemented by [`Keypair`]; implement it yourself to keep keys in an
+/// agent, HSM, or OS keychain.
+pub trait Signer: Send + Sync {
+    /// The identity whose key signs.
+    fn identity(&self) -> Identity;
+
+    /// Sign `data`.
+    fn sign(&self, data: &[u8]) -> Vec<u8>;
+}
+
+impl Signer for Keypair {
+    fn identity(&self) -> Identity {
+        Keypair::identity(self)
+    }
+
+    fn sign(&self, data: &[u8]) -> Vec<u8> {
+        Keypair::sign(self, data)
+    }
+}
+
+impl std::fmt::Debug for Keypair {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Keypair")
+            .field("identity", &self.identity().to_string())
+            .finish_non_exhaustive()
+    }
+}
+
+/// Verify an ed25519 signature against a fingerprint.
+pub(crate) fn verify_ed25519(
+    fingerprint: &str,
+    data: &[u8],
+    signature: &[u8],
+) -> Result<(), KeyError> {
+    let bytes = URL_SAFE_NO_PAD
+        .decode(fingerprint)
+        .map_err(|e| KeyError::Malformed(e.to_string()))?;
+    let bytes: [u8; 32] = bytes
+        .try_into()
+        .map_err(|_| KeyError::Malformed("fingerprint is not a 32-byte key".to_string()))?;
+    let key = VerifyingKey::from_bytes(&bytes).map_err(|e| KeyError::Malformed(e.to_string()))?;
+    let signature =
+        ed25519_dalek::Signature::from_slice(signature).map_err(|_| KeyError::BadSignature)?;
+    key.verify(data, &signature)
+        .map_err(|_| KeyError::BadSignature)
+}
+
+/// Errors from key handling and signature verification.
+#[derive(Debug, thiserror::Error)]
+pub enum KeyError {
+    #[error("io error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("malformed key: {0}")]
+    Malformed(String),
+    #[error("random number generator failed: {0}")]
+    Random(String),
+    #[error("identity scheme '{0}' cannot verify signatures")]
+    UnsupportedScheme(String),
+    #[error("signature verification failed")]
+    BadSignature,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sign_and_verify() {
+        let keypair = Keypair::generate().unwrap();
+        let identity = keypair.identity();
+        assert_eq!(identity.scheme(), "ed25519");
+
+        let signature = keypair.sign(b"hello");
+        identity.verify(b"hello", &signature).unwrap();
+        assert!(matches!(
+            identity.verify(b"hullo", &signature),
+            Err(KeyError::BadSignature)
+        ));
+    }
+
+    #[test]
+    fn other_key_rejected() {
+        let alice = Keypair::generate().unwrap();
+        let mallory = Keypair::generate().unwrap();
+        let signature = mallory.sign(b"i am alice");
+        assert!(alice.identity().verify(b"i am alice", &signature).is_err());
+    }
+
+    #[test]
+    fn local_identity_cannot_verify() {
+        let err = Identity::local("alice").verify(b"x", &[0; 64]).unwrap_err();
+        assert!(matches!(err, KeyError::UnsupportedScheme(_)));
+    }
+
+    #[test]
+    fn identity_survives_string_roundtrip() {
+        let keypair = Keypair::from_seed([7; 32]);
+        let parsed: Identity = keypair.identity().to_string().parse().unwrap();
+        parsed.verify(b"data", &keypair.sign(b"data")).unwrap();
+    }
+
+    #[test]
+    fn save_and_load() {
+        let dir = std::env::temp_dir().join(format!("interconnect-keys-{}", std::process::id()));
+        let path = dir.join("identity.key");
+
+        let keypair = Keypair::load_or_generate(&path).unwrap();
+        let loaded = Keypair::load_or_generate(&path).unwrap();
+        assert_eq!(keypair.identity(), loaded.identity());
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,484 @@
+//! Transport middleware.
+//!
+//! Each layer is a [`Transport`] wrapping another, so cross-cutting
+//! behaviour stacks onto any transport without touching it:
+//!
+//! ```ignore
+//! use interconnect_core::{Compression, Metrics, RateLimit, TransportExt};
+//!
+//! let metrics = Metrics::default();
+//! let transport = WsTransport::connect(url)
+//!     .await?
+//!     .size_limited(1 << 20)
+//!     .compressed(Compression::Zstd)
+//!     .rate_limited(RateLimit::per_second(20.0).burst(50))
+//!     .metered(metrics.clone())
+//!     .logged("room-a");
+//! ```
+//!
+//! The outermost layer sees a frame first on send and last on recv: a size
+//! limit inside `compressed` bounds bytes on the wire, one outside it bounds
+//! decompressed frames. Compression changes the bytes on the wire, so both
+//! ends need the layer.
+
+use crate::{MAX_FRAME_LEN, Transport};
+use std::io::Read;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+/// Frames smaller than this are sent uncompressed.
+pub const COMPRESSION_THRESHOLD: usize = 256;
+
+/// Errors from a layer, or from the transport inside it.
+#[derive(Debug, thiserror::Error)]
+pub enum LayerError<E> {
+    #[error(transparent)]
+    Inner(E),
+
+    #[error(transparent)]
+    Frame(#[from] FrameError),
+}
+
+/// A frame a layer refused.
+#[derive(Debug, thiserror::Error)]
+pub enum FrameError {
+    #[error("frame of {len} bytes exceeds the {max}-byte limit")]
+    TooLarge { len: usize, max: usize },
+
+    #[error("compression error: {0}")]
+    Compression(#[from] std::io::Error),
+}
+
+/// Adds layers to any transport.
+pub trait TransportExt: Transport + Sized {
+    /// Compress frames with `compression`.
+    fn compressed(self, compression: Compression) -> Compressed<Self> {
+        Compressed {
+            inner: self,
+            compression,
+        }
+    }
+
+    /// Log every frame to `tracing` under `label`: sizes at debug level,
+    /// contents at trace.
+    fn logged(self, label: impl Into<String>) -> Logged<Self> {
+        Logged {
+            inner: self,
+            label: label.into(),
+        }
+    }
+
+    /// Hold frames back to `limit`, in each direction.
+    fn rate_limited(self, limit: RateLimit) -> RateLimited<Self> {
+        RateLimited {
+            inner: self,
+            send: Bucket::new(limit),
+            recv: Bucket::new(limit),
+        }
+    }
+
+    /// Refuse frames longer than `max` bytes, in either direction.
+    fn size_limited(self, max: usize) -> SizeLimited<Self> {
+        SizeLimited { inner: self, max }
+    }
+
+    /// Count frames and bytes into `metrics`, which may be shared by many
+    /// transports.
+    fn metered(self, metrics: Metrics) -> Metered<Self> {
+        Metered {
+            inner: self,
+            metrics,
+        }
+    }
+}
+
+impl<T: Transport> TransportExt for T {}
+
+/// Compression algorithm for [`TransportExt::compressed`].
+///
+/// Each frame is tagged with how it was compressed, so peers choosing
+/// different algorithms still understand each other.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+    Zstd,
+    Deflate,
+}
+
+/// Frame tags.
+const RAW: u8 = 0;
+const ZSTD: u8 = 1;
+const DEFLATE: u8 = 2;
+
+impl Compression {
+    fn compress(self, data: &[u8]) -> std::io::Result<Vec<u8>> {
+        let mut out = match self {
+            Compression::Zstd => vec![ZSTD],
+            Compression::Deflate => vec![DEFLATE],
+        };
+        match self {
+            Compression::Zstd => out.extend(zstd::bulk::compress(data, 0)?),
+            Compression::Deflate => {
+                let mut encoder =
+                    flate2::read::DeflateEncoder::new(data, flate2::Compression::default());
+                encoder.read_to_end(&mut out)?;
+            }
+        }
+        Ok(out)
+    }
+}
+
+/// Decompress a tagged frame, refusing output beyond [`MAX_FRAME_LEN`].
+fn decompress(frame: &[u8]) -> Result<Vec<u8>, FrameError> {
+    let invalid = |msg: &str| std::io::Error::new(std::io::ErrorKind::InvalidData, msg);
+    let (&tag, data) = frame.split_first().ok_or_else(|| invalid("empty frame"))?;
+    match tag {
+        RAW => Ok(data.to_vec()),
+        ZSTD => read_limited(zstd::stream::read::Decoder::with_buffer(data)?),
+        DEFLATE => read_limited(flate2::read::DeflateDecoder::new(data)),
+        _ => Err(invalid(&format!("unknown compression tag {tag}")).into()),
+    }
+}
+
+/// Read a decoder to the end, growing only as far as [`MAX_FRAME_LEN`].
+fn read_limited(decoder: impl Read) -> Result<Vec<u8>, FrameError> {
+    let mut out = Vec::new();
+    decoder
+        .take(MAX_FRAME_LEN as u64 + 1)
+        .read_to_end(&mut out)?;
+    if out.len() > MAX_FRAME_LEN {
+        return Err(FrameError::TooLarge {
+            len: out.len(),
+            max: MAX_FRAME_LEN,
+        });
+    }
+    Ok(out)
+}
+
+/// See [`TransportExt::compressed`].
+pub struct Compressed<T> {
+    inner: T,
+    compression: Compression,
+}
+
+impl<T: Transport> Transport for Compressed<T> {
+    type Error = LayerError<T::Error>;
+
+    async fn send(&mut self, data: &[u8]) -> Result<(), Self::Error> {
+        let mut frame = Vec::new();
+        if data.len() >= COMPRESSION_THRESHOLD {
+            frame = self.compression.compress(data).map_err(FrameError::from)?;
+        }
+        // Small or incompressible frames go as they are.
+        if frame.is_empty() || frame.len() > data.len() {
+            frame = [&[RAW], data].concat();
+        }
+        self.inner.send(&frame).await.map_err(LayerError::Inner)
+    }
+
+    async fn recv(&mut self) -> Result<Option<Vec<u8>>, Self::Error> {
+        match self.inner.recv().await.map_err(LayerError::Inner)? {
+            Some(frame) => Ok(Some(decompress(&frame)?)),
+            None => Ok(None),
+        }
+    }
+}
+
+/// See [`TransportExt::logged`].
+pub struct Logged<T> {
+    inner: T,
+    label: String,
+}
+
+impl<T: Transport> Logged<T> {
+    fn log(&self, direction: &str, data: &[u8]) {
+        tracing::debug!(transport = %self.label, len = data.len(), "{direction}");
+        tracing::trace!(
+            transport = %self.label,
+            frame = %String::from_utf8_lossy(data),
+            "{direction}"
+        );
+    }
+}
+
+impl<T: Transport> Transport for Logged<T> {
+    type Error = T::Error;
+
+    async fn send(&mut self, data: &[u8]) -> Result<(), T::Error> {
+        self.log("send", data);
+        let result = self.inner.send(data).await;
+        if let Err(e) = &result {
+            tracing::debug!(transport = %self.label, "send failed: {e}");
+        }
+        result
+    }
+
+    async fn recv(&mut self) -> Result<Option<Vec<u8>>, T::Error> {
+        let result = self.inner.recv().await;
+        match &result {
+            Ok(Some(data)) => self.log("recv", data),
+            Ok(None) => tracing::debug!(transport = %self.label, "closed"),
+            Err(e) => tracing::debug!(transport = %self.label, "recv failed: {e}"),
+        }
+        result
+    }
+}
+
+/// A token bucket: `rate` frames per second on average, up to `burst` at
+/// once.
+#[derive(Debug, Clone, Copy)]
+pub struct RateLimit {
+    rate: f64,
+    burst: f64,
+}
+
+impl RateLimit {
+    /// `rate` frames per second, with bursts of up to one second's worth.
+    ///
+    /// Panics unless `rate` is positive.
+    pub fn per_second(rate: f64) -> Self {
+        assert!(rate > 0.0, "rate limit must be positive, got {rate}");
+        Self {
+            rate,
+            burst: rate.max(1.0),
+        }
+    }
+
+    /// Allow up to `burst` frames at once.
+    pub fn burst(mut self, burst: u32) -> Self {
+        self.burst = f64::from(burst.max(1));
+        self
+    }
+}
+
+struct Bucket {
+    limit: RateLimit,
+    tokens: f64,
+    refilled: Instant,
+}
+
+impl Bucket {
+    fn new(limit: RateLimit) -> Self {
+        Self {
+            limit,
+            tokens: limit.burst,
+            refilled: Instant::now(),
+        }
+    }
+
+    fn refill(&mut self) {
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.refilled).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.limit.rate).min(self.limit.burst);
+        self.refilled = now;
+    }
+
+    /// How long until a token is available, or `None` if one is.
+    fn wait(&mut self) -> Option<Duration> {
+        self.refill();
+        (self.tokens < 1.0).then(|| Duration::from_secs_f64((1.0 - self.tokens) / self.limit.rate))
+    }
+
+    /// Wait for a token without taking it.
+    async fn ready(&mut self) {
+        while let Some(wait) = self.wait() {
+            tokio::time::sleep(wait).await;
+        }
+    }
+
+    fn take(&mut self) {
+        self.refill();
+        self.tokens -= 1.0;
+    }
+
+    async fn acquire(&mut self) {
+        self.ready().await;
+        self.take();
+    }
+}
+
+/// See [`TransportExt::rate_limited`].
+///
+/// A peer sending too fast is not cut off; its frames are read more slowly,
+/// which pushes back on it through the inner transport.
+pub struct RateLimited<T> {
+    inner: T,
+    send: Bucket,
+    recv: Bucket,
+}
+
+impl<T: Transport> Transport for RateLimited<T> {
+    type Error = T::Error;
+
+    async fn send(&mut self, data: &[u8]) -> Result<(), T::Error> {
+        self.send.acquire().await;
+        self.inner.send(data).await
+    }
+
+    async fn recv(&mut self) -> Result<Option<Vec<u8>>, T::Error> {
+        // Only a frame that arrives costs a token, so a cancelled read
+        // spends nothing.
+        self.recv.ready().await;
+        let frame = self.inner.recv().await?;
+        if frame.is_some() {
+            self.recv.take();
+        }
+        Ok(frame)
+    }
+}
+
+/// See [`TransportExt::size_limited`].
+pub struct SizeLimited<T> {
+    inner: T,
+    max: usize,
+}
+
+impl<T> SizeLimited<T> {
+    fn check(&self, data: &[u8]) -> Result<(), FrameError> {
+        if data.len() > self.max {
+            return Err(FrameError::TooLarge {
+                len: data.len(),
+                max: self.max,
+            });
+        }
+        Ok(())
+    }
+}
+
+impl<T: Transport> Transport for SizeLimited<T> {
+    type Error = LayerError<T::Error>;
+
+    async fn send(&mut self, data: &[u8]) -> Result<(), Self::Error> {
+        self.check(data)?;
+        self.inner.send(data).await.map_err(LayerError::Inner)
+    }
+
+    async fn recv(&mut self) -> Result<Option<Vec<u8>>, Self::Error> {
+        let frame = self.inner.recv().await.map_err(LayerError::Inner)?;
+        if let Some(data) = &frame {
+            self.check(data)?;
+        }
+        Ok(frame)
+    }
+}
+
+/// Frame and byte counters for [`TransportExt::metered`].
+///
+/// Cheap to clone; clones share the counts.
+#[derive(Debug, Clone, Default)]
+pub struct Metrics(Arc<Counters>);
+
+#[derive(Debug, Default)]
+struct Counters {
+    frames_sent: AtomicU64,
+    bytes_sent: AtomicU64,
+    frames_received: AtomicU64,
+    bytes_received: AtomicU64,
+}
+
+impl Metrics {
+    pub fn frames_sent(&self) -> u64 {
+        self.0.frames_sent.load(Ordering::Relaxed)
+    }
+
+    pub fn bytes_sent(&self) -> u64 {
+        self.0.bytes_sent.load(Ordering::Relaxed)
+    }
+
+    pub fn frames_received(&self) -> u64 {
+        self.0.frames_received.load(Ordering::Relaxed)
+    }
+
+    pub fn bytes_received(&self) -> u64 {
+        self.0.bytes_received.load(Ordering::Relaxed)
+    }
+}
+
+/// See [`TransportExt::metered`].
+pub struct Metered<T> {
+    inner: T,
+    metrics: Metrics,
+}
+
+impl<T: Transport> Transport for Metered<T> {
+    type Error = T::Error;
+
+    async fn send(&mut self, data: &[u8]) -> Result<(), T::Error> {
+        self.inner.send(data).await?;
+        let counters = &self.metrics.0;
+        counters.frames_sent.fetch_add(1, Ordering::Relaxed);
+        counters
+            .bytes_sent
+            .fetch_add(data.len() as u64, Ordering::Relaxed);
+        Ok(())
+    }
+
+    async fn recv(&mut self) -> Result<Option<Vec<u8>>, T::Error> {
+        let frame = self.inner.recv().await?;
+        if let Some(data) = &frame {
+            let counters = &self.metrics.0;
+            counters.frames_received.fetch_add(1, Ordering::Relaxed);
+            counters
+                .bytes_received
+                .fetch_add(data.len() as u64, Ordering::Relaxed);
+        }
+        Ok(frame)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::loopback;
+
+    #[tokio::test]
+    async fn layers_stack() {
+        let (a, b) = loopback();
+        let wire = Metrics::default();
+        let mut a = a.metered(wire.clone()).compressed(Compression::Zstd);
+        let mut b = b.compressed(Compression::Deflate).size_limited(1000);
+
+        let big = vec![b'x'; 900];
+        a.send(&big).await.unwrap();
+        assert_eq!(b.recv().await.unwrap().unwrap(), big);
+        assert!(wire.bytes_sent() < 100);
+
+        b.send(b"small").await.unwrap();
+        assert_eq!(a.recv().await.unwrap().unwrap(), b"small");
+        assert_eq!(wire.bytes_received(), 6);
+
+        // The limit applies to the decompressed frame.
+        a.send(&[b'x'; 2000]).await.unwrap();
+        assert!(matches!(
+            b.recv().await,
+            Err(LayerError::Frame(FrameError::TooLarge { len: 2000, .. }))
+        ));
+    }
+
+    #[test]
+    fn bucket_refills_at_rate() {
+        let mut bucket = Bucket::new(RateLimit::per_second(10.0).burst(2));
+        assert_eq!(bucket.wait(), None);
+        bucket.take();
+        bucket.take();
+        let wait = bucket.wait().unwrap();
+        assert!(wait <= Duration::from_millis(100));
+        assert!(wait > Duration::from_millis(50));
+    }
+
+    #[test]
+    #[should_panic]
+    fn rate_must_be_positive() {
+        RateLimit::per_second(0.0);
+    }
+
+    #[test]
+    fn zstd_bombs_are_refused() {
+        let mut bomb = vec![ZSTD];
+        bomb.extend(zstd::bulk::compress(&vec![0; MAX_FRAME_LEN + 1], 0).unwrap());
+        assert!(matches!(
+            decompress(&bomb),
+            Err(FrameError::TooLarge { .. })
+        ));
+    }
+}
@@ -6,7 +6,8 @@
 //! # Quick Start
 //!
 //! 1. Define your types (Intent, Snapshot, Passport)
-//! 2. Implement [`SimpleAuthority`] or [`Authority`]
+//! 2. Implement [`SimpleAuthority`] or [`Authority`] ([`AsyncAuthority`] if
+//!    your hooks need to await I/O)
 //! 3. Use a transport crate to run your server
 //!
 //! # Example
@@ -34,34 +35,125 @@
 //! }
 //! ```
 
+mod auth;
 mod authority;
+mod codec;
+mod delta;
+mod framed;
 mod identity;
+mod import;
+mod keys;
+mod layer;
+mod loopback;
 mod message;
+mod schema;
+mod substrate;
 mod transfer;
 mod transport;
+mod trust;
+mod version;
 mod wire;
 
-pub use authority::{Authority, ImportResult, Rejection, Session, SimpleAuthority};
+pub use auth::{AuthError, AuthPolicy, Challenge, challenge_message};
+pub use authority::{AsyncAuthority, Authority, ImportResult, Rejection, Session, SimpleAuthority};
+pub use codec::{Codec, CodecError};
+pub use delta::{DeltaDecoder, DeltaEncoder, DeltaError};
+pub use json_patch::Patch;
+#[cfg(unix)]
+pub use framed::UnixTransport;
+pub use framed::{
+    ChildTransport, FramedReceiver, FramedSender, FramedTransport, MAX_FRAME_LEN, StdioTransport,
+    TcpTransport,
+};
 pub use identity::Identity;
+pub use import::{Allowlist, ImportError, ImportPolicy, PolicyPaths, RangeLimit};
+pub use keys::{KeyError, Keypair, Signer};
+pub use layer::{
+    COMPRESSION_THRESHOLD, Compressed, Compression, FrameError, LayerError, Logged, Metered,
+    Metrics, RateLimit, RateLimited, SizeLimited, TransportExt,
+};
+pub use loopback::{Loopback, LoopbackClosed, LoopbackReceiver, LoopbackSender, loopback};
 pub use message::{ClientMessage, ServerMessage};
-pub use transfer::{Passport, Transfer};
-pub use transport::Transport;
-pub use wire::{from_json, from_json_str, to_json, to_json_string, ClientWire, ServerWire, Wire};
+pub use schema::{JsonSchema, RoomSchema};
+pub use substrate::{
+    CHUNK_SIZE, MAX_SUBSTRATE_LEN, Substrate, SubstrateDownload, SubstrateError, SubstrateStore,
+    substrate_hash, verify_substrate,
+};
+pub use transfer::{Passport, PassportError, PassportVerifier, Transfer};
+pub use transport::{RecvHalf, SendHalf, SplitTransport, Transport};
+pub use trust::{KnownAuthorities, Trust, TrustError, TrustMode, manifest_nonce};
+pub use version::{
+    Capabilities, PROTOCOL_VERSION, Protocol, SUPPORTED_VERSIONS, VersionError, feature,
+};
+pub use wire::{
+    ClientWire, IntentOutcome, Resume, ServerWire, Wire, from_json, from_json_str, to_json,
+    to_json_string,
+};
 
 use serde::{Deserialize, Serialize};
 
 /// Manifest describing a server's capabilities and requirements.
+///
+/// Built with [`Manifest::new`] and its setters, so new fields do not break
+/// existing rooms.
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[non_exhaustive]
 pub struct Manifest {
     /// Server's identity (for verification).
     pub identity: Identity,
     /// Human-readable server name.
     pub name: String,
-    /// Substrate hash (if applicable).
+    /// Substrate hash (`sha256:<hex>`), if the room has one.
     pub substrate: Option<String>,
+    /// Protocol negotiated for this session. Filled in by the runtime; `None`
+    /// from authorities that predate negotiation.
+    #[serde(default)]
+    pub protocol: Option<Protocol>,
+    /// Schemas of the room's intents and snapshots, if it publishes them.
+    #[serde(default)]
+    pub schema: Option<RoomSchema>,
     /// Additional metadata (app-defined).
     #[serde(default)]
     pub metadata: serde_json::Value,
+    /// The authority's signature over
+    /// [`signing_bytes`](Self::signing_bytes), made for one client's nonce.
+    /// Filled in by the runtime when the authority has a signing key.
+    #[serde(default, with = "serde_bytes")]
+    pub signature: Option<Vec<u8>>,
+}
+
+impl Manifest {
+    /// A manifest for the room `name`, run by `identity`, with nothing else
+    /// set.
+    pub fn new(identity: Identity, name: impl Into<String>) -> Self {
+        Self {
+            identity,
+            name: name.into(),
+            substrate: None,
+            protocol: None,
+            schema: None,
+            metadata: serde_json::Value::Null,
+            signature: None,
+        }
+    }
+
+    /// Advertise the substrate with this hash.
+    pub fn substrate(mut self, hash: impl Into<String>) -> Self {
+        self.substrate = Some(hash.into());
+        self
+    }
+
+    /// Publish the room's intent and snapshot schemas.
+    pub fn schema(mut self, schema: RoomSchema) -> Self {
+        self.schema = Some(schema);
+        self
+    }
+
+    /// Attach app-specific metadata.
+    pub fn metadata(mut self, metadata: serde_json::Value) -> Self {
+        self.metadata = metadata;
+        self
+    }
 }
 
 /// Connection lifecycle state.
@@ -69,6 +161,8 @@ pub struct Manifest {
 pub enum ConnectionState {
     /// Establishing connection.
     Connecting,
+    /// Fetching and verifying the substrate after a cache miss.
+    LoadingSubstrate,
     /// Receiving initial state.
     Syncing,
     /// Normal operation.
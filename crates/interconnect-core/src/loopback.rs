@@ -0,0 +1,84 @@
+//! In-memory transport.
+//!
+//! Connects a client and an authority in the same process, for tests and
+//! for embedding a room without a network.
+
+use crate::{RecvHalf, SendHalf, SplitTransport, Transport};
+use tokio::sync::mpsc;
+
+/// One end of an in-memory duplex connection, made by [`loopback`].
+///
+/// Messages arrive in order and whole. Dropping one end closes the other:
+/// its `recv` returns `None` and its `send` fails.
+#[derive(Debug)]
+pub struct Loopback {
+    tx: mpsc::UnboundedSender<Vec<u8>>,
+    rx: mpsc::UnboundedReceiver<Vec<u8>>,
+}
+
+/// Create a connected pair of in-memory transports.
+pub fn loopback() -> (Loopback, Loopback) {
+    let (a_tx, a_rx) = mpsc::unbounded_channel();
+    let (b_tx, b_rx) = mpsc::unbounded_channel();
+    (
+        Loopback { tx: a_tx, rx: b_rx },
+        Loopback { tx: b_tx, rx: a_rx },
+    )
+}
+
+/// The other end of a [`Loopback`] was dropped.
+#[derive(Debug, thiserror::Error)]
+#[error("loopback peer closed")]
+pub struct LoopbackClosed;
+
+impl Transport for Loopback {
+    type Error = LoopbackClosed;
+
+    async fn send(&mut self, data: &[u8]) -> Result<(), LoopbackClosed> {
+        self.tx.send(data.to_vec()).map_err(|_| LoopbackClosed)
+    }
+
+    async fn recv(&mut self) -> Result<Option<Vec<u8>>, LoopbackClosed> {
+        Ok(self.rx.recv().await)
+    }
+}
+
+/// The sending half of a split [`Loopback`].
+#[derive(Debug)]
+pub struct LoopbackSender {
+    tx: mpsc::UnboundedSender<Vec<u8>>,
+}
+
+/// The receiving half of a split [`Loopback`].
+#[derive(Debug)]
+pub struct LoopbackReceiver {
+    rx: mpsc::UnboundedReceiver<Vec<u8>>,
+}
+
+impl SplitTransport for Loopback {
+    type Sender = LoopbackSender;
+    type Receiver = LoopbackReceiver;
+
+    fn split(self) -> (LoopbackSender, LoopbackReceiver) {
+        (
+            LoopbackSender { tx: self.tx },
+            LoopbackReceiver { rx: self.rx },
+        )
+    }
+}
+
+impl SendHalf for LoopbackSender {
+    type Error = LoopbackClosed;
+
+    async fn send(&mut self, data: &[u8]) -> Result<(), LoopbackClosed> {
+        self.tx.send(data.to_vec()).map_err(|_| LoopbackClosed)
+    }
+}
+
+impl RecvHalf for LoopbackReceiver {
+    type Error = LoopbackClosed;
+
+    async fn recv(&mut self) -> Result<Option<Vec<u8>>, LoopbackClosed> {
+        Ok(self.rx.recv().await)
+    }
+}
@@ -0,0 +1,66 @@
+//! Machine-readable descriptions of a room's wire types.
+//!
+//! An authority derives [`JsonSchema`] for its `Intent` and `Snapshot` types
+//! and publishes both schemas in its [`Manifest`](crate::Manifest), so clients
+//! (and tools like the CLI) can discover valid intents and validate them
+//! before sending.
+
+use serde::{Deserialize, Serialize};
+
+pub use schemars::JsonSchema;
+
+/// JSON Schemas for a room's intents and snapshots.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RoomSchema {
+    /// Schema of the `Intent` type, as carried inside `ClientWire::Intent`.
+    pub intent: serde_json::Value,
+    /// Schema of the `Snapshot` type.
+    pub snapshot: serde_json::Value,
+}
+
+impl RoomSchema {
+    /// Derive the schemas of intent type `I` and snapshot type `S`.
+    pub fn of<I: JsonSchema, S: JsonSchema>() -> Self {
+        Self {
+            intent: schemars::schema_for!(I).to_value(),
+            snapshot: schemars::schema_for!(S).to_value(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Codec, Identity, Manifest};
+
+    #[derive(Serialize, Deserialize, JsonSchema)]
+    #[serde(tag = "action", rename_all = "snake_case")]
+    enum Intent {
+        Say { text: String },
+        Leave,
+    }
+
+    #[derive(Serialize, Deserialize, JsonSchema)]
+    struct Snapshot {
+        lines: Vec<String>,
+    }
+
+    #[test]
+    fn schema_follows_serde_attributes() {
+        let schema = RoomSchema::of::<Intent, Snapshot>();
+        let text = schema.intent.to_string();
+        assert!(text.contains(r#""say""#));
+        assert!(text.contains(r#""action""#));
+        assert_eq!(schema.snapshot["properties"]["lines"]["type"], "array");
+    }
+
+    #[test]
+    fn manifest_carries_schema_in_every_codec() {
+        let manifest = Manifest::new(Identity::local("room"), "room")
+            .schema(RoomSchema::of::<Intent, Snapshot>());
+        for codec in Codec::ALL {
+            let decoded: Manifest = codec.decode(&codec.encode(&manifest).unwrap()).unwrap();
+            assert_eq!(decoded.schema, manifest.schema, "{codec}");
+        }
+    }
+}
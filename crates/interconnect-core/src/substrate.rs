@@ -0,0 +1,347 @@
+//! Substrates: content-addressed static room data.
+//!
+//! An authority advertises its substrate as `Manifest::substrate`, a
+//! `sha256:<hex>` hash of the substrate bytes. Clients look the hash up in a
+//! local [`SubstrateStore`]; on a miss they fetch it:
+//!
+//! ```text
+//! Client → Authority: SubstrateRequest { hash }
+//! Authority → Client: SubstrateChunk { hash, offset: 0, total, data }
+//! Authority → Client: SubstrateChunk { hash, offset: 65536, total, data }
+//! ...
+//! ```
+//!
+//! The client reassembles the chunks with a [`SubstrateDownload`], which
+//! checks the hash before anything is cached. The substrate then stays
+//! available locally when the authority is gone.
+
+use crate::ServerWire;
+use sha2::{Digest, Sha256};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Tells apart the temporary files of concurrent writes in one process.
+static NEXT_TMP: AtomicU64 = AtomicU64::new(0);
+
+/// Bytes per `SubstrateChunk`.
+pub const CHUNK_SIZE: usize = 64 * 1024;
+
+/// Largest substrate a client will download.
+pub const MAX_SUBSTRATE_LEN: u64 = 256 * 1024 * 1024;
+
+const SHA256_PREFIX: &str = "sha256:";
+
+/// The content address of `data`: `sha256:<hex>`.
+pub fn substrate_hash(data: &[u8]) -> String {
+    let digest = Sha256::digest(data);
+    let mut hash = String::with_capacity(SHA256_PREFIX.len() + 64);
+    hash.push_str(SHA256_PREFIX);
+    for byte in digest {
+        hash.push_str(&format!("{byte:02x}"));
+    }
+    hash
+}
+
+/// Check that `data` has content address `hash`.
+pub fn verify_substrate(hash: &str, data: &[u8]) -> Result<(), SubstrateError> {
+    hex_digest(hash)?;
+    let actual = substrate_hash(data);
+    if actual != hash {
+        return Err(SubstrateError::Mismatch {
+            expected: hash.to_string(),
+            actual,
+        });
+    }
+    Ok(())
+}
+
+/// The hex digest of a well-formed hash.
+fn hex_digest(hash: &str) -> Result<&str, SubstrateError> {
+    hash.strip_prefix(SHA256_PREFIX)
+        .filter(|hex| {
+            hex.len() == 64 && hex.bytes().all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f'))
+        })
+        .ok_or_else(|| SubstrateError::InvalidHash(hash.to_string()))
+}
+
+/// A substrate an authority serves.
+#[derive(Debug, Clone)]
+pub struct Substrate {
+    hash: String,
+    data: Vec<u8>,
+}
+
+impl Substrate {
+    pub fn new(data: Vec<u8>) -> Self {
+        Self {
+            hash: substrate_hash(&data),
+            data,
+        }
+    }
+
+    /// The hash to put in `Manifest::substrate`.
+    pub fn hash(&self) -> &str {
+        &self.hash
+    }
+
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// The `SubstrateChunk` messages answering a `SubstrateRequest`.
+    ///
+    /// Always at least one message, so an empty substrate still completes.
+    pub fn chunks<S>(&self) -> impl Iterator<Item = ServerWire<S>> + '_ {
+        let len = self.data.len();
+        (0..len.div_ceil(CHUNK_SIZE).max(1)).map(move |i| {
+            let start = i * CHUNK_SIZE;
+            let end = (start + CHUNK_SIZE).min(len);
+            ServerWire::SubstrateChunk {
+                hash: self.hash.clone(),
+                offset: start as u64,
+                total: len as u64,
+                data: self.data[start..end].to_vec(),
+            }
+        })
+    }
+}
+
+/// Client-side reassembly of a chunked substrate.
+#[derive(Debug, Clone)]
+pub struct SubstrateDownload {
+    hash: String,
+    data: Vec<u8>,
+}
+
+impl SubstrateDownload {
+    pub fn new(hash: impl Into<String>) -> Self {
+        Self {
+            hash: hash.into(),
+            data: Vec::new(),
+        }
+    }
+
+    /// Add the next chunk. Returns the verified substrate once `total` bytes
+    /// have arrived.
+    pub fn push(
+        &mut self,
+        offset: u64,
+        total: u64,
+        chunk: &[u8],
+    ) -> Result<Option<Vec<u8>>, SubstrateError> {
+        if total > MAX_SUBSTRATE_LEN {
+            return Err(SubstrateError::TooLarge(total));
+        }
+        if offset != self.data.len() as u64 || offset + chunk.len() as u64 > total {
+            return Err(SubstrateError::BadChunk { offset, total });
+        }
+
+        self.data.extend_from_slice(chunk);
+        if (self.data.len() as u64) < total {
+            return Ok(None);
+        }
+        verify_substrate(&self.hash, &self.data)?;
+        Ok(Some(std::mem::take(&mut self.data)))
+    }
+}
+
+/// A local content-addressed substrate cache.
+///
+/// Entries are files named by their digest. Reads re-verify the hash, so a
+/// corrupted entry is dropped and reported as a miss.
+#[derive(Debug, Clone)]
+pub struct SubstrateStore {
+    root: PathBuf,
+}
+
+impl SubstrateStore {
+    /// Open (creating if needed) a store rooted at `root`.
+    pub fn open(root: impl Into<PathBuf>) -> Result<Self, SubstrateError> {
+        let root = root.into();
+        std::fs::create_dir_all(root.join("sha256"))?;
+        Ok(Self { root })
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Whether `hash` is cached (without verifying it).
+    pub fn contains(&self, hash: &str) -> bool {
+        self.path(hash).is_ok_and(|p| p.is_file())
+    }
+
+    /// Read and verify a cached substrate.
+    pub fn get(&self, hash: &str) -> Result<Option<Vec<u8>>, SubstrateError> {
+        let path = self.path(hash)?;
+        let data = match std::fs::read(&path) {
+            Ok(data) => data,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        if verify_substrate(hash, &data).is_err() {
+            std::fs::remove_file(&path)?;
+            return Ok(None);
+        }
+        Ok(Some(data))
+    }
+
+    /// Store `data`, returning its hash.
+    pub fn put(&self, data: &[u8]) -> Result<String, SubstrateError> {
+        let hash = substrate_hash(data);
+        self.write(&hash, data)?;
+        Ok(hash)
+    }
+
+    /// Store `data` under `hash`, refusing it if the hash does not match.
+    pub fn insert(&self, hash: &str, data: &[u8]) -> Result<(), SubstrateError> {
+        verify_substrate(hash, data)?;
+        self.write(hash, data)
+    }
+
+    fn write(&self, hash: &str, data: &[u8]) -> Result<(), SubstrateError> {
+        let path = self.path(hash)?;
+        // Write then rename, so readers never see a partial entry.
+        let n = NEXT_TMP.fetch_add(1, Ordering::Relaxed);
+        let tmp = path.with_extension(format!("tmp{}-{n}", std::process::id()));
+        let mut file = std::fs::File::create(&tmp)?;
+        file.write_all(data)?;
+        file.sync_all()?;
+        std::fs::rename(&tmp, &path)?;
+        Ok(())
+    }
+
+    fn path(&self, hash: &str) -> Result<PathBuf, SubstrateError> {
+        Ok(self.root.join("sha256").join(hex_digest(hash)?))
+    }
+}
+
+/// Errors from substrate storage and transfer.
+#[derive(Debug, thiserror::Error)]
+pub enum SubstrateError {
+    #[error("io error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("invalid substrate hash '{0}' (expected sha256:<hex>)")]
+    InvalidHash(String),
+    #[error("substrate hash mismatch: expected {expected}, got {actual}")]
+    Mismatch { expected: String, actual: String },
+    #[error("unexpected substrate chunk at offset {offset} of {total}")]
+    BadChunk { offset: u64, total: u64 },
+    #[error("substrate of {0} bytes exceeds the size limit")]
+    TooLarge(u64),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_store(name: &str) -> SubstrateStore {
+        let dir = std::env::temp_dir().join(format!(
+            "interconnect-substrate-{name}-{}",
+            std::process::id()
+        ));
+        let _ = std::fs::remove_dir_all(&dir);
+        SubstrateStore::open(dir).unwrap()
+    }
+
+    #[test]
+    fn hash_format() {
+        assert_eq!(
+            substrate_hash(b""),
+            "sha256:e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+        assert!(verify_substrate("sha256:abc", b"").is_err());
+        assert!(verify_substrate("md5:d41d8cd98f00b204e9800998ecf8427e", b"").is_err());
+    }
+
+    #[test]
+    fn chunked_download_roundtrip() {
+        let data: Vec<u8> = (0..CHUNK_SIZE * 2 + 10).map(|i| i as u8).collect();
+        let substrate = Substrate::new(data.clone());
+        let mut download = SubstrateDownload::new(substrate.hash());
+
+        let mut result = None;
+        let mut count = 0;
+        for msg in substrate.chunks::<()>() {
+            let ServerWire::SubstrateChunk {
+                offset,
+                total,
+                data,
+                ..
+            } = msg
+            else {
+                panic!("expected chunk");
+            };
+            count += 1;
+            result = download.push(offset, total, &data).unwrap();
+        }
+        assert_eq!(count, 3);
+        assert_eq!(result.unwrap(), data);
+    }
+
+    #[test]
+    fn empty_substrate_completes() {
+        let substrate = Substrate::new(Vec::new());
+        let mut download = SubstrateDownload::new(substrate.hash());
+        let chunks: Vec<ServerWire<()>> = substrate.chunks().collect();
+        assert_eq!(chunks.len(), 1);
+        assert_eq!(download.push(0, 0, &[]).unwrap(), Some(Vec::new()));
+    }
+
+    #[test]
+    fn tampered_download_rejected() {
+        let substrate = Substrate::new(b"room".to_vec());
+        let mut download = SubstrateDownload::new(substrate.hash());
+        assert!(matches!(
+            download.push(0, 4, b"ruin"),
+            Err(SubstrateError::Mismatch { .. })
+        ));
+
+        let mut download = SubstrateDownload::new(substrate.hash());
+        assert!(matches!(
+            download.push(2, 4, b"om"),
+            Err(SubstrateError::BadChunk { .. })
+        ));
+    }
+
+    #[test]
+    fn store_verifies() {
+        let store = temp_store("verify");
+        let hash = store.put(b"cave layout").unwrap();
+        assert!(store.contains(&hash));
+        assert_eq!(store.get(&hash).unwrap().unwrap(), b"cave layout");
+
+        // Corrupt on disk: dropped and reported as a miss.
+        std::fs::write(store.path(&hash).unwrap(), b"cave layoot").unwrap();
+        assert_eq!(store.get(&hash).unwrap(), None);
+        assert!(!store.contains(&hash));
+
+        assert!(store.insert(&hash, b"something else").is_err());
+        assert!(store.get("sha256:../../etc/passwd").is_err());
+
+        std::fs::remove_dir_all(store.root()).unwrap();
+    }
+
+    #[test]
+    fn concurrent_puts_of_one_substrate() {
+        let store = temp_store("concurrent");
+        std::thread::scope(|scope| {
+            for _ in 0..8 {
+                scope.spawn(|| {
+                    for _ in 0..20 {
+                        store.put(b"shared map").unwrap();
+                    }
+                });
+            }
+        });
+        assert_eq!(
+            std::fs::read_dir(store.root().join("sha256"))
+                .unwrap()
+                .count(),
+            1
+        );
+
+        std::fs::remove_dir_all(store.root()).unwrap();
+    }
+}
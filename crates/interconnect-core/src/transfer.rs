@@ -1,7 +1,22 @@
 //! Transfer types for server-to-server handoff.
+//!
+//! A [`Passport`] is issued by the origin authority and carried by the client
+//! to the destination. The origin signs it, and it is bound to one traveller,
+//! one destination, an expiry and a single-use nonce. So a client can neither
+//! edit it nor present it twice or somewhere else. Destinations check all of
+//! that with a [`PassportVerifier`] before the payload reaches
+//! `Authority::on_transfer_in`.
 
-use crate::Identity;
+use crate::{Codec, CodecError, Identity, KeyError, Signer};
 use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Domain separator for passport signatures.
+const PASSPORT_CONTEXT: &[u8] = b"interconnect-passport-v1:";
+
+/// Length of the random single-use nonce.
+const NONCE_LEN: usize = 16;
 
 /// A transfer directive, telling the client to connect to another server.
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -14,33 +29,293 @@ pub struct Transfer {
 
 /// A passport carried during transfer.
 ///
-/// Contains the user's identity and app-defined data that travels with them.
+/// Contains the user's identity and app-defined data that travels with them,
+/// signed by the issuing authority.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Passport {
     /// The user's identity.
     pub identity: Identity,
+    /// The origin authority's identity (its manifest identity).
+    pub issuer: Identity,
+    /// The destination this passport is valid at.
+    pub destination: String,
+    /// Expiry, in seconds since the Unix epoch.
+    pub expires_at: u64,
+    /// Random single-use nonce.
+    #[serde(with = "serde_bytes")]
+    pub nonce: Vec<u8>,
     /// App-defined payload (inventory, stats, etc.).
+    #[serde(with = "serde_bytes")]
     pub data: Vec<u8>,
-    /// Optional signature (scheme-dependent).
+    /// Issuer's signature over [`signing_bytes`](Self::signing_bytes).
+    #[serde(default, with = "serde_bytes")]
     pub signature: Option<Vec<u8>>,
 }
 
 impl Passport {
-    /// Create a new unsigned passport.
-    pub fn new(identity: Identity, data: Vec<u8>) -> Self {
-        Self {
+    /// Issue a signed passport for `identity`, valid at `destination` for `ttl`.
+    pub fn issue<S: Signer + ?Sized>(
+        issuer: &S,
+        identity: Identity,
+        destination: impl Into<String>,
+        data: Vec<u8>,
+        ttl: Duration,
+    ) -> Result<Self, KeyError> {
+        let mut nonce = vec![0u8; NONCE_LEN];
+        getrandom::fill(&mut nonce).map_err(|e| KeyError::Random(e.to_string()))?;
+
+        let mut passport = Self {
             identity,
+            issuer: issuer.identity(),
+            destination: destination.into(),
+            expires_at: unix_now().saturating_add(ttl.as_secs()),
+            nonce,
             data,
             signature: None,
+        };
+        passport.signature = Some(issuer.sign(&passport.signing_bytes()));
+        Ok(passport)
+    }
+
+    /// The bytes the issuer signs: every field but the signature, each
+    /// length-prefixed so field boundaries cannot be shifted.
+    pub fn signing_bytes(&self) -> Vec<u8> {
+        let mut buf = PASSPORT_CONTEXT.to_vec();
+        for field in [
+            self.identity.to_string().as_bytes(),
+            self.issuer.to_string().as_bytes(),
+            self.destination.as_bytes(),
+            &self.expires_at.to_be_bytes(),
+            &self.nonce,
+            &self.data,
+        ] {
+            buf.extend_from_slice(&(field.len() as u64).to_be_bytes());
+            buf.extend_from_slice(field);
         }
+        buf
+    }
+
+    /// Encode for the `passport` field of `ServerWire::Transfer` and
+    /// `ClientWire::Auth`.
+    ///
+    /// Always MessagePack, whatever codec the session uses: the client relays
+    /// these bytes opaquely and may speak a different codec to the destination.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, CodecError> {
+        Codec::MessagePack.encode(self)
+    }
+
+    /// Decode bytes produced by [`to_bytes`](Self::to_bytes).
+    pub fn from_bytes(data: &[u8]) -> Result<Self, CodecError> {
+        Codec::MessagePack.decode(data)
     }
+}
+
+/// Destination-side passport checks.
+///
+/// Holds the set of origin authorities this destination accepts passports
+/// from, and remembers nonces until their passports expire so each passport
+/// is accepted at most once.
+#[derive(Debug, Clone)]
+pub struct PassportVerifier {
+    destination: String,
+    trusted: HashSet<Identity>,
+    max_ttl: Duration,
+    seen: HashMap<Vec<u8>, u64>,
+}
+
+impl PassportVerifier {
+    /// Longest passport lifetime accepted by default.
+    pub const DEFAULT_MAX_TTL: Duration = Duration::from_secs(300);
 
-    /// Create a passport with a signature.
-    pub fn signed(identity: Identity, data: Vec<u8>, signature: Vec<u8>) -> Self {
+    /// A verifier for passports addressed to `destination`.
+    pub fn new(destination: impl Into<String>) -> Self {
         Self {
-            identity,
-            data,
-            signature: Some(signature),
+            destination: destination.into(),
+            trusted: HashSet::new(),
+            max_ttl: Self::DEFAULT_MAX_TTL,
+            seen: HashMap::new(),
+        }
+    }
+
+    /// Accept passports issued by `origin` (an origin's manifest identity).
+    pub fn trust(mut self, origin: Identity) -> Self {
+        self.trusted.insert(origin);
+        self
+    }
+
+    /// Reject passports valid for longer than `ttl`.
+    ///
+    /// This also bounds how long nonces are remembered.
+    pub fn max_ttl(mut self, ttl: Duration) -> Self {
+        self.max_ttl = ttl;
+        self
+    }
+
+    /// Verify passport bytes presented by `traveller` and mark them used.
+    pub fn verify(&mut self, data: &[u8], traveller: &Identity) -> Result<Passport, PassportError> {
+        self.verify_at(data, traveller, unix_now())
+    }
+
+    /// [`verify`](Self::verify) with an explicit clock (seconds since epoch).
+    pub fn verify_at(
+        &mut self,
+        data: &[u8],
+        traveller: &Identity,
+        now: u64,
+    ) -> Result<Passport, PassportError> {
+        let passport = Passport::from_bytes(data)?;
+
+        if !self.trusted.contains(&passport.issuer) {
+            return Err(PassportError::UntrustedIssuer(passport.issuer));
+        }
+        let signature = passport.signature.as_ref().ok_or(PassportError::Unsigned)?;
+        passport.issuer.verify(&passport.signing_bytes(), signature)?;
+
+        if passport.destination != self.destination {
+            return Err(PassportError::WrongDestination(passport.destination));
+        }
+        if &passport.identity != traveller {
+            return Err(PassportError::WrongIdentity(passport.identity));
+        }
+        if passport.expires_at <= now {
+            return Err(PassportError::Expired);
         }
+        if passport.expires_at - now > self.max_ttl.as_secs() {
+            return Err(PassportError::TooLong);
+        }
+
+        self.seen.retain(|_, expires_at| *expires_at > now);
+        if self.seen.contains_key(&passport.nonce) {
+            return Err(PassportError::Replayed);
+        }
+        self.seen.insert(passport.nonce.clone(), passport.expires_at);
+
+        Ok(passport)
+    }
+}
+
+/// Why a destination refused a passport.
+#[derive(Debug, thiserror::Error)]
+pub enum PassportError {
+    #[error("malformed passport: {0}")]
+    Malformed(#[from] CodecError),
+    #[error("passport is not signed")]
+    Unsigned,
+    #[error("passport issuer {0} is not trusted")]
+    UntrustedIssuer(Identity),
+    #[error("passport signature invalid: {0}")]
+    Signature(#[from] KeyError),
+    #[error("passport is for '{0}', not this authority")]
+    WrongDestination(String),
+    #[error("passport was issued to {0}")]
+    WrongIdentity(Identity),
+    #[error("passport expired")]
+    Expired,
+    #[error("passport lifetime exceeds the allowed maximum")]
+    TooLong,
+    #[error("passport already used")]
+    Replayed,
+}
+
+impl PassportError {
+    /// Error code for `ServerWire::Error`.
+    pub fn code(&self) -> &'static str {
+        match self {
+            PassportError::Expired | PassportError::TooLong => "passport_expired",
+            PassportError::Replayed => "passport_replayed",
+            _ => "passport_rejected",
+        }
+    }
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Keypair;
+
+    const TTL: Duration = Duration::from_secs(60);
+
+    fn setup() -> (Keypair, Identity, PassportVerifier) {
+        let origin = Keypair::from_seed([3; 32]);
+        let alice = Identity::local("alice");
+        let verifier = PassportVerifier::new("ws://b").trust(origin.identity());
+        (origin, alice, verifier)
+    }
+
+    #[test]
+    fn accepts_once() {
+        let (origin, alice, mut verifier) = setup();
+        let bytes = Passport::issue(&origin, alice.clone(), "ws://b", b"inv".to_vec(), TTL)
+            .unwrap()
+            .to_bytes()
+            .unwrap();
+
+        let passport = verifier.verify(&bytes, &alice).unwrap();
+        assert_eq!(passport.data, b"inv");
+        assert!(matches!(
+            verifier.verify(&bytes, &alice),
+            Err(PassportError::Replayed)
+        ));
+    }
+
+    #[test]
+    fn tampering_rejected() {
+        let (origin, alice, mut verifier) = setup();
+        let mut passport =
+            Passport::issue(&origin, alice.clone(), "ws://b", b"1 gold".to_vec(), TTL).unwrap();
+        passport.data = b"999 gold".to_vec();
+
+        let err = verifier.verify(&passport.to_bytes().unwrap(), &alice).unwrap_err();
+        assert!(matches!(err, PassportError::Signature(_)));
+    }
+
+    #[test]
+    fn binding_enforced() {
+        let (origin, alice, mut verifier) = setup();
+
+        let elsewhere = Passport::issue(&origin, alice.clone(), "ws://c", vec![], TTL).unwrap();
+        assert!(matches!(
+            verifier.verify(&elsewhere.to_bytes().unwrap(), &alice),
+            Err(PassportError::WrongDestination(_))
+        ));
+
+        let passport = Passport::issue(&origin, alice, "ws://b", vec![], TTL).unwrap();
+        assert!(matches!(
+            verifier.verify(&passport.to_bytes().unwrap(), &Identity::local("mallory")),
+            Err(PassportError::WrongIdentity(_))
+        ));
+
+        let stranger = Keypair::from_seed([4; 32]);
+        let forged = Passport::issue(&stranger, Identity::local("bob"), "ws://b", vec![], TTL)
+            .unwrap();
+        assert!(matches!(
+            verifier.verify(&forged.to_bytes().unwrap(), &Identity::local("bob")),
+            Err(PassportError::UntrustedIssuer(_))
+        ));
+    }
+
+    #[test]
+    fn expiry_enforced() {
+        let (origin, alice, mut verifier) = setup();
+        let passport = Passport::issue(&origin, alice.clone(), "ws://b", vec![], TTL).unwrap();
+        let bytes = passport.to_bytes().unwrap();
+
+        assert!(matches!(
+            verifier.verify_at(&bytes, &alice, passport.expires_at),
+            Err(PassportError::Expired)
+        ));
+
+        let long = Passport::issue(&origin, alice.clone(), "ws://b", vec![], TTL * 60).unwrap();
+        assert!(matches!(
+            verifier.verify(&long.to_bytes().unwrap(), &alice),
+            Err(PassportError::TooLong)
+        ));
     }
 }
@@ -3,7 +3,13 @@
 //! A transport moves bytes between a client and an authority.
 //! The protocol layer speaks messages; the transport layer moves bytes.
 //!
-//! Implementations: WebSocket, Unix socket, HTTP long-poll, message queue.
+//! Implementations: WebSocket (`interconnect-client`, `interconnect-server`),
+//! length-prefixed byte streams ([`FramedTransport`](crate::FramedTransport):
+//! TCP, Unix sockets, process stdio), the in-memory
+//! [`Loopback`](crate::Loopback), and HTTP (`interconnect-client`,
+//! `interconnect-server`); message queues to come. Compression, logging,
+//! rate and size limits and metrics wrap any of them as layers (see
+//! [`TransportExt`](crate::TransportExt)).
 //!
 //! Note: Discord is NOT a transport. It is a separate authority with its own
 //! rooms. A client can be connected to Discord and another authority
@@ -24,7 +30,47 @@ pub trait Transport: Send {
     ) -> impl std::future::Future<Output = Result<(), Self::Error>> + Send;
 
     /// Receive the next message. Returns `None` when the connection is closed.
+    ///
+    /// Must be cancel-safe: dropping the future before it completes loses no
+    /// message. Sessions race `recv` against their own timers and events.
     fn recv(
         &mut self,
     ) -> impl std::future::Future<Output = Result<Option<Vec<u8>>, Self::Error>> + Send;
 }
+
+/// The sending half of a [`SplitTransport`].
+pub trait SendHalf: Send {
+    type Error: std::error::Error + Send + Sync + 'static;
+
+    /// Send a message.
+    fn send(
+        &mut self,
+        data: &[u8],
+    ) -> impl std::future::Future<Output = Result<(), Self::Error>> + Send;
+}
+
+/// The receiving half of a [`SplitTransport`].
+pub trait RecvHalf: Send {
+    type Error: std::error::Error + Send + Sync + 'static;
+
+    /// Receive the next message. Returns `None` when the connection is closed.
+    /// Cancel-safe, like [`Transport::recv`].
+    fn recv(
+        &mut self,
+    ) -> impl std::future::Future<Output = Result<Option<Vec<u8>>, Self::Error>> + Send;
+}
+
+/// A transport whose two directions can be driven independently: one task
+/// sends while another waits in `recv`.
+///
+/// Implemented by [`Loopback`](crate::Loopback),
+/// [`FramedTransport`](crate::FramedTransport), and the WebSocket and HTTP
+/// transports in `interconnect-client`.
+pub trait SplitTransport: Transport {
+    type Sender: SendHalf<Error = Self::Error>;
+    type Receiver: RecvHalf<Error = Self::Error>;
+
+    /// Separate the directions. The transport closes once both halves are
+    /// dropped.
+    fn split(self) -> (Self::Sender, Self::Receiver);
+}
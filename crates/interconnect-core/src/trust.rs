@@ -0,0 +1,305 @@
+//! Authority identity: signed manifests and known authorities.
+//!
+//! An authority with a cryptographic identity signs the manifest it sends
+//! each client, over a fresh nonce the client chose, so the signature proves
+//! the key holder is on the other end now. The client then looks the
+//! identity up in its [`KnownAuthorities`], keyed by the address it dialled.
+//! Like SSH's `known_hosts`, a changed key is a hard error.
+
+use crate::{Identity, KeyError, Manifest, Signer};
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+/// Domain separator for manifest signatures.
+const MANIFEST_CONTEXT: &[u8] = b"interconnect-manifest-v1:";
+
+/// Length of the nonce a client sends for the manifest signature.
+const NONCE_LEN: usize = 16;
+
+/// A fresh nonce for the `nonce` field of `ClientWire::Auth`.
+pub fn manifest_nonce() -> Result<Vec<u8>, KeyError> {
+    let mut nonce = vec![0u8; NONCE_LEN];
+    getrandom::fill(&mut nonce).map_err(|e| KeyError::Random(e.to_string()))?;
+    Ok(nonce)
+}
+
+impl Manifest {
+    /// The bytes the authority signs: every field but the signature, then
+    /// the client's nonce, each length-prefixed.
+    ///
+    /// Structured fields are signed as JSON, so the signature survives any
+    /// codec.
+    pub fn signing_bytes(&self, nonce: &[u8]) -> Vec<u8> {
+        let mut buf = MANIFEST_CONTEXT.to_vec();
+        for field in [
+            self.identity.to_string().into_bytes(),
+            self.name.clone().into_bytes(),
+            json(&self.substrate),
+            json(&self.protocol),
+            json(&self.schema),
+            json(&self.metadata),
+            nonce.to_vec(),
+        ] {
+            buf.extend_from_slice(&(field.len() as u64).to_be_bytes());
+            buf.extend_from_slice(&field);
+        }
+        buf
+    }
+
+    /// Sign for the client that sent `nonce`.
+    pub fn sign<S: Signer + ?Sized>(&mut self, signer: &S, nonce: &[u8]) {
+        self.signature = Some(signer.sign(&self.signing_bytes(nonce)));
+    }
+
+    /// Check the signature against the manifest's own identity.
+    pub fn verify(&self, nonce: &[u8]) -> Result<(), TrustError> {
+        let signature = self.signature.as_deref().ok_or(TrustError::Unsigned)?;
+        Ok(self
+            .identity
+            .verify(&self.signing_bytes(nonce), signature)?)
+    }
+}
+
+/// Serialize a manifest field. Plain data; this cannot fail.
+fn json(value: &impl serde::Serialize) -> Vec<u8> {
+    serde_json::to_vec(value).unwrap_or_default()
+}
+
+/// How [`KnownAuthorities`] treats addresses it has no entry for.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TrustMode {
+    /// Trust on first use: remember the identity and accept it.
+    #[default]
+    FirstUse,
+    /// Accept only identities pinned in advance.
+    Pinned,
+}
+
+/// Whether [`KnownAuthorities::check`] already knew the authority.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Trust {
+    Known,
+    /// First use; the identity has been recorded.
+    New,
+}
+
+/// Errors from checking an authority's identity.
+#[derive(Debug, thiserror::Error)]
+pub enum TrustError {
+    #[error("io error: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("known authorities line {line}: {reason}")]
+    Malformed { line: usize, reason: String },
+
+    #[error(
+        "authority identity for {address} has changed (known {known}, presented {presented}); \
+         if this is expected, remove the old entry"
+    )]
+    Changed {
+        address: String,
+        known: Identity,
+        presented: Identity,
+    },
+
+    #[error("{address} is not a pinned authority")]
+    Unknown { address: String },
+
+    #[error("manifest is not signed")]
+    Unsigned,
+
+    #[error("manifest signature: {0}")]
+    Signature(#[from] KeyError),
+}
+
+/// Identities of the authorities a client has met, by address.
+///
+/// Stored as text, one `<address> <identity>` per line; `#` starts a
+/// comment. Cheap to clone; clones share entries.
+#[derive(Debug, Clone, Default)]
+pub struct KnownAuthorities {
+    path: Option<PathBuf>,
+    mode: TrustMode,
+    entries: Arc<Mutex<BTreeMap<String, Identity>>>,
+}
+
+impl KnownAuthorities {
+    /// A store that lives only in memory.
+    pub fn in_memory() -> Self {
+        Self::default()
+    }
+
+    /// Load the store at `path`, which need not exist yet. New entries are
+    /// appended to it.
+    pub fn open(path: impl Into<PathBuf>) -> Result<Self, TrustError> {
+        let path = path.into();
+        let text = match std::fs::read_to_string(&path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+            Err(e) => return Err(e.into()),
+        };
+        let mut entries = BTreeMap::new();
+        for (i, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or_default().trim();
+            if line.is_empty() {
+                continue;
+            }
+            let malformed = |reason: String| TrustError::Malformed {
+                line: i + 1,
+                reason,
+            };
+            let (address, identity) = line
+                .split_once(char::is_whitespace)
+                .ok_or_else(|| malformed("expected `<address> <identity>`".into()))?;
+            let identity = identity
+                .trim()
+                .parse::<Identity>()
+                .map_err(|e| malformed(e.to_string()))?;
+            entries.insert(address.to_string(), identity);
+        }
+        Ok(Self {
+            path: Some(path),
+            mode: TrustMode::default(),
+            entries: Arc::new(Mutex::new(entries)),
+        })
+    }
+
+    /// Treat unknown addresses according to `mode`.
+    pub fn mode(mut self, mode: TrustMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    pub fn path(&self) -> Option<&Path> {
+        self.path.as_deref()
+    }
+
+    /// The identity recorded for `address`.
+    pub fn get(&self, address: &str) -> Option<Identity> {
+        self.entries.lock().unwrap().get(address).cloned()
+    }
+
+    /// Record `identity` for `address`, replacing any previous entry.
+    pub fn pin(&self, address: &str, identity: Identity) -> Result<(), TrustError> {
+        let mut entries = self.entries.lock().unwrap();
+        let replaced = entries
+            .insert(address.to_string(), identity.clone())
+            .is_some();
+        if replaced {
+            self.rewrite(&entries)
+        } else {
+            self.append(address, &identity)
+        }
+    }
+
+    /// Check that `identity` may speak for `address`.
+    pub fn check(&self, address: &str, identity: &Identity) -> Result<Trust, TrustError> {
+        let mut entries = self.entries.lock().unwrap();
+        match entries.get(address) {
+            Some(known) if known == identity => Ok(Trust::Known),
+            Some(known) => Err(TrustError::Changed {
+                address: address.to_string(),
+                known: known.clone(),
+                presented: identity.clone(),
+            }),
+            None if self.mode == TrustMode::Pinned => Err(TrustError::Unknown {
+                address: address.to_string(),
+            }),
+            None => {
+                self.append(address, identity)?;
+                entries.insert(address.to_string(), identity.clone());
+                Ok(Trust::New)
+            }
+        }
+    }
+
+    fn append(&self, address: &str, identity: &Identity) -> Result<(), TrustError> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let mut file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)?;
+        writeln!(file, "{address} {identity}")?;
+        Ok(())
+    }
+
+    fn rewrite(&self, entries: &BTreeMap<String, Identity>) -> Result<(), TrustError> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        let text: String = entries
+            .iter()
+            .map(|(address, identity)| format!("{address} {identity}\n"))
+            .collect();
+        std::fs::write(path, text)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Keypair;
+
+    fn manifest(key: &Keypair) -> Manifest {
+        Manifest::new(key.identity(), "room").metadata(serde_json::json!({ "topic": "rust" }))
+    }
+
+    #[test]
+    fn signatures_bind_the_nonce() {
+        let key = Keypair::from_seed([3; 32]);
+        let mut signed = manifest(&key);
+        signed.sign(&key, b"nonce-a");
+        signed.verify(b"nonce-a").unwrap();
+        assert!(signed.verify(b"nonce-b").is_err());
+
+        // Survives a trip through a binary codec.
+        let bytes = crate::Codec::MessagePack.encode(&signed).unwrap();
+        let decoded: Manifest = crate::Codec::MessagePack.decode(&bytes).unwrap();
+        decoded.verify(b"nonce-a").unwrap();
+
+        signed.name = "impostor".into();
+        assert!(signed.verify(b"nonce-a").is_err());
+        assert!(matches!(
+            manifest(&key).verify(b"nonce-a"),
+            Err(TrustError::Unsigned)
+        ));
+    }
+
+    #[test]
+    fn changed_keys_are_refused() {
+        let dir = std::env::temp_dir().join(format!("known-{}", std::process::id()));
+        let path = dir.join("known_authorities");
+        let _ = std::fs::remove_file(&path);
+        let a = Keypair::from_seed([1; 32]).identity();
+        let b = Keypair::from_seed([2; 32]).identity();
+
+        let known = KnownAuthorities::open(&path).unwrap();
+        assert_eq!(known.check("ws://room", &a).unwrap(), Trust::New);
+        assert_eq!(known.check("ws://room", &a).unwrap(), Trust::Known);
+
+        // Reloaded from disk, the entry still holds.
+        let known = KnownAuthorities::open(&path)
+            .unwrap()
+            .mode(TrustMode::Pinned);
+        assert!(matches!(
+            known.check("ws://room", &b),
+            Err(TrustError::Changed { .. })
+        ));
+        assert!(matches!(
+            known.check("ws://other", &a),
+            Err(TrustError::Unknown { .. })
+        ));
+        known.pin("ws://room", b.clone()).unwrap();
+        let known = KnownAuthorities::open(&path).unwrap();
+        assert_eq!(known.get("ws://room"), Some(b));
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
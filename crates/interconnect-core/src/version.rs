@@ -0,0 +1,173 @@
+//! Protocol versions and feature negotiation.
+//!
+//! The client lists the versions and optional features it speaks in
+//! `ClientWire::Auth`. The authority picks the highest common version and the
+//! features both sides support, and confirms them in the `Manifest`. Features
+//! are strings on the wire so either side can list ones the other has never
+//! heard of; those are simply not negotiated.
+
+use crate::Codec;
+use serde::{Deserialize, Serialize};
+
+/// The newest protocol version this crate speaks.
+pub const PROTOCOL_VERSION: u32 = 1;
+
+/// Every protocol version this crate speaks.
+pub const SUPPORTED_VERSIONS: &[u32] = &[1];
+
+/// Optional protocol features.
+pub mod feature {
+    /// `ServerWire::Delta` against acknowledged snapshots.
+    pub const DELTAS: &str = "deltas";
+    /// `ClientWire::SubstrateRequest` and `ServerWire::SubstrateChunk`.
+    pub const SUBSTRATE: &str = "substrate";
+    /// `ClientWire::Request` and `ServerWire::IntentResult`.
+    pub const INTENT_RESULTS: &str = "intent_results";
+    /// A refused passport is reported as `ServerWire::Error` with code
+    /// `passport_rejected` rather than as a system message. The session
+    /// goes ahead either way.
+    pub const PASSPORT_ERRORS: &str = "passport_errors";
+    /// `ServerWire::Resume` tokens, and `resume` in `ClientWire::Auth` to
+    /// pick up a dropped session.
+    pub const RESUME: &str = "resume";
+
+    /// Every feature this crate implements.
+    pub const ALL: &[&str] = &[DELTAS, SUBSTRATE, INTENT_RESULTS, PASSPORT_ERRORS, RESUME];
+}
+
+/// What one side of a connection speaks.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Capabilities {
+    /// Protocol versions, in any order.
+    pub versions: Vec<u32>,
+    /// Optional features (see [`feature`]).
+    #[serde(default)]
+    pub features: Vec<String>,
+}
+
+impl Default for Capabilities {
+    fn default() -> Self {
+        Self::current()
+    }
+}
+
+impl Capabilities {
+    /// Everything this crate supports.
+    pub fn current() -> Self {
+        Self {
+            versions: SUPPORTED_VERSIONS.to_vec(),
+            features: feature::ALL.iter().map(|f| f.to_string()).collect(),
+        }
+    }
+
+    /// What a client that predates negotiation (and so sends no
+    /// capabilities) speaks: version 1 with its features.
+    pub fn legacy() -> Self {
+        Self {
+            versions: vec![1],
+            features: [feature::DELTAS, feature::SUBSTRATE, feature::INTENT_RESULTS]
+                .iter()
+                .map(|f| f.to_string())
+                .collect(),
+        }
+    }
+
+    /// Drop `feature`, e.g. to opt out of deltas.
+    pub fn without(mut self, feature: &str) -> Self {
+        self.features.retain(|f| f != feature);
+        self
+    }
+
+    /// Whether `feature` is listed.
+    pub fn supports(&self, feature: &str) -> bool {
+        self.features.iter().any(|f| f == feature)
+    }
+
+    /// Agree on the highest version and the features both `self` and `peer`
+    /// speak, for a session using `codec`.
+    pub fn negotiate(&self, peer: &Capabilities, codec: Codec) -> Result<Protocol, VersionError> {
+        let version = self
+            .versions
+            .iter()
+            .filter(|v| peer.versions.contains(v))
+            .max()
+            .copied()
+            .ok_or_else(|| VersionError {
+                ours: self.versions.clone(),
+                theirs: peer.versions.clone(),
+            })?;
+        let features = self
+            .features
+            .iter()
+            .filter(|f| peer.supports(f))
+            .cloned()
+            .collect();
+        Ok(Protocol {
+            version,
+            features,
+            codec,
+        })
+    }
+}
+
+/// The protocol an authority settled on for a session, confirmed in the
+/// `Manifest`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Protocol {
+    /// Negotiated protocol version.
+    pub version: u32,
+    /// Features both sides support.
+    #[serde(default)]
+    pub features: Vec<String>,
+    /// Codec of the session, detected from the client's `Auth`.
+    pub codec: Codec,
+}
+
+impl Protocol {
+    /// Whether `feature` was negotiated.
+    pub fn supports(&self, feature: &str) -> bool {
+        self.features.iter().any(|f| f == feature)
+    }
+}
+
+/// The two sides share no protocol version.
+#[derive(Debug, Clone, thiserror::Error)]
+#[error("incompatible protocol: we speak versions {ours:?}, peer speaks {theirs:?}")]
+pub struct VersionError {
+    pub ours: Vec<u32>,
+    pub theirs: Vec<u32>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn negotiates_highest_common_version_and_shared_features() {
+        let ours = Capabilities {
+            versions: vec![1, 2, 3],
+            features: vec!["deltas".into(), "substrate".into()],
+        };
+        let theirs = Capabilities {
+            versions: vec![2, 1],
+            features: vec!["substrate".into(), "teleport".into()],
+        };
+        let protocol = ours.negotiate(&theirs, Codec::Cbor).unwrap();
+        assert_eq!(protocol.version, 2);
+        assert_eq!(protocol.features, ["substrate"]);
+        assert!(!protocol.supports(feature::DELTAS));
+        assert_eq!(protocol.codec, Codec::Cbor);
+    }
+
+    #[test]
+    fn no_common_version() {
+        let theirs = Capabilities {
+            versions: vec![9],
+            features: Vec::new(),
+        };
+        let err = Capabilities::current()
+            .negotiate(&theirs, Codec::Json)
+            .unwrap_err();
+        assert_eq!(err.theirs, [9]);
+    }
+}
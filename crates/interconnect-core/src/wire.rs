@@ -23,33 +23,110 @@ pub enum ClientWire<I> {
         /// Display name (optional).
         #[serde(default)]
         name: Option<String>,
-        /// Passport data if transferring from another server.
-        #[serde(default)]
+        /// Passport bytes from `ServerWire::Transfer`, relayed unchanged, if
+        /// transferring from another server.
+        #[serde(default, with = "serde_bytes")]
         passport: Option<Vec<u8>>,
+        /// Protocol versions and features the client speaks. Clients that
+        /// predate negotiation omit it and get
+        /// [`Capabilities::legacy`](crate::Capabilities::legacy).
+        #[serde(default)]
+        capabilities: Option<crate::Capabilities>,
+        /// Random bytes the authority signs into the manifest, proving it
+        /// holds its identity key (see [`Manifest::verify`]).
+        #[serde(default, with = "serde_bytes")]
+        nonce: Option<Vec<u8>>,
+        /// Pick up a session that was dropped, instead of starting a new one.
+        #[serde(default)]
+        resume: Option<Resume>,
+    },
+    /// Answer to `ServerWire::Challenge`: a signature over
+    /// [`challenge_message`](crate::challenge_message)`(authority, nonce)`
+    /// made with the key behind the claimed identity.
+    ChallengeResponse {
+        #[serde(with = "serde_bytes")]
+        signature: Vec<u8>,
     },
     /// Send an intent.
     Intent(I),
-    /// Acknowledge a snapshot.
+    /// Send an intent the authority answers with a
+    /// `ServerWire::IntentResult` carrying the same `id`.
+    Request { id: u64, intent: I },
+    /// Acknowledge a snapshot (full or rebuilt from a delta). The authority
+    /// may then send deltas against it.
     Ack { seq: u64 },
     /// Request transfer to another server.
     TransferRequest { destination: String },
+    /// Request the substrate with this hash, answered with
+    /// `ServerWire::SubstrateChunk`s.
+    SubstrateRequest { hash: String },
     /// Ping (keep-alive).
     Ping,
 }
 
+/// The `resume` field of `ClientWire::Auth`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Resume {
+    /// The token from the session's last `ServerWire::Resume`.
+    #[serde(with = "serde_bytes")]
+    pub token: Vec<u8>,
+    /// The last snapshot the client acknowledged. The authority carries on
+    /// from it, with a delta where it can.
+    pub seq: u64,
+}
+
 /// Messages sent from server to client.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(tag = "type", rename_all = "snake_case")]
 pub enum ServerWire<S> {
+    /// Proof-of-identity request, sent after `Auth` and before `Manifest`
+    /// when the authority wants the client to prove it holds its key.
+    Challenge {
+        #[serde(with = "serde_bytes")]
+        nonce: Vec<u8>,
+        /// The authority's identity, as in its manifest. The client signs it
+        /// with the nonce, so the answer is only good at this authority.
+        authority: Identity,
+    },
+    /// Token for resuming this session after a disconnect, sent before
+    /// `Manifest` when both sides speak `resume`. `resumed` says whether the
+    /// `ClientWire::Auth` picked up a dropped session; if not, a new one
+    /// began. Each token is good for one resume.
+    Resume {
+        #[serde(with = "serde_bytes")]
+        token: Vec<u8>,
+        resumed: bool,
+    },
     /// Server manifest.
     Manifest(Manifest),
     /// State snapshot.
     Snapshot { seq: u64, data: S },
+    /// State change as an RFC 6902 JSON Patch against snapshot `base_seq`,
+    /// which the client has acknowledged. Applying it yields snapshot `seq`.
+    Delta {
+        base_seq: u64,
+        seq: u64,
+        patch: crate::Patch,
+    },
+    /// Part of a substrate: `data` covers bytes `offset..offset + data.len()`
+    /// of `total`. Chunks arrive in order.
+    SubstrateChunk {
+        hash: String,
+        offset: u64,
+        total: u64,
+        #[serde(with = "serde_bytes")]
+        data: Vec<u8>,
+    },
     /// Transfer directive.
     Transfer {
         destination: String,
+        /// Signed [`Passport`](crate::Passport), see `Passport::to_bytes`.
+        #[serde(with = "serde_bytes")]
         passport: Vec<u8>,
     },
+    /// Outcome of the `ClientWire::Request` with this `id`. For accepted
+    /// intents it arrives before the snapshot showing their effect.
+    IntentResult { id: u64, outcome: IntentOutcome },
     /// Error message.
     Error { code: String, message: String },
     /// System message (informational).
@@ -75,6 +152,35 @@ impl<S> ServerWire<S> {
     }
 }
 
+/// What the authority did with a `ClientWire::Request`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum IntentOutcome {
+    /// The intent was applied.
+    Accepted,
+    /// The intent was refused, with the same `code` and `message` an
+    /// uncorrelated intent would get in `ServerWire::Error`.
+    Rejected { code: String, message: String },
+}
+
+impl IntentOutcome {
+    /// Create a rejection.
+    pub fn rejected(code: impl Into<String>, message: impl Into<String>) -> Self {
+        Self::Rejected {
+            code: code.into(),
+            message: message.into(),
+        }
+    }
+
+    /// Whether the intent was applied.
+    pub fn is_accepted(&self) -> bool {
+        matches!(self, Self::Accepted)
+    }
+}
+
+// JSON helpers. Equivalent to `Codec::Json`; kept for callers that only
+// ever speak JSON (browser clients, platform connectors).
+
 /// Serialize a wire message to JSON bytes.
 pub fn to_json<T: Serialize>(msg: &T) -> Result<Vec<u8>, serde_json::Error> {
     serde_json::to_vec(msg)
@@ -126,6 +232,30 @@ mod tests {
         }
     }
 
+    #[test]
+    fn intent_result_roundtrip() {
+        let msg: ClientWire<TestIntent> = ClientWire::Request {
+            id: 7,
+            intent: TestIntent::Chat { msg: "hi".into() },
+        };
+        let parsed: ClientWire<TestIntent> = from_json(&to_json(&msg).unwrap()).unwrap();
+        assert!(matches!(parsed, ClientWire::Request { id: 7, .. }));
+
+        let msg: ServerWire<TestSnapshot> = ServerWire::IntentResult {
+            id: 7,
+            outcome: IntentOutcome::rejected("intent_error", "no"),
+        };
+        let json = to_json_string(&msg).unwrap();
+        assert!(json.contains(r#""status":"rejected""#));
+        match from_json_str(&json).unwrap() {
+            ServerWire::<TestSnapshot>::IntentResult { id, outcome } => {
+                assert_eq!(id, 7);
+                assert!(!outcome.is_accepted());
+            }
+            _ => panic!("wrong variant"),
+        }
+    }
+
     #[test]
     fn server_wire_roundtrip() {
         let msg: ServerWire<TestSnapshot> = ServerWire::Snapshot {
@@ -2,8 +2,11 @@
 //!
 //! Each connector runs in its own task. The daemon communicates with it via
 //! unbounded channels of `serde_json::Value` — intents in, snapshots out.
+//! A connection that drops is reconnected with backoff; intents sent in the
+//! meantime wait for it.
 
-use interconnect_core::ServerWire;
+use interconnect_client::{Backoff, ClientError, ReconnectEvent, ReconnectingConnection};
+use interconnect_core::{ServerWire, Transport, Wire};
 use serde::Deserialize;
 use tokio::sync::mpsc;
 
@@ -71,45 +74,44 @@ fn parse_opts<T: for<'de> Deserialize<'de>>(
 }
 
 // ---------------------------------------------------------------------------
-// Per-connector spawners
+// Room task
 // ---------------------------------------------------------------------------
 
-async fn spawn_slack(
-    config: &RoomConfig,
+/// Push `snapshot`, then run `conn` in its own task: snapshots out to
+/// `push_tx`, intents in from the returned handle.
+fn run_room<T, I, S>(
+    mut conn: ReconnectingConnection<T, I, S>,
+    snapshot: S,
     push_tx: mpsc::UnboundedSender<serde_json::Value>,
-) -> Result<RoomHandle, RoomError> {
-    #[derive(Deserialize)]
-    struct Opts {
-        bot_token: String,
-        app_token: String,
-        channel_id: String,
-    }
-    let opts: Opts = parse_opts(config)?;
-
-    let (mut conn, snapshot) =
-        interconnect_connector_slack::connect(opts.bot_token, opts.app_token, opts.channel_id)
-            .await
-            .map_err(|e| RoomError::Connect(e.to_string()))?;
-
-    let _ = push_tx.send(
-        serde_json::to_value(&snapshot).unwrap_or(serde_json::Value::Null),
-    );
+    label: &'static str,
+) -> RoomHandle
+where
+    T: Transport + 'static,
+    T::Error: Into<ClientError>,
+    I: Wire + Clone,
+    S: Wire,
+{
+    let push = move |data: &S| {
+        let _ = push_tx.send(serde_json::to_value(data).unwrap_or(serde_json::Value::Null));
+    };
+    push(&snapshot);
 
     let (intent_tx, mut intent_rx) = mpsc::unbounded_channel::<serde_json::Value>();
 
     tokio::spawn(async move {
         loop {
             tokio::select! {
-                msg = conn.recv() => {
-                    match msg {
-                        Ok(Some(ServerWire::Snapshot { data, .. })) => {
-                            let _ = push_tx.send(
-                                serde_json::to_value(&data).unwrap_or(serde_json::Value::Null),
-                            );
-                        }
+                event = conn.recv() => {
+                    match event {
+                        Ok(Some(ReconnectEvent::Message(ServerWire::Snapshot { data, .. })))
+                        | Ok(Some(ReconnectEvent::Reconnected { snapshot: data })) => push(&data),
+                        Ok(Some(ReconnectEvent::Reconnecting { delay, error, .. })) => match error {
+                            Some(e) => eprintln!("{label} room lost ({e}); reconnecting in {delay:?}"),
+                            None => eprintln!("{label} room closed; reconnecting in {delay:?}"),
+                        },
                         Ok(Some(_)) => {}
                         Ok(None) => break,
-                        Err(e) => { eprintln!("slack room error: {e}"); break; }
+                        Err(e) => { eprintln!("{label} room error: {e}"); break; }
                     }
                 }
                 intent = intent_rx.recv() => {
@@ -126,7 +128,39 @@ async fn spawn_slack(
         }
     });
 
-    Ok(RoomHandle { tx: intent_tx })
+    RoomHandle { tx: intent_tx }
+}
+
+// ---------------------------------------------------------------------------
+// Per-connector spawners
+// ---------------------------------------------------------------------------
+
+async fn spawn_slack(
+    config: &RoomConfig,
+    push_tx: mpsc::UnboundedSender<serde_json::Value>,
+) -> Result<RoomHandle, RoomError> {
+    #[derive(Deserialize)]
+    struct Opts {
+        bot_token: String,
+        app_token: String,
+        channel_id: String,
+    }
+    let opts: Opts = parse_opts(config)?;
+
+    let (conn, snapshot) = ReconnectingConnection::connect(
+        move || {
+            interconnect_connector_slack::connect(
+                opts.bot_token.clone(),
+                opts.app_token.clone(),
+                opts.channel_id.clone(),
+            )
+        },
+        Backoff::default(),
+    )
+    .await
+    .map_err(|e| RoomError::Connect(e.to_string()))?;
+
+    Ok(run_room(conn, snapshot, push_tx, "slack"))
 }
 
 async fn spawn_discord(
@@ -141,47 +175,14 @@ async fn spawn_discord(
     let opts: Opts = parse_opts(config)?;
 
     let channel_id = interconnect_connector_discord::Id::new(opts.channel_id);
-    let (mut conn, snapshot) =
-        interconnect_connector_discord::connect(opts.token, channel_id)
-            .await
-            .map_err(|e| RoomError::Connect(e.to_string()))?;
-
-    let _ = push_tx.send(
-        serde_json::to_value(&snapshot).unwrap_or(serde_json::Value::Null),
-    );
-
-    let (intent_tx, mut intent_rx) = mpsc::unbounded_channel::<serde_json::Value>();
-
-    tokio::spawn(async move {
-        loop {
-            tokio::select! {
-                msg = conn.recv() => {
-                    match msg {
-                        Ok(Some(ServerWire::Snapshot { data, .. })) => {
-                            let _ = push_tx.send(
-                                serde_json::to_value(&data).unwrap_or(serde_json::Value::Null),
-                            );
-                        }
-                        Ok(Some(_)) => {}
-                        Ok(None) => break,
-                        Err(e) => { eprintln!("discord room error: {e}"); break; }
-                    }
-                }
-                intent = intent_rx.recv() => {
-                    match intent {
-                        Some(payload) => {
-                            if let Ok(intent) = serde_json::from_value(payload) {
-                                let _ = conn.send_intent(intent).await;
-                            }
-                        }
-                        None => break,
-                    }
-                }
-            }
-        }
-    });
+    let (conn, snapshot) = ReconnectingConnection::connect(
+        move || interconnect_connector_discord::connect(opts.token.clone(), channel_id),
+        Backoff::default(),
+    )
+    .await
+    .map_err(|e| RoomError::Connect(e.to_string()))?;
 
-    Ok(RoomHandle { tx: intent_tx })
+    Ok(run_room(conn, snapshot, push_tx, "discord"))
 }
 
 async fn spawn_sqlite(
@@ -206,47 +207,14 @@ async fn spawn_sqlite_generic(
     }
     let opts: Opts = parse_opts(config)?;
 
-    let (mut conn, snapshot) =
-        interconnect_connector_sqlite::connect(opts.path, opts.table)
-            .await
-            .map_err(|e| RoomError::Connect(e.to_string()))?;
-
-    let _ = push_tx.send(
-        serde_json::to_value(&snapshot).unwrap_or(serde_json::Value::Null),
-    );
-
-    let (intent_tx, mut intent_rx) = mpsc::unbounded_channel::<serde_json::Value>();
-
-    tokio::spawn(async move {
-        loop {
-            tokio::select! {
-                msg = conn.recv() => {
-                    match msg {
-                        Ok(Some(ServerWire::Snapshot { data, .. })) => {
-                            let _ = push_tx.send(
-                                serde_json::to_value(&data).unwrap_or(serde_json::Value::Null),
-                            );
-                        }
-                        Ok(Some(_)) => {}
-                        Ok(None) => break,
-                        Err(e) => { eprintln!("sqlite room error: {e}"); break; }
-                    }
-                }
-                intent = intent_rx.recv() => {
-                    match intent {
-                        Some(payload) => {
-                            if let Ok(intent) = serde_json::from_value(payload) {
-                                let _ = conn.send_intent(intent).await;
-                            }
-                        }
-                        None => break,
-                    }
-                }
-            }
-        }
-    });
+    let (conn, snapshot) = ReconnectingConnection::connect(
+        move || interconnect_connector_sqlite::connect(opts.path.clone(), opts.table.clone()),
+        Backoff::default(),
+    )
+    .await
+    .map_err(|e| RoomError::Connect(e.to_string()))?;
 
-    Ok(RoomHandle { tx: intent_tx })
+    Ok(run_room(conn, snapshot, push_tx, "sqlite"))
 }
 
 async fn spawn_sqlite_chat(
@@ -271,47 +239,16 @@ async fn spawn_sqlite_chat(
         interconnect_connector_sqlite::ChatLogConfig::chat_default()
     };
 
-    let (mut conn, snapshot) =
-        interconnect_connector_sqlite::connect_chat(opts.path, chat_config)
-            .await
-            .map_err(|e| RoomError::Connect(e.to_string()))?;
-
-    let _ = push_tx.send(
-        serde_json::to_value(&snapshot).unwrap_or(serde_json::Value::Null),
-    );
-
-    let (intent_tx, mut intent_rx) = mpsc::unbounded_channel::<serde_json::Value>();
-
-    tokio::spawn(async move {
-        loop {
-            tokio::select! {
-                msg = conn.recv() => {
-                    match msg {
-                        Ok(Some(ServerWire::Snapshot { data, .. })) => {
-                            let _ = push_tx.send(
-                                serde_json::to_value(&data).unwrap_or(serde_json::Value::Null),
-                            );
-                        }
-                        Ok(Some(_)) => {}
-                        Ok(None) => break,
-                        Err(e) => { eprintln!("sqlite chat room error: {e}"); break; }
-                    }
-                }
-                intent = intent_rx.recv() => {
-                    match intent {
-                        Some(payload) => {
-                            if let Ok(intent) = serde_json::from_value::<interconnect_connector_sqlite::ChatIntent>(payload) {
-                                let _ = conn.send_intent(intent).await;
-                            }
-                        }
-                        None => break,
-                    }
-                }
-            }
-        }
-    });
+    let (conn, snapshot) = ReconnectingConnection::connect(
+        move || {
+            interconnect_connector_sqlite::connect_chat(opts.path.clone(), chat_config.clone())
+        },
+        Backoff::default(),
+    )
+    .await
+    .map_err(|e| RoomError::Connect(e.to_string()))?;
 
-    Ok(RoomHandle { tx: intent_tx })
+    Ok(run_room(conn, snapshot, push_tx, "sqlite chat"))
 }
 
 async fn spawn_telegram(
@@ -325,47 +262,14 @@ async fn spawn_telegram(
     }
     let opts: Opts = parse_opts(config)?;
 
-    let (mut conn, snapshot) =
-        interconnect_connector_telegram::connect(opts.bot_token, opts.chat_id)
-            .await
-            .map_err(|e| RoomError::Connect(e.to_string()))?;
-
-    let _ = push_tx.send(
-        serde_json::to_value(&snapshot).unwrap_or(serde_json::Value::Null),
-    );
-
-    let (intent_tx, mut intent_rx) = mpsc::unbounded_channel::<serde_json::Value>();
-
-    tokio::spawn(async move {
-        loop {
-            tokio::select! {
-                msg = conn.recv() => {
-                    match msg {
-                        Ok(Some(ServerWire::Snapshot { data, .. })) => {
-                            let _ = push_tx.send(
-                                serde_json::to_value(&data).unwrap_or(serde_json::Value::Null),
-                            );
-                        }
-                        Ok(Some(_)) => {}
-                        Ok(None) => break,
-                        Err(e) => { eprintln!("telegram room error: {e}"); break; }
-                    }
-                }
-                intent = intent_rx.recv() => {
-                    match intent {
-                        Some(payload) => {
-                            if let Ok(intent) = serde_json::from_value(payload) {
-                                let _ = conn.send_intent(intent).await;
-                            }
-                        }
-                        None => break,
-                    }
-                }
-            }
-        }
-    });
+    let (conn, snapshot) = ReconnectingConnection::connect(
+        move || interconnect_connector_telegram::connect(opts.bot_token.clone(), opts.chat_id),
+        Backoff::default(),
+    )
+    .await
+    .map_err(|e| RoomError::Connect(e.to_string()))?;
 
-    Ok(RoomHandle { tx: intent_tx })
+    Ok(run_room(conn, snapshot, push_tx, "telegram"))
 }
 
 async fn spawn_matrix(
@@ -380,47 +284,20 @@ async fn spawn_matrix(
     }
     let opts: Opts = parse_opts(config)?;
 
-    let (mut conn, snapshot) =
-        interconnect_connector_matrix::connect(opts.homeserver, opts.access_token, opts.room_id)
-            .await
-            .map_err(|e| RoomError::Connect(e.to_string()))?;
-
-    let _ = push_tx.send(
-        serde_json::to_value(&snapshot).unwrap_or(serde_json::Value::Null),
-    );
-
-    let (intent_tx, mut intent_rx) = mpsc::unbounded_channel::<serde_json::Value>();
-
-    tokio::spawn(async move {
-        loop {
-            tokio::select! {
-                msg = conn.recv() => {
-                    match msg {
-                        Ok(Some(ServerWire::Snapshot { data, .. })) => {
-                            let _ = push_tx.send(
-                                serde_json::to_value(&data).unwrap_or(serde_json::Value::Null),
-                            );
-                        }
-                        Ok(Some(_)) => {}
-                        Ok(None) => break,
-                        Err(e) => { eprintln!("matrix room error: {e}"); break; }
-                    }
-                }
-                intent = intent_rx.recv() => {
-                    match intent {
-                        Some(payload) => {
-                            if let Ok(intent) = serde_json::from_value(payload) {
-                                let _ = conn.send_intent(intent).await;
-                            }
-                        }
-                        None => break,
-                    }
-                }
-            }
-        }
-    });
+    let (conn, snapshot) = ReconnectingConnection::connect(
+        move || {
+            interconnect_connector_matrix::connect(
+                opts.homeserver.clone(),
+                opts.access_token.clone(),
+                opts.room_id.clone(),
+            )
+        },
+        Backoff::default(),
+    )
+    .await
+    .map_err(|e| RoomError::Connect(e.to_string()))?;
 
-    Ok(RoomHandle { tx: intent_tx })
+    Ok(run_room(conn, snapshot, push_tx, "matrix"))
 }
 
 async fn spawn_irc(
@@ -436,47 +313,21 @@ async fn spawn_irc(
     }
     let opts: Opts = parse_opts(config)?;
 
-    let (mut conn, snapshot) =
-        interconnect_connector_irc::connect(opts.server, opts.port, opts.nick, opts.channel)
-            .await
-            .map_err(|e| RoomError::Connect(e.to_string()))?;
-
-    let _ = push_tx.send(
-        serde_json::to_value(&snapshot).unwrap_or(serde_json::Value::Null),
-    );
-
-    let (intent_tx, mut intent_rx) = mpsc::unbounded_channel::<serde_json::Value>();
-
-    tokio::spawn(async move {
-        loop {
-            tokio::select! {
-                msg = conn.recv() => {
-                    match msg {
-                        Ok(Some(ServerWire::Snapshot { data, .. })) => {
-                            let _ = push_tx.send(
-                                serde_json::to_value(&data).unwrap_or(serde_json::Value::Null),
-                            );
-                        }
-                        Ok(Some(_)) => {}
-                        Ok(None) => break,
-                        Err(e) => { eprintln!("irc room error: {e}"); break; }
-                    }
-                }
-                intent = intent_rx.recv() => {
-                    match intent {
-                        Some(payload) => {
-                            if let Ok(intent) = serde_json::from_value(payload) {
-                                let _ = conn.send_intent(intent).await;
-                            }
-                        }
-                        None => break,
-                    }
-                }
-            }
-        }
-    });
+    let (conn, snapshot) = ReconnectingConnection::connect(
+        move || {
+            interconnect_connector_irc::connect(
+                opts.server.clone(),
+                opts.port,
+                opts.nick.clone(),
+                opts.channel.clone(),
+            )
+        },
+        Backoff::default(),
+    )
+    .await
+    .map_err(|e| RoomError::Connect(e.to_string()))?;
 
-    Ok(RoomHandle { tx: intent_tx })
+    Ok(run_room(conn, snapshot, push_tx, "irc"))
 }
 
 async fn spawn_zulip(
@@ -493,52 +344,22 @@ async fn spawn_zulip(
     }
     let opts: Opts = parse_opts(config)?;
 
-    let (mut conn, snapshot) = interconnect_connector_zulip::connect(
-        opts.realm,
-        opts.email,
-        opts.api_key,
-        opts.stream,
-        opts.topic,
+    let (conn, snapshot) = ReconnectingConnection::connect(
+        move || {
+            interconnect_connector_zulip::connect(
+                opts.realm.clone(),
+                opts.email.clone(),
+                opts.api_key.clone(),
+                opts.stream.clone(),
+                opts.topic.clone(),
+            )
+        },
+        Backoff::default(),
     )
     .await
     .map_err(|e| RoomError::Connect(e.to_string()))?;
 
-    let _ = push_tx.send(
-        serde_json::to_value(&snapshot).unwrap_or(serde_json::Value::Null),
-    );
-
-    let (intent_tx, mut intent_rx) = mpsc::unbounded_channel::<serde_json::Value>();
-
-    tokio::spawn(async move {
-        loop {
-            tokio::select! {
-                msg = conn.recv() => {
-                    match msg {
-                        Ok(Some(ServerWire::Snapshot { data, .. })) => {
-                            let _ = push_tx.send(
-                                serde_json::to_value(&data).unwrap_or(serde_json::Value::Null),
-                            );
-                        }
-                        Ok(Some(_)) => {}
-                        Ok(None) => break,
-                        Err(e) => { eprintln!("zulip room error: {e}"); break; }
-                    }
-                }
-                intent = intent_rx.recv() => {
-                    match intent {
-                        Some(payload) => {
-                            if let Ok(intent) = serde_json::from_value(payload) {
-                                let _ = conn.send_intent(intent).await;
-                            }
-                        }
-                        None => break,
-                    }
-                }
-            }
-        }
-    });
-
-    Ok(RoomHandle { tx: intent_tx })
+    Ok(run_room(conn, snapshot, push_tx, "zulip"))
 }
 
 async fn spawn_maillist(
@@ -554,51 +375,21 @@ async fn spawn_maillist(
     }
     let opts: Opts = parse_opts(config)?;
 
-    let (mut conn, snapshot) = interconnect_connector_maillist::connect(
-        opts.base_url,
-        opts.username,
-        opts.password,
-        opts.list_id,
+    let (conn, snapshot) = ReconnectingConnection::connect(
+        move || {
+            interconnect_connector_maillist::connect(
+                opts.base_url.clone(),
+                opts.username.clone(),
+                opts.password.clone(),
+                opts.list_id,
+            )
+        },
+        Backoff::default(),
     )
     .await
     .map_err(|e| RoomError::Connect(e.to_string()))?;
 
-    let _ = push_tx.send(
-        serde_json::to_value(&snapshot).unwrap_or(serde_json::Value::Null),
-    );
-
-    let (intent_tx, mut intent_rx) = mpsc::unbounded_channel::<serde_json::Value>();
-
-    tokio::spawn(async move {
-        loop {
-            tokio::select! {
-                msg = conn.recv() => {
-                    match msg {
-                        Ok(Some(ServerWire::Snapshot { data, .. })) => {
-                            let _ = push_tx.send(
-                                serde_json::to_value(&data).unwrap_or(serde_json::Value::Null),
-                            );
-                        }
-                        Ok(Some(_)) => {}
-                        Ok(None) => break,
-                        Err(e) => { eprintln!("maillist room error: {e}"); break; }
-                    }
-                }
-                intent = intent_rx.recv() => {
-                    match intent {
-                        Some(payload) => {
-                            if let Ok(intent) = serde_json::from_value(payload) {
-                                let _ = conn.send_intent(intent).await;
-                            }
-                        }
-                        None => break,
-                    }
-                }
-            }
-        }
-    });
-
-    Ok(RoomHandle { tx: intent_tx })
+    Ok(run_room(conn, snapshot, push_tx, "maillist"))
 }
 
 async fn spawn_signal(
@@ -613,47 +404,20 @@ async fn spawn_signal(
     }
     let opts: Opts = parse_opts(config)?;
 
-    let (mut conn, snapshot) =
-        interconnect_connector_signal::connect(opts.signal_cli_path, opts.account, opts.recipient)
-            .await
-            .map_err(|e| RoomError::Connect(e.to_string()))?;
-
-    let _ = push_tx.send(
-        serde_json::to_value(&snapshot).unwrap_or(serde_json::Value::Null),
-    );
-
-    let (intent_tx, mut intent_rx) = mpsc::unbounded_channel::<serde_json::Value>();
-
-    tokio::spawn(async move {
-        loop {
-            tokio::select! {
-                msg = conn.recv() => {
-                    match msg {
-                        Ok(Some(ServerWire::Snapshot { data, .. })) => {
-                            let _ = push_tx.send(
-                                serde_json::to_value(&data).unwrap_or(serde_json::Value::Null),
-                            );
-                        }
-                        Ok(Some(_)) => {}
-                        Ok(None) => break,
-                        Err(e) => { eprintln!("signal room error: {e}"); break; }
-                    }
-                }
-                intent = intent_rx.recv() => {
-                    match intent {
-                        Some(payload) => {
-                            if let Ok(intent) = serde_json::from_value(payload) {
-                                let _ = conn.send_intent(intent).await;
-                            }
-                        }
-                        None => break,
-                    }
-                }
-            }
-        }
-    });
+    let (conn, snapshot) = ReconnectingConnection::connect(
+        move || {
+            interconnect_connector_signal::connect(
+                opts.signal_cli_path.clone(),
+                opts.account.clone(),
+                opts.recipient.clone(),
+            )
+        },
+        Backoff::default(),
+    )
+    .await
+    .map_err(|e| RoomError::Connect(e.to_string()))?;
 
-    Ok(RoomHandle { tx: intent_tx })
+    Ok(run_room(conn, snapshot, push_tx, "signal"))
 }
 
 async fn spawn_github(
@@ -669,51 +433,21 @@ async fn spawn_github(
     }
     let opts: Opts = parse_opts(config)?;
 
-    let (mut conn, snapshot) = interconnect_connector_github::connect(
-        opts.token,
-        opts.owner,
-        opts.repo,
-        opts.issue_number,
+    let (conn, snapshot) = ReconnectingConnection::connect(
+        move || {
+            interconnect_connector_github::connect(
+                opts.token.clone(),
+                opts.owner.clone(),
+                opts.repo.clone(),
+                opts.issue_number,
+            )
+        },
+        Backoff::default(),
     )
     .await
     .map_err(|e| RoomError::Connect(e.to_string()))?;
 
-    let _ = push_tx.send(
-        serde_json::to_value(&snapshot).unwrap_or(serde_json::Value::Null),
-    );
-
-    let (intent_tx, mut intent_rx) = mpsc::unbounded_channel::<serde_json::Value>();
-
-    tokio::spawn(async move {
-        loop {
-            tokio::select! {
-                msg = conn.recv() => {
-                    match msg {
-                        Ok(Some(ServerWire::Snapshot { data, .. })) => {
-                            let _ = push_tx.send(
-                                serde_json::to_value(&data).unwrap_or(serde_json::Value::Null),
-                            );
-                        }
-                        Ok(Some(_)) => {}
-                        Ok(None) => break,
-                        Err(e) => { eprintln!("github room error: {e}"); break; }
-                    }
-                }
-                intent = intent_rx.recv() => {
-                    match intent {
-                        Some(payload) => {
-                            if let Ok(intent) = serde_json::from_value(payload) {
-                                let _ = conn.send_intent(intent).await;
-                            }
-                        }
-                        None => break,
-                    }
-                }
-            }
-        }
-    });
-
-    Ok(RoomHandle { tx: intent_tx })
+    Ok(run_room(conn, snapshot, push_tx, "github"))
 }
 
 async fn spawn_whatsapp(
@@ -728,50 +462,20 @@ async fn spawn_whatsapp(
     }
     let opts: Opts = parse_opts(config)?;
 
-    let (mut conn, snapshot) = interconnect_connector_whatsapp::connect(
-        opts.phone_number_id,
-        opts.access_token,
-        opts.recipient_phone,
+    let (conn, snapshot) = ReconnectingConnection::connect(
+        move || {
+            interconnect_connector_whatsapp::connect(
+                opts.phone_number_id.clone(),
+                opts.access_token.clone(),
+                opts.recipient_phone.clone(),
+            )
+        },
+        Backoff::default(),
     )
     .await
     .map_err(|e| RoomError::Connect(e.to_string()))?;
 
-    let _ = push_tx.send(
-        serde_json::to_value(&snapshot).unwrap_or(serde_json::Value::Null),
-    );
-
-    let (intent_tx, mut intent_rx) = mpsc::unbounded_channel::<serde_json::Value>();
-
-    tokio::spawn(async move {
-        loop {
-            tokio::select! {
-                msg = conn.recv() => {
-                    match msg {
-                        Ok(Some(ServerWire::Snapshot { data, .. })) => {
-                            let _ = push_tx.send(
-                                serde_json::to_value(&data).unwrap_or(serde_json::Value::Null),
-                            );
-                        }
-                        Ok(Some(_)) => {}
-                        Ok(None) => break,
-                        Err(e) => { eprintln!("whatsapp room error: {e}"); break; }
-                    }
-                }
-                intent = intent_rx.recv() => {
-                    match intent {
-                        Some(payload) => {
-                            if let Ok(intent) = serde_json::from_value(payload) {
-                                let _ = conn.send_intent(intent).await;
-                            }
-                        }
-                        None => break,
-                    }
-                }
-            }
-        }
-    });
-
-    Ok(RoomHandle { tx: intent_tx })
+    Ok(run_room(conn, snapshot, push_tx, "whatsapp"))
 }
 
 async fn spawn_fs(
@@ -784,44 +488,12 @@ async fn spawn_fs(
     }
     let opts: Opts = parse_opts(config)?;
 
-    let (mut conn, snapshot) = interconnect_connector_fs::connect(opts.root)
-        .await
-        .map_err(|e| RoomError::Connect(e.to_string()))?;
-
-    let _ = push_tx.send(
-        serde_json::to_value(&snapshot).unwrap_or(serde_json::Value::Null),
-    );
-
-    let (intent_tx, mut intent_rx) = mpsc::unbounded_channel::<serde_json::Value>();
-
-    tokio::spawn(async move {
-        loop {
-            tokio::select! {
-                msg = conn.recv() => {
-                    match msg {
-                        Ok(Some(ServerWire::Snapshot { data, .. })) => {
-                            let _ = push_tx.send(
-                                serde_json::to_value(&data).unwrap_or(serde_json::Value::Null),
-                            );
-                        }
-                        Ok(Some(_)) => {}
-                        Ok(None) => break,
-                        Err(e) => { eprintln!("fs room error: {e}"); break; }
-                    }
-                }
-                intent = intent_rx.recv() => {
-                    match intent {
-                        Some(payload) => {
-                            if let Ok(intent) = serde_json::from_value(payload) {
-                                let _ = conn.send_intent(intent).await;
-                            }
-                        }
-                        None => break,
-                    }
-                }
-            }
-        }
-    });
+    let (conn, snapshot) = ReconnectingConnection::connect(
+        move || interconnect_connector_fs::connect(opts.root.clone()),
+        Backoff::default(),
+    )
+    .await
+    .map_err(|e| RoomError::Connect(e.to_string()))?;
 
-    Ok(RoomHandle { tx: intent_tx })
+    Ok(run_room(conn, snapshot, push_tx, "fs"))
 }
@@ -0,0 +1,46 @@
+//! Server error types.
+
+/// Errors from serving a session.
+#[derive(Debug, thiserror::Error)]
+pub enum ServerError {
+    #[error("io error: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("transport error: {0}")]
+    Transport(Box<dyn std::error::Error + Send + Sync + 'static>),
+
+    #[error("codec error: {0}")]
+    Codec(#[from] interconnect_core::CodecError),
+
+    #[error("json error: {0}")]
+    Json(#[from] serde_json::Error),
+
+    /// The client could not prove its identity.
+    #[error("auth error: {0}")]
+    Auth(#[from] interconnect_core::AuthError),
+
+    /// A passport could not be issued.
+    #[error("key error: {0}")]
+    Key(#[from] interconnect_core::KeyError),
+
+    /// The client broke the handshake (e.g. did not start with `Auth`).
+    #[error("handshake error: {0}")]
+    Handshake(String),
+
+    /// The authority refused the session.
+    #[error("authority error: {0}")]
+    Authority(Box<dyn std::error::Error + Send + Sync + 'static>),
+
+    /// The client sent nothing within `ServerOptions::idle_timeout`.
+    #[error("client silent for {0:?}")]
+    Timeout(std::time::Duration),
+
+    #[error("connection closed")]
+    Closed,
+}
+
+impl ServerError {
+    pub(crate) fn transport(e: impl std::error::Error + Send + Sync + 'static) -> Self {
+        ServerError::Transport(Box::new(e))
+    }
+}
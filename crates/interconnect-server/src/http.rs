@@ -0,0 +1,516 @@
+//! HTTP endpoint for clients whose proxies break WebSockets.
+//!
+//! Client frames go up by POST; server frames come down by Server-Sent
+//! Events or long-poll. Every frame is base64, whatever the codec, and
+//! numbered from 0 within its session. A client that loses its stream
+//! resumes it from a cursor (the number of the next frame it wants), so
+//! nothing is lost across reconnects. Routes, relative to where
+//! [`http_router`] is mounted:
+//!
+//! | Route | |
+//! |---|---|
+//! | `POST /sessions` | open a session: `{"session": id}` |
+//! | `POST /sessions/{id}/send?cursor=N` | one client frame as the body |
+//! | `POST /sessions/{id}/ack?cursor=N` | acknowledge frames without sending |
+//! | `GET /sessions/{id}/events?cursor=N` | SSE; event id is the frame number, `close` ends the session |
+//! | `GET /sessions/{id}/poll?cursor=N` | `{"cursor", "frames", "closed"}`, waiting up to [`POLL_TIMEOUT`] |
+//! | `DELETE /sessions/{id}` | close |
+//!
+//! Presenting a cursor acknowledges every frame below it; those frames are
+//! discarded and asking for them again is `410 Gone`. SSE clients may send
+//! `Last-Event-ID` instead of a cursor. An SSE stream does not acknowledge
+//! what it delivers, so a client that only listens must `ack` as it goes:
+//! a session holding more than 4096 unacknowledged frames is closed.
+
+use crate::Server;
+use axum::Router;
+use axum::extract::{Path, Query, State};
+use axum::http::{HeaderMap, StatusCode};
+use axum::response::sse::{Event, KeepAlive, Sse};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Json, body::Bytes};
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use futures_util::{Stream, StreamExt, stream};
+use interconnect_core::{AsyncAuthority, Transport, Wire};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::convert::Infallible;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::{Notify, mpsc};
+
+/// How long a long-poll (or one SSE wait) holds for frames before
+/// answering with none.
+pub const POLL_TIMEOUT: Duration = Duration::from_secs(25);
+
+/// A session with no open stream and no requests for this long is closed.
+pub const SESSION_IDLE_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Frames held for a client before its session is closed as stuck.
+const MAX_BUFFERED: usize = 4096;
+
+/// Routes serving `server` over HTTP. Nest them under any path of an axum
+/// app; see the [module docs](self) for the routes.
+pub fn http_router<A>(server: Server<A>) -> Router
+where
+    A: AsyncAuthority + 'static,
+    A::Intent: Wire,
+    A::Snapshot: Wire,
+    A::Passport: Wire,
+{
+    Router::new()
+        .route("/sessions", post(open::<A>))
+        .route("/sessions/{id}", axum::routing::delete(close::<A>))
+        .route("/sessions/{id}/send", post(send::<A>))
+        .route("/sessions/{id}/ack", post(ack::<A>))
+        .route("/sessions/{id}/poll", get(poll::<A>))
+        .route("/sessions/{id}/events", get(events::<A>))
+        .with_state(Endpoint {
+            server,
+            sessions: Default::default(),
+        })
+}
+
+struct Endpoint<A> {
+    server: Server<A>,
+    sessions: Arc<Mutex<HashMap<String, Arc<HttpSession>>>>,
+}
+
+impl<A> Clone for Endpoint<A> {
+    fn clone(&self) -> Self {
+        Self {
+            server: self.server.clone(),
+            sessions: self.sessions.clone(),
+        }
+    }
+}
+
+impl<A> Endpoint<A> {
+    fn session(&self, id: &str) -> Result<Arc<HttpSession>, StatusCode> {
+        let session = self.sessions.lock().unwrap().get(id).cloned();
+        let session = session.ok_or(StatusCode::NOT_FOUND)?;
+        *session.last_seen.lock().unwrap() = Instant::now();
+        Ok(session)
+    }
+}
+
+/// One client's side of the bridge: frames from the client, frames for it.
+struct HttpSession {
+    /// Taken on `DELETE`, which ends the session.
+    inbox: Mutex<Option<mpsc::UnboundedSender<Vec<u8>>>>,
+    outbox: Mutex<Outbox>,
+    ready: Notify,
+    last_seen: Mutex<Instant>,
+    /// Open SSE streams and long-polls. A session being read is not idle.
+    readers: AtomicUsize,
+}
+
+#[derive(Default)]
+struct Outbox {
+    /// Number of `frames[0]`.
+    first: u64,
+    frames: VecDeque<Vec<u8>>,
+    closed: bool,
+}
+
+/// A long-poll answer.
+#[derive(Serialize)]
+struct Batch {
+    /// Cursor to ask with next.
+    cursor: u64,
+    frames: Vec<String>,
+    /// The session is over; no frames follow these.
+    closed: bool,
+}
+
+#[derive(Deserialize)]
+struct Cursor {
+    cursor: Option<u64>,
+}
+
+impl HttpSession {
+    /// Frames from `cursor` on, waiting up to `wait` for at least one.
+    /// Long-polls `ack` the cursor; an SSE stream does not, since frames it
+    /// has written may not have reached the client.
+    async fn take(&self, cursor: u64, wait: Duration, ack: bool) -> Result<Batch, StatusCode> {
+        let deadline = tokio::time::Instant::now() + wait;
+        loop {
+            let ready = self.ready.notified();
+            tokio::pin!(ready);
+            ready.as_mut().enable();
+            {
+                let mut outbox = self.outbox.lock().unwrap();
+                if ack {
+                    outbox.ack(cursor)?;
+                }
+                let frames: Vec<_> = outbox.since(cursor)?.map(|f| BASE64.encode(f)).collect();
+                if !frames.is_empty() || outbox.closed {
+                    return Ok(Batch {
+                        cursor: cursor + frames.len() as u64,
+                        frames,
+                        closed: outbox.closed,
+                    });
+                }
+            }
+            if tokio::time::timeout_at(deadline, ready).await.is_err() {
+                return Ok(Batch {
+                    cursor,
+                    frames: Vec::new(),
+                    closed: false,
+                });
+            }
+        }
+    }
+
+    fn idle(&self) -> bool {
+        self.readers.load(Ordering::Relaxed) == 0
+            && self.last_seen.lock().unwrap().elapsed() > SESSION_IDLE_TIMEOUT
+    }
+
+    fn close(&self) {
+        self.outbox.lock().unwrap().closed = true;
+        self.ready.notify_waiters();
+    }
+}
+
+impl Outbox {
+    /// Frames numbered `cursor` and up.
+    fn since(&self, cursor: u64) -> Result<impl Iterator<Item = &Vec<u8>>, StatusCode> {
+        let skip = cursor.checked_sub(self.first).ok_or(StatusCode::GONE)?;
+        if skip > self.frames.len() as u64 {
+            return Err(StatusCode::BAD_REQUEST);
+        }
+        Ok(self.frames.iter().skip(skip as usize))
+    }
+
+    /// Discard frames below `cursor`, which the client has received.
+    fn ack(&mut self, cursor: u64) -> Result<(), StatusCode> {
+        let _ = self.since(cursor)?;
+        while self.first < cursor {
+            self.frames.pop_front();
+            self.first += 1;
+        }
+        Ok(())
+    }
+}
+
+/// Counts an open stream or poll against its session while alive.
+struct Reader(Arc<HttpSession>);
+
+impl Reader {
+    fn new(session: Arc<HttpSession>) -> Self {
+        session.readers.fetch_add(1, Ordering::Relaxed);
+        Self(session)
+    }
+}
+
+impl Drop for Reader {
+    fn drop(&mut self) {
+        *self.0.last_seen.lock().unwrap() = Instant::now();
+        self.0.readers.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// The server's end of an HTTP session, handed to [`Server::serve`].
+struct HttpTransport {
+    session: Arc<HttpSession>,
+    inbox: mpsc::UnboundedReceiver<Vec<u8>>,
+}
+
+impl Transport for HttpTransport {
+    type Error = std::io::Error;
+
+    async fn send(&mut self, data: &[u8]) -> std::io::Result<()> {
+        {
+            let mut outbox = self.session.outbox.lock().unwrap();
+            if outbox.frames.len() >= MAX_BUFFERED {
+                return Err(std::io::Error::other("client stopped reading"));
+            }
+            outbox.frames.push_back(data.to_vec());
+        }
+        self.session.ready.notify_waiters();
+        Ok(())
+    }
+
+    async fn recv(&mut self) -> std::io::Result<Option<Vec<u8>>> {
+        loop {
+            tokio::select! {
+                frame = self.inbox.recv() => return Ok(frame),
+                _ = tokio::time::sleep(SESSION_IDLE_TIMEOUT / 4) => {
+                    if self.session.idle() {
+                        return Ok(None);
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl Drop for HttpTransport {
+    fn drop(&mut self) {
+        self.session.close();
+    }
+}
+
+#[derive(Serialize)]
+struct Opened {
+    session: String,
+}
+
+async fn open<A>(State(endpoint): State<Endpoint<A>>) -> Response
+where
+    A: AsyncAuthority + 'static,
+    A::Intent: Wire,
+    A::Snapshot: Wire,
+    A::Passport: Wire,
+{
+    let mut raw = [0u8; 16];
+    if getrandom::fill(&mut raw).is_err() {
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    }
+    let id: String = raw.iter().map(|b| format!("{b:02x}")).collect();
+
+    let (tx, inbox) = mpsc::unbounded_channel();
+    let session = Arc::new(HttpSession {
+        inbox: Mutex::new(Some(tx)),
+        outbox: Mutex::default(),
+        ready: Notify::new(),
+        last_seen: Mutex::new(Instant::now()),
+        readers: AtomicUsize::new(0),
+    });
+    endpoint
+        .sessions
+        .lock()
+        .unwrap()
+        .insert(id.clone(), session.clone());
+
+    let transport = HttpTransport { session, inbox };
+    let session_id = id.clone();
+    tokio::spawn(async move {
+        if let Err(e) = endpoint.server.serve(transport).await {
+            tracing::debug!("http session {session_id}: {e}");
+        }
+        endpoint.sessions.lock().unwrap().remove(&session_id);
+    });
+    (StatusCode::CREATED, Json(Opened { session: id })).into_response()
+}
+
+async fn close<A>(
+    State(endpoint): State<Endpoint<A>>,
+    Path(id): Path<String>,
+) -> Result<StatusCode, StatusCode> {
+    endpoint.session(&id)?.inbox.lock().unwrap().take();
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn send<A>(
+    State(endpoint): State<Endpoint<A>>,
+    Path(id): Path<String>,
+    Query(Cursor { cursor }): Query<Cursor>,
+    body: Bytes,
+) -> Result<StatusCode, StatusCode> {
+    let session = endpoint.session(&id)?;
+    if let Some(cursor) = cursor {
+        session.outbox.lock().unwrap().ack(cursor)?;
+    }
+    let inbox = session.inbox.lock().unwrap();
+    let inbox = inbox.as_ref().ok_or(StatusCode::NOT_FOUND)?;
+    inbox
+        .send(body.to_vec())
+        .map_err(|_| StatusCode::NOT_FOUND)?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn ack<A>(
+    State(endpoint): State<Endpoint<A>>,
+    Path(id): Path<String>,
+    Query(Cursor { cursor }): Query<Cursor>,
+) -> Result<StatusCode, StatusCode> {
+    let cursor = cursor.ok_or(StatusCode::BAD_REQUEST)?;
+    endpoint.session(&id)?.outbox.lock().unwrap().ack(cursor)?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn poll<A>(
+    State(endpoint): State<Endpoint<A>>,
+    Path(id): Path<String>,
+    Query(Cursor { cursor }): Query<Cursor>,
+) -> Result<Json<Batch>, StatusCode> {
+    let reader = Reader::new(endpoint.session(&id)?);
+    let batch = reader
+        .0
+        .take(cursor.unwrap_or(0), POLL_TIMEOUT, true)
+        .await?;
+    Ok(Json(batch))
+}
+
+async fn events<A>(
+    State(endpoint): State<Endpoint<A>>,
+    Path(id): Path<String>,
+    Query(Cursor { cursor }): Query<Cursor>,
+    headers: HeaderMap,
+) -> Result<Sse<impl Stream<Item = Result<Event, Infallible>>>, StatusCode> {
+    // `Last-Event-ID` is the number of the last frame the client received.
+    let last_event = headers
+        .get("last-event-id")
+        .and_then(|v| v.to_str().ok()?.parse::<u64>().ok());
+    let last_event = last_event
+        .map(|n| n.checked_add(1).ok_or(StatusCode::BAD_REQUEST))
+        .transpose()?;
+    let cursor = last_event.or(cursor).unwrap_or(0);
+    let reader = Reader::new(endpoint.session(&id)?);
+    reader.0.outbox.lock().unwrap().ack(cursor)?;
+
+    let frames = stream::unfold(Some((reader, cursor)), |state| async move {
+        let (reader, cursor) = state?;
+        let batch = reader.0.take(cursor, POLL_TIMEOUT, false).await.ok()?;
+        let mut events: Vec<_> = batch
+            .frames
+            .into_iter()
+            .zip(cursor..)
+            .map(|(frame, n)| Ok(Event::default().id(n.to_string()).data(frame)))
+            .collect();
+        let next = if batch.closed {
+            events.push(Ok(Event::default().event("close").data("")));
+            None
+        } else {
+            Some((reader, batch.cursor))
+        };
+        Some((stream::iter(events), next))
+    });
+    Ok(Sse::new(frames.flatten()).keep_alive(KeepAlive::default()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use interconnect_client::{Connection, HttpMode, HttpTransport};
+    use interconnect_core::{
+        Identity, ImportResult, IntentOutcome, Manifest, Session, SimpleAuthority,
+    };
+
+    #[derive(serde::Serialize, serde::Deserialize)]
+    struct Add {
+        add: u64,
+    }
+
+    #[derive(Default)]
+    struct Counter {
+        count: u64,
+    }
+
+    impl SimpleAuthority for Counter {
+        type Intent = Add;
+        type Snapshot = u64;
+        type Passport = ();
+        type Error = Infallible;
+
+        fn on_connect(&mut self, _session: &Session) -> Result<(), Infallible> {
+            Ok(())
+        }
+
+        fn on_transfer_in(
+            &mut self,
+            _session: &Session,
+            passport: (),
+        ) -> Result<ImportResult<()>, Infallible> {
+            Ok(ImportResult::accept(passport))
+        }
+
+        fn on_disconnect(&mut self, _session: &Session) {}
+
+        fn handle_intent(
+            &mut self,
+            _session: &Session,
+            Add { add }: Add,
+        ) -> Result<(), Infallible> {
+            self.count += add;
+            Ok(())
+        }
+
+        fn snapshot(&self) -> u64 {
+            self.count
+        }
+
+        fn emit_passport(&self, _session: &Session) {}
+
+        fn validate_destination(&self, _destination: &str) -> bool {
+            true
+        }
+    }
+
+    async fn serve() -> (Server<Counter>, String) {
+        let manifest = Manifest::new(Identity::local("counter"), "counter");
+        let server = Server::new(Counter::default(), manifest, Default::default());
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let app = Router::new().nest("/room", http_router(server.clone()));
+        tokio::spawn(async move { axum::serve(listener, app).await });
+        (server, format!("http://{addr}/room"))
+    }
+
+    #[test]
+    fn cursors_acknowledge_frames() {
+        let mut outbox = Outbox::default();
+        outbox.frames.extend([vec![0], vec![1], vec![2]]);
+        assert_eq!(outbox.since(1).unwrap().count(), 2);
+        assert_eq!(outbox.since(4).err(), Some(StatusCode::BAD_REQUEST));
+
+        outbox.ack(2).unwrap();
+        assert_eq!(outbox.since(2).unwrap().collect::<Vec<_>>(), [&vec![2]]);
+        // Acknowledged frames cannot be asked for again.
+        assert_eq!(outbox.since(1).err(), Some(StatusCode::GONE));
+    }
+
+    #[tokio::test]
+    async fn rooms_work_over_sse_and_long_poll() {
+        let (_, url) = serve().await;
+        for mode in [HttpMode::Sse, HttpMode::LongPoll] {
+            let transport = HttpTransport::connect_with(&url, mode).await.unwrap();
+            let (mut conn, start) =
+                Connection::<_, Add, u64>::connect(transport, Identity::local("alice"), None, None)
+                    .await
+                    .unwrap();
+            assert_eq!(
+                conn.request(Add { add: 2 }).await.unwrap(),
+                IntentOutcome::Accepted
+            );
+            loop {
+                if let Some(interconnect_core::ServerWire::Snapshot { data, .. }) =
+                    conn.recv().await.unwrap()
+                {
+                    assert_eq!(data, start + 2);
+                    break;
+                }
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn listening_clients_acknowledge_frames() {
+        let (server, url) = serve().await;
+        let transport = HttpTransport::connect(&url).await.unwrap();
+        let (mut conn, _) =
+            Connection::<_, Add, u64>::connect(transport, Identity::local("alice"), None, None)
+                .await
+                .unwrap();
+
+        // More frames than a session holds unacknowledged, and nothing sent.
+        for batch in 0..50 {
+            for i in 0..100 {
+                server.system(format!("{batch}.{i}"));
+            }
+            let mut heard = 0;
+            while heard < 100 {
+                match conn.recv().await.unwrap() {
+                    Some(interconnect_core::ServerWire::System { .. }) => heard += 1,
+                    Some(_) => {}
+                    None => panic!("session closed"),
+                }
+            }
+        }
+    }
+}
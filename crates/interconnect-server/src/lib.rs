@@ -0,0 +1,73 @@
+//! Authority runtime for the Interconnect protocol.
+//!
+//! Runs the connection lifecycle for any
+//! [`AsyncAuthority`](interconnect_core::AsyncAuthority), which includes every
+//! sync [`Authority`](interconnect_core::Authority):
+//! auth and identity challenges, passport verification and `on_transfer_in`,
+//! manifest and snapshot delivery (with deltas), per-session snapshot fan-out,
+//! transfer requests, substrate fetches, Ping/Pong and disconnect cleanup.
+//! The application only implements the authority.
+//!
+//! # Quick Start
+//!
+//! ```ignore
+//! use interconnect_server::{Server, ServerOptions, WsListener};
+//!
+//! let options = ServerOptions::default().signer(keypair.clone());
+//! let server = Server::new(MyRoom::new(), manifest, options);
+//!
+//! // State that changes on its own (ticks, subprocess output) is pushed with
+//! // `update` or `notify`.
+//! let ticker = server.clone();
+//! tokio::spawn(async move {
+//!     loop {
+//!         ticker.update(|room| room.tick()).await;
+//!     }
+//! });
+//!
+//! server.run(WsListener::bind("127.0.0.1:8080").await?).await?;
+//! ```
+//!
+//! Besides [`WsListener`], tokio's `TcpListener` and `UnixListener` are
+//! [`Listener`]s of length-prefixed
+//! [`FramedTransport`](interconnect_core::FramedTransport)s, for local agents
+//! and sidecars. Transports that are not accepted from a listener (an axum
+//! WebSocket, a test pipe, the stdio a sidecar room was spawned with) are
+//! served one at a time with [`Server::serve`]. [`Listener::layer`] wraps
+//! every accepted transport in middleware (compression, limits, metrics).
+//!
+//! For clients behind proxies that break WebSockets, [`http_router`] serves
+//! the same rooms over plain HTTP (POST up; Server-Sent Events or long-poll
+//! down) from any axum app:
+//!
+//! ```ignore
+//! let app = axum::Router::new().nest("/room", interconnect_server::http_router(server));
+//! ```
+//!
+//! # Real-Time Rooms
+//!
+//! A room that runs on a clock hands its step function to
+//! [`Server::simulate`]. Intents are then buffered and applied in arrival
+//! order at the start of each tick, snapshots go out at their own rate, and
+//! ticks that overrun their period are logged, counted and reported:
+//!
+//! ```ignore
+//! let ticks = TickOptions::per_second(60)
+//!     .snapshot_every(3) // 20 snapshots a second
+//!     .on_overrun(|overrun| metrics.record(overrun.took));
+//! let stats = server.simulate(ticks, |world, tick| world.step(tick.dt));
+//! ```
+
+mod error;
+pub mod http;
+mod listener;
+mod server;
+mod tick;
+mod ws;
+
+pub use error::ServerError;
+pub use http::http_router;
+pub use listener::{Layered, Listener};
+pub use server::{DEFAULT_PASSPORT_TTL, Server, ServerOptions};
+pub use tick::{Overrun, Tick, TickOptions, TickStats};
+pub use ws::{WsListener, WsTransport};
@@ -0,0 +1,147 @@
+//! Sources of incoming transports.
+
+use interconnect_core::{TcpTransport, Transport};
+
+/// Accepts incoming client connections.
+///
+/// Implement this to serve authorities over a new kind of socket. Accepting
+/// should be quick: expensive per-connection setup (such as a WebSocket
+/// upgrade) belongs in the transport, where it runs on the session's task.
+pub trait Listener: Send {
+    /// The transport for one accepted connection.
+    type Transport: Transport + 'static;
+
+    /// Wait for the next connection.
+    fn accept(
+        &mut self,
+    ) -> impl std::future::Future<Output = std::io::Result<Self::Transport>> + Send;
+
+    /// Wrap every accepted transport with `layer`, for example
+    /// `|t| t.compressed(Compression::Zstd)` (see
+    /// [`TransportExt`](interconnect_core::TransportExt)).
+    fn layer<F, T>(self, layer: F) -> Layered<Self, F>
+    where
+        Self: Sized,
+        F: FnMut(Self::Transport) -> T + Send,
+        T: Transport + 'static,
+    {
+        Layered { inner: self, layer }
+    }
+}
+
+/// A listener whose transports are wrapped in layers. See
+/// [`Listener::layer`].
+pub struct Layered<L, F> {
+    inner: L,
+    layer: F,
+}
+
+impl<L, F, T> Listener for Layered<L, F>
+where
+    L: Listener,
+    F: FnMut(L::Transport) -> T + Send,
+    T: Transport + 'static,
+{
+    type Transport = T;
+
+    async fn accept(&mut self) -> std::io::Result<T> {
+        let transport = self.inner.accept().await?;
+        Ok((self.layer)(transport))
+    }
+}
+
+/// Length-prefixed TCP (see `FramedTransport`), for local agents and
+/// sidecars that have no WebSocket client.
+impl Listener for tokio::net::TcpListener {
+    type Transport = TcpTransport;
+
+    async fn accept(&mut self) -> std::io::Result<TcpTransport> {
+        let (stream, _) = tokio::net::TcpListener::accept(self).await?;
+        Ok(TcpTransport::tcp(stream))
+    }
+}
+
+/// Length-prefixed Unix sockets (see `FramedTransport`).
+#[cfg(unix)]
+impl Listener for tokio::net::UnixListener {
+    type Transport = interconnect_core::UnixTransport;
+
+    async fn accept(&mut self) -> std::io::Result<interconnect_core::UnixTransport> {
+        let (stream, _) = tokio::net::UnixListener::accept(self).await?;
+        Ok(interconnect_core::UnixTransport::unix(stream))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Server;
+    use interconnect_client::Connection;
+    use interconnect_core::{
+        Compression, Identity, ImportResult, Manifest, Metrics, Session, SimpleAuthority,
+        TransportExt,
+    };
+    use std::convert::Infallible;
+
+    /// A room whose snapshot is a fixed, compressible string.
+    struct Banner;
+
+    impl SimpleAuthority for Banner {
+        type Intent = ();
+        type Snapshot = String;
+        type Passport = ();
+        type Error = Infallible;
+
+        fn on_connect(&mut self, _session: &Session) -> Result<(), Infallible> {
+            Ok(())
+        }
+
+        fn on_transfer_in(
+            &mut self,
+            _session: &Session,
+            passport: (),
+        ) -> Result<ImportResult<()>, Infallible> {
+            Ok(ImportResult::accept(passport))
+        }
+
+        fn on_disconnect(&mut self, _session: &Session) {}
+
+        fn handle_intent(&mut self, _session: &Session, _intent: ()) -> Result<(), Infallible> {
+            Ok(())
+        }
+
+        fn snapshot(&self) -> String {
+            "welcome ".repeat(500)
+        }
+
+        fn emit_passport(&self, _session: &Session) {}
+
+        fn validate_destination(&self, _destination: &str) -> bool {
+            false
+        }
+    }
+
+    #[tokio::test]
+    async fn layered_listeners_wrap_each_transport() {
+        let manifest = Manifest::new(Identity::local("banner"), "banner");
+        let server = Server::new(Banner, manifest, Default::default());
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let metrics = Metrics::default();
+        let wire = metrics.clone();
+        let listener =
+            listener.layer(move |t| t.metered(wire.clone()).compressed(Compression::Zstd));
+        tokio::spawn(async move { server.run(listener).await });
+
+        let transport = TcpTransport::connect(addr)
+            .await
+            .unwrap()
+            .compressed(Compression::Zstd);
+        let (_conn, snapshot) =
+            Connection::<_, (), String>::connect(transport, Identity::local("alice"), None, None)
+                .await
+                .unwrap();
+        assert_eq!(snapshot.len(), 4000);
+        assert!(metrics.bytes_sent() < 1000);
+    }
+}
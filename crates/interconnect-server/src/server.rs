@@ -0,0 +1,1655 @@
+//! The session lifecycle.
+
+use crate::{Listener, Overrun, ServerError, Tick, TickOptions, TickStats};
+use futures_util::future::BoxFuture;
+use interconnect_core::{
+    AsyncAuthority, AuthError, AuthPolicy, Capabilities, ClientWire, Codec, DeltaEncoder, Identity,
+    IntentOutcome, Manifest, Passport, PassportVerifier, Protocol, Resume, ServerWire, Session,
+    Signer, Substrate, Transport, Wire, feature,
+};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::sync::Mutex as SyncMutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+use tokio::sync::mpsc::error::TryRecvError;
+use tokio::sync::{Mutex, RwLock, broadcast, mpsc, oneshot};
+use tokio::time::{Instant, MissedTickBehavior};
+
+/// Length of resume tokens, in bytes.
+const RESUME_TOKEN_LEN: usize = 32;
+
+/// How long a client has to answer an identity challenge, and a resume
+/// waits for the session it takes over, when there is no
+/// [`ServerOptions::idle_timeout`].
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// How long passports issued on transfer stay valid by default.
+pub const DEFAULT_PASSPORT_TTL: Duration = Duration::from_secs(60);
+
+/// Options for [`Server::new`].
+#[derive(Clone)]
+pub struct ServerOptions {
+    /// Which claimed identities must be proven with a challenge.
+    pub auth_policy: AuthPolicy,
+    /// Signs passports for outgoing transfers. Without one, transfer
+    /// requests are refused. If its identity is the manifest's, it also
+    /// signs the manifest sent to each client.
+    pub signer: Option<Arc<dyn Signer>>,
+    /// Checks passports presented on transfer in. Without one, every
+    /// passport is refused and the client joins as a new session.
+    pub passports: Option<PassportVerifier>,
+    /// Lifetime of issued passports.
+    pub passport_ttl: Duration,
+    /// The room's substrate, advertised in the manifest and served on
+    /// `SubstrateRequest`.
+    pub substrate: Option<Substrate>,
+    /// Broadcast "<name> joined" / "<name> left" system messages.
+    pub announce_presence: bool,
+    /// How long a dropped session waits to be resumed before
+    /// `on_disconnect`. Zero, the default, turns resumption off.
+    pub resume_grace: Duration,
+    /// How long a client may send nothing before its session is evicted.
+    /// `None`, the default, waits forever; clients should then send
+    /// heartbeats more often than this.
+    pub idle_timeout: Option<Duration>,
+}
+
+impl Default for ServerOptions {
+    fn default() -> Self {
+        Self {
+            auth_policy: AuthPolicy::default(),
+            signer: None,
+            passports: None,
+            passport_ttl: DEFAULT_PASSPORT_TTL,
+            substrate: None,
+            announce_presence: false,
+            resume_grace: Duration::ZERO,
+            idle_timeout: None,
+        }
+    }
+}
+
+impl ServerOptions {
+    /// Use `policy` for identity challenges.
+    pub fn auth_policy(mut self, policy: AuthPolicy) -> Self {
+        self.auth_policy = policy;
+        self
+    }
+
+    /// Sign outgoing passports with `signer`.
+    pub fn signer(mut self, signer: impl Signer + 'static) -> Self {
+        self.signer = Some(Arc::new(signer));
+        self
+    }
+
+    /// Accept passports that pass `verifier`.
+    pub fn passports(mut self, verifier: PassportVerifier) -> Self {
+        self.passports = Some(verifier);
+        self
+    }
+
+    /// Issue passports valid for `ttl`.
+    pub fn passport_ttl(mut self, ttl: Duration) -> Self {
+        self.passport_ttl = ttl;
+        self
+    }
+
+    /// Serve `substrate`.
+    pub fn substrate(mut self, substrate: Substrate) -> Self {
+        self.substrate = Some(substrate);
+        self
+    }
+
+    /// Announce joins and leaves to everyone in the room.
+    pub fn announce_presence(mut self, announce: bool) -> Self {
+        self.announce_presence = announce;
+        self
+    }
+
+    /// Let clients that drop resume their session within `grace`.
+    pub fn resume_grace(mut self, grace: Duration) -> Self {
+        self.resume_grace = grace;
+        self
+    }
+
+    /// Evict sessions that send nothing for `timeout`.
+    pub fn idle_timeout(mut self, timeout: Duration) -> Self {
+        self.idle_timeout = Some(timeout);
+        self
+    }
+}
+
+/// Something every session should hear about.
+#[derive(Debug, Clone)]
+enum Event {
+    /// The authority's state changed; send a fresh snapshot.
+    Update,
+    /// A system message.
+    System(String),
+}
+
+struct Shared<A> {
+    authority: RwLock<A>,
+    manifest: Manifest,
+    options: ServerOptions,
+    verifier: Mutex<PassportVerifier>,
+    next_session_id: AtomicU64,
+    events: broadcast::Sender<Event>,
+    /// Resumable sessions, by resume token.
+    slots: Mutex<HashMap<Vec<u8>, Slot>>,
+    /// Where intents wait for the next tick, while simulating.
+    ticks: SyncMutex<Option<mpsc::UnboundedSender<Queued<A>>>>,
+}
+
+/// The outcome of a queued intent, with its request id if it had one.
+type Reply = (Option<u64>, IntentOutcome);
+
+/// An intent waiting for the next tick, as a job that applies it and
+/// replies to its session.
+type Queued<A> = Box<dyn for<'a> FnOnce(&'a mut A) -> BoxFuture<'a, ()> + Send>;
+
+/// Where a resumable session is.
+enum Slot {
+    /// On a transport. Sending on `kick` ends its session loop.
+    Live {
+        identity: Identity,
+        kick: oneshot::Sender<()>,
+    },
+    /// Taken over by a resume, which waits for the session's state.
+    Resuming(oneshot::Sender<Parked>),
+    /// Off its transport, until resumed or `until`.
+    Parked { parked: Parked, until: Instant },
+}
+
+/// A session without a transport.
+struct Parked {
+    session: Session,
+    sent: Sent,
+}
+
+/// What a session has been sent, to carry on from.
+#[derive(Default)]
+struct Sent {
+    deltas: DeltaEncoder,
+    /// The last snapshot's sequence number.
+    seq: Option<u64>,
+}
+
+/// A session through the handshake.
+struct Admitted {
+    session: Session,
+    protocol: Protocol,
+    /// The token the client may resume with.
+    token: Option<Vec<u8>>,
+    /// What was sent before, if this resumed a dropped session.
+    resumed: Option<Sent>,
+}
+
+/// A running authority.
+///
+/// Cheap to clone; every clone drives the same authority and sessions.
+pub struct Server<A> {
+    shared: Arc<Shared<A>>,
+}
+
+impl<A> Clone for Server<A> {
+    fn clone(&self) -> Self {
+        Self {
+            shared: self.shared.clone(),
+        }
+    }
+}
+
+impl<A> Server<A>
+where
+    A: AsyncAuthority + 'static,
+    A::Intent: Wire,
+    A::Snapshot: Wire,
+    A::Passport: Wire,
+{
+    /// Wrap `authority`. If `options` has a substrate, its hash replaces
+    /// `manifest.substrate`.
+    pub fn new(authority: A, mut manifest: Manifest, mut options: ServerOptions) -> Self {
+        if let Some(substrate) = &options.substrate {
+            manifest.substrate = Some(substrate.hash().to_string());
+        }
+        // A verifier that trusts nobody refuses every passport.
+        let verifier = options
+            .passports
+            .take()
+            .unwrap_or_else(|| PassportVerifier::new(String::new()));
+        let (events, _) = broadcast::channel(256);
+        Self {
+            shared: Arc::new(Shared {
+                authority: RwLock::new(authority),
+                manifest,
+                options,
+                verifier: Mutex::new(verifier),
+                next_session_id: AtomicU64::new(1),
+                events,
+                slots: Mutex::default(),
+                ticks: SyncMutex::default(),
+            }),
+        }
+    }
+
+    pub fn manifest(&self) -> &Manifest {
+        &self.shared.manifest
+    }
+
+    /// Read the authority's state.
+    pub async fn read<R>(&self, f: impl FnOnce(&A) -> R) -> R {
+        f(&*self.shared.authority.read().await)
+    }
+
+    /// Change the authority's state, then send every session a snapshot.
+    pub async fn update<R>(&self, f: impl FnOnce(&mut A) -> R) -> R {
+        let result = f(&mut *self.shared.authority.write().await);
+        self.notify();
+        result
+    }
+
+    /// Send every session a fresh snapshot, for state that changed outside
+    /// [`update`](Self::update).
+    pub fn notify(&self) {
+        let _ = self.shared.events.send(Event::Update);
+    }
+
+    /// Send every session a system message.
+    pub fn system(&self, message: impl Into<String>) {
+        let _ = self.shared.events.send(Event::System(message.into()));
+    }
+
+    /// Run the authority on a fixed timestep, calling `step` once a tick.
+    ///
+    /// From now on intents are buffered until the next tick, which applies
+    /// them in arrival order and then calls `step`, all under one write
+    /// lock. Sessions get a snapshot every [`TickOptions::snapshot_every`]
+    /// ticks instead of after every intent, and hear of rejected intents
+    /// when their tick runs. Replaces any running simulation.
+    ///
+    /// Must be called within a Tokio runtime.
+    pub fn simulate(
+        &self,
+        options: TickOptions,
+        step: impl FnMut(&mut A, Tick) + Send + 'static,
+    ) -> TickStats {
+        let (queue, intents) = mpsc::unbounded_channel();
+        *self.shared.ticks.lock().unwrap() = Some(queue);
+        let stats = TickStats::default();
+        tokio::spawn(self.clone().ticks(options, intents, step, stats.clone()));
+        stats
+    }
+
+    /// Stop the simulation after its next tick. Intents then apply as they
+    /// arrive again.
+    pub fn stop_simulation(&self) {
+        self.shared.ticks.lock().unwrap().take();
+    }
+
+    /// The simulation loop, until its queue is dropped.
+    async fn ticks(
+        self,
+        options: TickOptions,
+        mut intents: mpsc::UnboundedReceiver<Queued<A>>,
+        mut step: impl FnMut(&mut A, Tick) + Send,
+        stats: TickStats,
+    ) {
+        let mut interval = tokio::time::interval(options.period);
+        interval.set_missed_tick_behavior(MissedTickBehavior::Skip);
+        for number in 0.. {
+            interval.tick().await;
+            let started = Instant::now();
+            let mut authority = self.shared.authority.write().await;
+            let mut applied = 0;
+            let stopped = loop {
+                match intents.try_recv() {
+                    Ok(queued) => {
+                        queued(&mut authority).await;
+                        applied += 1;
+                    }
+                    Err(TryRecvError::Empty) => break false,
+                    Err(TryRecvError::Disconnected) => break true,
+                }
+            };
+            step(
+                &mut authority,
+                Tick {
+                    number,
+                    dt: options.period,
+                },
+            );
+            drop(authority);
+            stats.ticked(applied);
+            if stopped || (number + 1) % u64::from(options.snapshot_every) == 0 {
+                self.notify();
+            }
+
+            let took = started.elapsed();
+            if took > options.period {
+                stats.overran();
+                tracing::warn!(
+                    "tick {number} overran: took {took:?} of {:?}",
+                    options.period
+                );
+                if let Some(on_overrun) = &options.on_overrun {
+                    on_overrun(Overrun {
+                        tick: number,
+                        took,
+                        period: options.period,
+                    });
+                }
+            }
+            if stopped {
+                return;
+            }
+        }
+    }
+
+    /// Accept connections from `listener` and serve each on its own task.
+    ///
+    /// Only returns if accepting fails. Session errors are logged.
+    pub async fn run<L: Listener>(&self, mut listener: L) -> Result<(), ServerError> {
+        loop {
+            let transport = listener.accept().await?;
+            let server = self.clone();
+            tokio::spawn(async move {
+                if let Err(e) = server.serve(transport).await {
+                    tracing::debug!("session ended: {e}");
+                }
+            });
+        }
+    }
+
+    /// Serve one connection until it closes.
+    ///
+    /// With [`ServerOptions::resume_grace`], a session whose transport
+    /// closes is kept for that long, and `on_disconnect` only runs if the
+    /// client has not resumed it by then.
+    pub async fn serve<T: Transport>(&self, mut transport: T) -> Result<(), ServerError> {
+        let Admitted {
+            session,
+            protocol,
+            token,
+            resumed,
+        } = self.admit(&mut transport).await?;
+        let mut sent = match resumed {
+            Some(sent) => {
+                tracing::debug!("{} resumed", session.name);
+                sent
+            }
+            None => {
+                tracing::debug!(
+                    "{} connected (v{}, {})",
+                    session.name,
+                    protocol.version,
+                    protocol.codec
+                );
+                self.notify();
+                if self.shared.options.announce_presence {
+                    self.system(format!("{} joined", session.name));
+                }
+                Sent::default()
+            }
+        };
+
+        let mut kick = None;
+        if let Some(token) = &token {
+            let (tx, rx) = oneshot::channel();
+            let slot = Slot::Live {
+                identity: session.identity.clone(),
+                kick: tx,
+            };
+            self.shared.slots.lock().await.insert(token.clone(), slot);
+            kick = Some(rx);
+        }
+
+        let result = self
+            .session(&mut transport, &session, &protocol, &mut sent, kick)
+            .await;
+
+        let parked = Parked { session, sent };
+        match token {
+            Some(token) => self.detach(token, parked).await,
+            None => self.disconnect(&parked.session).await,
+        }
+        result
+    }
+
+    /// End a session for good.
+    async fn disconnect(&self, session: &Session) {
+        self.shared
+            .authority
+            .write()
+            .await
+            .on_disconnect(session)
+            .await;
+        self.notify();
+        if self.shared.options.announce_presence {
+            self.system(format!("{} left", session.name));
+        }
+        tracing::debug!("{} disconnected", session.name);
+    }
+
+    /// Hand a resumable session whose loop has ended to the resume that
+    /// took it over, or park it.
+    async fn detach(&self, token: Vec<u8>, parked: Parked) {
+        let slot = self.shared.slots.lock().await.remove(&token);
+        let parked = match slot {
+            Some(Slot::Resuming(handoff)) => match handoff.send(parked) {
+                Ok(()) => return,
+                // The resuming client went away too.
+                Err(parked) => parked,
+            },
+            // A resume gave up waiting for it, and started afresh.
+            None => return self.disconnect(&parked.session).await,
+            _ => parked,
+        };
+        self.park(token, parked).await;
+    }
+
+    /// Keep `parked` for the grace window, then disconnect it unless it was
+    /// resumed.
+    async fn park(&self, token: Vec<u8>, parked: Parked) {
+        tracing::debug!("{} dropped; holding for resume", parked.session.name);
+        let until = Instant::now() + self.shared.options.resume_grace;
+        self.shared
+            .slots
+            .lock()
+            .await
+            .insert(token.clone(), Slot::Parked { parked, until });
+        let server = self.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep_until(until).await;
+            let mut slots = server.shared.slots.lock().await;
+            // A later park under the same token has its own timer.
+            if let Some(Slot::Parked { until: due, .. }) = slots.get(&token)
+                && *due <= Instant::now()
+                && let Some(Slot::Parked { parked, .. }) = slots.remove(&token)
+            {
+                drop(slots);
+                server.disconnect(&parked.session).await;
+            }
+        });
+    }
+
+    /// Take the session `resume` names away from its transport or out of
+    /// the parking lot, if `identity` owns it. A session that does not
+    /// hand over in time is given up on, and ends as if evicted.
+    async fn unpark(&self, resume: &Resume, identity: &Identity) -> Option<Parked> {
+        let mut handoff = {
+            let mut slots = self.shared.slots.lock().await;
+            match slots.remove(&resume.token)? {
+                Slot::Parked { parked, .. } if parked.session.identity == *identity => {
+                    return Some(parked);
+                }
+                // The client is back before the old transport noticed it
+                // was gone.
+                Slot::Live {
+                    identity: owner,
+                    kick,
+                } if owner == *identity => {
+                    let (tx, rx) = oneshot::channel();
+                    let _ = kick.send(());
+                    slots.insert(resume.token.clone(), Slot::Resuming(tx));
+                    rx
+                }
+                slot => {
+                    slots.insert(resume.token.clone(), slot);
+                    return None;
+                }
+            }
+        };
+        let limit = self
+            .shared
+            .options
+            .idle_timeout
+            .unwrap_or(HANDSHAKE_TIMEOUT);
+        if let Ok(parked) = tokio::time::timeout(limit, &mut handoff).await {
+            return parked.ok();
+        }
+        // Likely stuck sending to a dead transport.
+        let mut slots = self.shared.slots.lock().await;
+        if let Some(Slot::Resuming(_)) = slots.get(&resume.token) {
+            slots.remove(&resume.token);
+        }
+        drop(slots);
+        // It may have handed over just now.
+        handoff.try_recv().ok()
+    }
+
+    /// Run the handshake up to `on_connect` / `on_transfer_in` (or a
+    /// resume) and the manifest.
+    async fn admit<T: Transport>(&self, transport: &mut T) -> Result<Admitted, ServerError> {
+        // The client's codec is detected from its Auth frame and used for the
+        // rest of the session.
+        let raw = match self.shared.options.idle_timeout {
+            Some(timeout) => tokio::time::timeout(timeout, transport.recv())
+                .await
+                .map_err(|_| ServerError::Timeout(timeout))?,
+            None => transport.recv().await,
+        };
+        let raw = raw
+            .map_err(ServerError::transport)?
+            .ok_or(ServerError::Closed)?;
+        let codec = Codec::detect(&raw).unwrap_or_default();
+        let ClientWire::Auth {
+            identity,
+            name,
+            passport,
+            capabilities,
+            nonce,
+            resume,
+        } = codec.decode::<ClientWire<A::Intent>>(&raw)?
+        else {
+            self.send(
+                transport,
+                codec,
+                &ServerWire::error("auth_required", "expected auth"),
+            )
+            .await?;
+            return Err(ServerError::Handshake("expected Auth".to_string()));
+        };
+
+        let capabilities = capabilities.unwrap_or_else(Capabilities::legacy);
+        let protocol = match Capabilities::current().negotiate(&capabilities, codec) {
+            Ok(protocol) => protocol,
+            Err(e) => {
+                let msg = ServerWire::error("incompatible_version", e.to_string());
+                self.send(transport, codec, &msg).await?;
+                return Err(ServerError::Handshake(e.to_string()));
+            }
+        };
+
+        if let Err(e) = self.prove_identity(transport, codec, &identity).await {
+            tracing::warn!("Rejected {identity}: {e}");
+            self.send(
+                transport,
+                codec,
+                &ServerWire::error(e.code(), e.to_string()),
+            )
+            .await?;
+            return Err(e.into());
+        }
+
+        let resumable =
+            protocol.supports(feature::RESUME) && !self.shared.options.resume_grace.is_zero();
+        let parked = match &resume {
+            Some(resume) if resumable => self.unpark(resume, &identity).await,
+            _ => None,
+        };
+        let (session, resumed) = match parked {
+            Some(Parked { session, mut sent }) => {
+                // Carry on from the last snapshot the client has.
+                if let Some(resume) = &resume
+                    && protocol.supports(feature::DELTAS)
+                {
+                    sent.deltas.ack(resume.seq);
+                }
+                (session, Some(sent))
+            }
+            None => {
+                let session = self
+                    .join(transport, codec, &protocol, identity, name, passport)
+                    .await?;
+                (session, None)
+            }
+        };
+
+        let token = if resumable { resume_token() } else { None };
+        let greeted = async {
+            if let Some(token) = &token {
+                let msg = ServerWire::Resume {
+                    token: token.clone(),
+                    resumed: resumed.is_some(),
+                };
+                self.send(transport, codec, &msg).await?;
+            }
+            let mut manifest = self.shared.manifest.clone();
+            manifest.protocol = Some(protocol.clone());
+            // Only the key behind the manifest identity can vouch for it.
+            if let Some(signer) = &self.shared.options.signer
+                && signer.identity() == manifest.identity
+            {
+                manifest.sign(signer.as_ref(), nonce.as_deref().unwrap_or_default());
+            }
+            self.send(transport, codec, &ServerWire::Manifest(manifest))
+                .await
+        }
+        .await;
+        if let Err(e) = greeted {
+            // The old token stays good, so the client can try again.
+            if let (Some(sent), Some(resume)) = (resumed, resume) {
+                self.park(resume.token, Parked { session, sent }).await;
+            }
+            return Err(e);
+        }
+        Ok(Admitted {
+            session,
+            protocol,
+            token,
+            resumed,
+        })
+    }
+
+    /// Start a new session: check any passport, then `on_connect` or
+    /// `on_transfer_in`.
+    async fn join<T: Transport>(
+        &self,
+        transport: &mut T,
+        codec: Codec,
+        protocol: &Protocol,
+        identity: Identity,
+        name: Option<String>,
+        passport: Option<Vec<u8>>,
+    ) -> Result<Session, ServerError> {
+        let id = self.shared.next_session_id.fetch_add(1, Ordering::Relaxed);
+        let display_name = name.unwrap_or_else(|| identity.payload().to_string());
+        let session = Session::new(id, identity, display_name);
+
+        // A refused passport is reported, and the client joins as if it had
+        // not brought one.
+        let passport = match passport {
+            Some(bytes) => match self.check_passport(&bytes, &session.identity).await {
+                Ok(passport) => Some(passport),
+                Err(reason) => {
+                    tracing::warn!("Passport from {} rejected: {reason}", session.name);
+                    let msg = if protocol.supports(feature::PASSPORT_ERRORS) {
+                        ServerWire::error("passport_rejected", reason.to_string())
+                    } else {
+                        ServerWire::system(format!("Passport rejected: {reason}"))
+                    };
+                    self.send(transport, codec, &msg).await?;
+                    None
+                }
+            },
+            None => None,
+        };
+
+        let joined = {
+            let mut authority = self.shared.authority.write().await;
+            match passport {
+                Some(passport) => authority
+                    .on_transfer_in(&session, passport)
+                    .await
+                    .map(|result| result.rejected),
+                None => authority.on_connect(&session).await.map(|()| Vec::new()),
+            }
+        };
+        let rejected = match joined {
+            Ok(rejected) => rejected,
+            Err(e) => {
+                let msg = ServerWire::error("connect_rejected", e.to_string());
+                self.send(transport, codec, &msg).await?;
+                return Err(ServerError::Authority(Box::new(e)));
+            }
+        };
+        if !rejected.is_empty() {
+            let items: Vec<String> = rejected
+                .iter()
+                .map(|r| format!("{} ({})", r.item, r.reason))
+                .collect();
+            let msg = ServerWire::system(format!("Import rejected: {}", items.join(", ")));
+            self.send(transport, codec, &msg).await?;
+        }
+        Ok(session)
+    }
+
+    /// Challenge identities the auth policy wants proven.
+    async fn prove_identity<T: Transport>(
+        &self,
+        transport: &mut T,
+        codec: Codec,
+        identity: &Identity,
+    ) -> Result<(), AuthError> {
+        let Some(challenge) = self.shared.options.auth_policy.check(identity)? else {
+            return Ok(());
+        };
+        let authority = &self.shared.manifest.identity;
+        let msg = ServerWire::Challenge {
+            nonce: challenge.nonce().to_vec(),
+            authority: authority.clone(),
+        };
+        // Transport failures surface as a missing response.
+        if self.send(transport, codec, &msg).await.is_err() {
+            return Err(AuthError::NoResponse);
+        }
+        let limit = self
+            .shared
+            .options
+            .idle_timeout
+            .unwrap_or(HANDSHAKE_TIMEOUT);
+        let raw = match tokio::time::timeout(limit, transport.recv()).await {
+            Ok(Ok(Some(raw))) => raw,
+            _ => return Err(AuthError::NoResponse),
+        };
+        match codec.decode::<ClientWire<A::Intent>>(&raw) {
+            Ok(ClientWire::ChallengeResponse { signature }) => {
+                Ok(challenge.verify(authority, identity, &signature)?)
+            }
+            _ => Err(AuthError::NoResponse),
+        }
+    }
+
+    /// Verify passport bytes and decode the app payload.
+    async fn check_passport(
+        &self,
+        bytes: &[u8],
+        traveller: &Identity,
+    ) -> Result<A::Passport, String> {
+        let passport = self
+            .shared
+            .verifier
+            .lock()
+            .await
+            .verify(bytes, traveller)
+            .map_err(|e| e.to_string())?;
+        serde_json::from_slice(&passport.data).map_err(|e| e.to_string())
+    }
+
+    /// The live part of a session: snapshots out, client messages in.
+    ///
+    /// Picks up from `sent`, and ends early if `kick` fires or the client
+    /// outstays [`ServerOptions::idle_timeout`].
+    async fn session<T: Transport>(
+        &self,
+        transport: &mut T,
+        session: &Session,
+        protocol: &Protocol,
+        sent: &mut Sent,
+        mut kick: Option<oneshot::Receiver<()>>,
+    ) -> Result<(), ServerError> {
+        let codec = protocol.codec;
+        // Without negotiated deltas, acks are ignored and every snapshot goes
+        // out in full.
+        let deltas_on = protocol.supports(feature::DELTAS);
+        // Subscribe before the initial snapshot so no update is missed.
+        let mut events = self.shared.events.subscribe();
+        let mut seq = sent.seq.map_or(0, |seq| seq + 1);
+        sent.seq = Some(seq);
+        self.send_snapshot(transport, codec, session, &mut sent.deltas, seq)
+            .await?;
+        let idle_timeout = self.shared.options.idle_timeout;
+        let mut heard = Instant::now();
+        // Outcomes of intents queued for a simulation tick.
+        let (reply, mut replies) = mpsc::unbounded_channel();
+
+        loop {
+            tokio::select! {
+                raw = transport.recv() => {
+                    heard = Instant::now();
+                    let Some(raw) = raw.map_err(ServerError::transport)? else {
+                        return Ok(());
+                    };
+                    match codec.decode(&raw) {
+                        Ok(ClientWire::Ack { .. }) if !deltas_on => {}
+                        Ok(wire) => {
+                            self.handle(transport, codec, session, &mut sent.deltas, &reply, wire)
+                                .await?;
+                        }
+                        Err(e) => tracing::warn!("Invalid message from {}: {e}", session.name),
+                    }
+                }
+
+                Some((id, outcome)) = replies.recv() => {
+                    self.report(transport, codec, id, outcome).await?;
+                }
+
+                event = events.recv() => match event {
+                    // Snapshots are built when sent, so a lagging session
+                    // only needs the latest one.
+                    Ok(Event::Update) | Err(broadcast::error::RecvError::Lagged(_)) => {
+                        seq += 1;
+                        sent.seq = Some(seq);
+                        self.send_snapshot(transport, codec, session, &mut sent.deltas, seq)
+                            .await?;
+                    }
+                    Ok(Event::System(message)) => {
+                        self.send(transport, codec, &ServerWire::System { message })
+                            .await?;
+                    }
+                    Err(broadcast::error::RecvError::Closed) => return Ok(()),
+                },
+
+                // The client resumed on another transport.
+                () = kicked(&mut kick) => return Ok(()),
+
+                timeout = silent(heard, idle_timeout) => {
+                    return Err(ServerError::Timeout(timeout));
+                }
+            }
+        }
+    }
+
+    /// Handle one message from a live session.
+    async fn handle<T: Transport>(
+        &self,
+        transport: &mut T,
+        codec: Codec,
+        session: &Session,
+        deltas: &mut DeltaEncoder,
+        reply: &mpsc::UnboundedSender<Reply>,
+        wire: ClientWire<A::Intent>,
+    ) -> Result<(), ServerError> {
+        match wire {
+            ClientWire::Intent(intent) => {
+                if let Some(outcome) = self.apply(session, None, intent, reply).await {
+                    self.report(transport, codec, None, outcome).await?;
+                }
+            }
+            ClientWire::Request { id, intent } => {
+                if let Some(outcome) = self.apply(session, Some(id), intent, reply).await {
+                    self.report(transport, codec, Some(id), outcome).await?;
+                }
+            }
+            ClientWire::Ack { seq } => deltas.ack(seq),
+            ClientWire::TransferRequest { destination } => {
+                let msg = self.transfer(session, destination).await?;
+                self.send(transport, codec, &msg).await?;
+            }
+            ClientWire::SubstrateRequest { hash } => match &self.shared.options.substrate {
+                Some(substrate) if substrate.hash() == hash => {
+                    for chunk in substrate.chunks() {
+                        self.send(transport, codec, &chunk).await?;
+                    }
+                }
+                _ => {
+                    let msg =
+                        ServerWire::error("substrate_not_found", format!("no substrate {hash}"));
+                    self.send(transport, codec, &msg).await?;
+                }
+            },
+            ClientWire::Ping => self.send(transport, codec, &ServerWire::Pong).await?,
+            ClientWire::Auth { .. } | ClientWire::ChallengeResponse { .. } => {}
+        }
+        Ok(())
+    }
+
+    /// Run an intent through the authority, notifying sessions if it was
+    /// applied. While simulating, queue it for the next tick instead and
+    /// return `None`; its outcome comes back on `reply`.
+    async fn apply(
+        &self,
+        session: &Session,
+        id: Option<u64>,
+        intent: A::Intent,
+        reply: &mpsc::UnboundedSender<Reply>,
+    ) -> Option<IntentOutcome> {
+        let queue = self.shared.ticks.lock().unwrap().clone();
+        if let Some(queue) = queue {
+            let (session, reply) = (session.clone(), reply.clone());
+            let queued: Queued<A> = Box::new(move |authority| {
+                Box::pin(async move {
+                    let outcome = outcome(authority.handle_intent(&session, intent).await);
+                    let _ = reply.send((id, outcome));
+                })
+            });
+            // The simulation's task is gone; apply it now.
+            if let Err(e) = queue.send(queued) {
+                e.0(&mut *self.shared.authority.write().await).await;
+                self.notify();
+            }
+            return None;
+        }
+        let result = self
+            .shared
+            .authority
+            .write()
+            .await
+            .handle_intent(session, intent)
+            .await;
+        if result.is_ok() {
+            self.notify();
+        }
+        Some(outcome(result))
+    }
+
+    /// Tell the client how its intent went: a result for a request, an
+    /// error for a refused plain intent.
+    async fn report<T: Transport>(
+        &self,
+        transport: &mut T,
+        codec: Codec,
+        id: Option<u64>,
+        outcome: IntentOutcome,
+    ) -> Result<(), ServerError> {
+        let msg = match (id, outcome) {
+            (Some(id), outcome) => ServerWire::IntentResult { id, outcome },
+            (None, IntentOutcome::Rejected { code, message }) => {
+                ServerWire::Error { code, message }
+            }
+            (None, IntentOutcome::Accepted) => return Ok(()),
+        };
+        self.send(transport, codec, &msg).await
+    }
+
+    /// Answer a transfer request with a signed passport, or an error.
+    async fn transfer(
+        &self,
+        session: &Session,
+        destination: String,
+    ) -> Result<ServerWire<A::Snapshot>, ServerError> {
+        let Some(signer) = &self.shared.options.signer else {
+            return Ok(ServerWire::error(
+                "transfer_unavailable",
+                "this authority does not issue passports",
+            ));
+        };
+        let data = {
+            let authority = self.shared.authority.read().await;
+            if !authority.validate_destination(&destination).await {
+                return Ok(ServerWire::error(
+                    "invalid_destination",
+                    format!("Unknown destination: {destination}"),
+                ));
+            }
+            serde_json::to_vec(&authority.emit_passport(session).await)?
+        };
+        let passport = Passport::issue(
+            signer.as_ref(),
+            session.identity.clone(),
+            destination.clone(),
+            data,
+            self.shared.options.passport_ttl,
+        )?;
+        tracing::info!("{} transferring to {destination}", session.name);
+        Ok(ServerWire::Transfer {
+            destination,
+            passport: passport.to_bytes()?,
+        })
+    }
+
+    async fn send_snapshot<T: Transport>(
+        &self,
+        transport: &mut T,
+        codec: Codec,
+        session: &Session,
+        deltas: &mut DeltaEncoder,
+        seq: u64,
+    ) -> Result<(), ServerError> {
+        let snapshot = self
+            .shared
+            .authority
+            .read()
+            .await
+            .snapshot_for(session)
+            .await;
+        let msg = deltas.encode(seq, snapshot)?;
+        self.send(transport, codec, &msg).await
+    }
+
+    async fn send<T: Transport>(
+        &self,
+        transport: &mut T,
+        codec: Codec,
+        msg: &ServerWire<A::Snapshot>,
+    ) -> Result<(), ServerError> {
+        transport
+            .send(&codec.encode(msg)?)
+            .await
+            .map_err(ServerError::transport)
+    }
+}
+
+/// Resolves once a resume has taken the session over; never without `kick`.
+async fn kicked(kick: &mut Option<oneshot::Receiver<()>>) {
+    match kick {
+        Some(kick) => {
+            let _ = kick.await;
+        }
+        None => std::future::pending().await,
+    }
+}
+
+/// The outcome reported for an intent the authority applied or refused.
+fn outcome<E: std::fmt::Display>(result: Result<(), E>) -> IntentOutcome {
+    match result {
+        Ok(()) => IntentOutcome::Accepted,
+        Err(e) => IntentOutcome::rejected("intent_error", e.to_string()),
+    }
+}
+
+/// Resolves to `timeout` once nothing has been heard for that long since
+/// `heard`; never without a timeout.
+async fn silent(heard: Instant, timeout: Option<Duration>) -> Duration {
+    match timeout {
+        Some(timeout) => {
+            tokio::time::sleep_until(heard + timeout).await;
+            timeout
+        }
+        None => std::future::pending().await,
+    }
+}
+
+/// A fresh resume token, or `None` if the system has no randomness to give.
+fn resume_token() -> Option<Vec<u8>> {
+    let mut token = vec![0; RESUME_TOKEN_LEN];
+    getrandom::fill(&mut token).ok()?;
+    Some(token)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use interconnect_core::{ImportResult, Loopback, PROTOCOL_VERSION, SimpleAuthority, loopback};
+
+    /// Intents must be maps to sit inside the tagged `ClientWire`.
+    #[derive(serde::Serialize, serde::Deserialize)]
+    struct Add {
+        add: u64,
+    }
+
+    /// A counter room.
+    #[derive(Default)]
+    struct Counter {
+        count: u64,
+    }
+
+    #[derive(Debug, thiserror::Error)]
+    #[error("too big")]
+    struct TooBig;
+
+    impl SimpleAuthority for Counter {
+        type Intent = Add;
+        type Snapshot = u64;
+        type Passport = ();
+        type Error = TooBig;
+
+        fn on_connect(&mut self, _session: &Session) -> Result<(), TooBig> {
+            Ok(())
+        }
+
+        fn on_transfer_in(
+            &mut self,
+            _session: &Session,
+            passport: (),
+        ) -> Result<ImportResult<()>, TooBig> {
+            Ok(ImportResult::accept(passport))
+        }
+
+        fn on_disconnect(&mut self, _session: &Session) {}
+
+        fn handle_intent(&mut self, _session: &Session, Add { add }: Add) -> Result<(), TooBig> {
+            if add > 10 {
+                return Err(TooBig);
+            }
+            self.count += add;
+            Ok(())
+        }
+
+        fn snapshot(&self) -> u64 {
+            self.count
+        }
+
+        fn emit_passport(&self, _session: &Session) {}
+
+        fn validate_destination(&self, _destination: &str) -> bool {
+            true
+        }
+    }
+
+    /// The client end of an in-memory connection.
+    struct Pipe(Loopback);
+
+    impl Pipe {
+        async fn say(&mut self, msg: ClientWire<Add>) {
+            self.0
+                .send(&Codec::Json.encode(&msg).unwrap())
+                .await
+                .unwrap();
+        }
+
+        async fn hear(&mut self) -> ServerWire<u64> {
+            Codec::Json
+                .decode(&self.0.recv().await.unwrap().unwrap())
+                .unwrap()
+        }
+
+        async fn join(server: &Server<Counter>, name: &str) -> Self {
+            let (client, end) = loopback();
+            let mut client = Pipe(client);
+            let server = server.clone();
+            tokio::spawn(async move { server.serve(end).await });
+            client
+                .say(ClientWire::Auth {
+                    identity: Identity::local(name),
+                    name: None,
+                    passport: None,
+                    capabilities: Some(Capabilities::current().without(feature::RESUME)),
+                    nonce: None,
+                    resume: None,
+                })
+                .await;
+            let ServerWire::Manifest(manifest) = client.hear().await else {
+                panic!("expected manifest");
+            };
+            assert_eq!(manifest.protocol.unwrap().version, PROTOCOL_VERSION);
+            client
+        }
+    }
+
+    fn server(options: ServerOptions) -> Server<Counter> {
+        let manifest = Manifest::new(Identity::local("counter"), "counter");
+        Server::new(Counter::default(), manifest, options)
+    }
+
+    #[tokio::test]
+    async fn intents_fan_out() {
+        let server = server(ServerOptions::default());
+        let mut alice = Pipe::join(&server, "alice").await;
+        assert!(matches!(
+            alice.hear().await,
+            ServerWire::Snapshot { data: 0, .. }
+        ));
+        let mut bob = Pipe::join(&server, "bob").await;
+        assert!(matches!(
+            bob.hear().await,
+            ServerWire::Snapshot { data: 0, .. }
+        ));
+
+        bob.say(ClientWire::Intent(Add { add: 5 })).await;
+        // Alice also hears about Bob joining; the last snapshot has the count.
+        loop {
+            if let ServerWire::Snapshot { data: 5, .. } = alice.hear().await {
+                break;
+            }
+        }
+
+        bob.say(ClientWire::Intent(Add { add: 50 })).await;
+        loop {
+            match bob.hear().await {
+                ServerWire::Error { code, .. } => {
+                    assert_eq!(code, "intent_error");
+                    break;
+                }
+                ServerWire::Snapshot { .. } => continue,
+                other => panic!("unexpected {other:?}"),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn transfer_needs_signer() {
+        let server = server(ServerOptions::default());
+        let mut alice = Pipe::join(&server, "alice").await;
+        alice.hear().await;
+        alice
+            .say(ClientWire::TransferRequest {
+                destination: "ws://elsewhere".into(),
+            })
+            .await;
+        assert!(matches!(
+            alice.hear().await,
+            ServerWire::Error { code, .. } if code == "transfer_unavailable"
+        ));
+
+        let keypair = interconnect_core::Keypair::from_seed([1; 32]);
+        let server = self::server(ServerOptions::default().signer(keypair.clone()));
+        let mut alice = Pipe::join(&server, "alice").await;
+        alice.hear().await;
+        alice
+            .say(ClientWire::TransferRequest {
+                destination: "ws://elsewhere".into(),
+            })
+            .await;
+        let ServerWire::Transfer { passport, .. } = alice.hear().await else {
+            panic!("expected transfer");
+        };
+        let passport = Passport::from_bytes(&passport).unwrap();
+        assert_eq!(passport.issuer, keypair.identity());
+        assert_eq!(passport.destination, "ws://elsewhere");
+    }
+
+    #[tokio::test]
+    async fn requests_get_results() {
+        let server = server(ServerOptions::default());
+        let mut alice = Pipe::join(&server, "alice").await;
+        alice.hear().await;
+
+        alice
+            .say(ClientWire::Request {
+                id: 1,
+                intent: Add { add: 3 },
+            })
+            .await;
+        assert!(matches!(
+            alice.hear().await,
+            ServerWire::IntentResult {
+                id: 1,
+                outcome: IntentOutcome::Accepted
+            }
+        ));
+        assert!(matches!(
+            alice.hear().await,
+            ServerWire::Snapshot { data: 3, .. }
+        ));
+
+        alice
+            .say(ClientWire::Request {
+                id: 2,
+                intent: Add { add: 30 },
+            })
+            .await;
+        assert!(matches!(
+            alice.hear().await,
+            ServerWire::IntentResult {
+                id: 2,
+                outcome: IntentOutcome::Rejected { .. }
+            }
+        ));
+    }
+
+    #[tokio::test]
+    async fn incompatible_client_is_refused() {
+        let server = server(ServerOptions::default());
+        let (client, end) = loopback();
+        let mut client = Pipe(client);
+        let task = tokio::spawn(async move { server.serve(end).await });
+        client
+            .say(ClientWire::Auth {
+                identity: Identity::local("future"),
+                name: None,
+                passport: None,
+                capabilities: Some(Capabilities {
+                    versions: vec![PROTOCOL_VERSION + 1],
+                    features: Vec::new(),
+                }),
+                nonce: None,
+                resume: None,
+            })
+            .await;
+        assert!(matches!(
+            client.hear().await,
+            ServerWire::Error { code, .. } if code == "incompatible_version"
+        ));
+        assert!(matches!(
+            task.await.unwrap(),
+            Err(ServerError::Handshake(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn clients_check_signed_manifests() {
+        use interconnect_client::{ClientError, ConnectOptions, Connection};
+        use interconnect_core::{Keypair, KnownAuthorities, TrustError};
+
+        let signed = |seed| {
+            let key = Keypair::from_seed([seed; 32]);
+            let manifest = Manifest::new(key.identity(), "counter");
+            Server::new(
+                Counter::default(),
+                manifest,
+                ServerOptions::default().signer(key),
+            )
+        };
+        let known = KnownAuthorities::in_memory();
+        let connect = |server: Server<Counter>| {
+            let (client, end) = loopback();
+            tokio::spawn(async move { server.serve(end).await });
+            let options = ConnectOptions::default().known_authorities(known.clone(), "mem://room");
+            Connection::<_, Add, u64>::connect_with(
+                client,
+                Identity::local("alice"),
+                None,
+                None,
+                options,
+            )
+        };
+
+        let first = signed(1);
+        connect(first.clone()).await.unwrap();
+        assert_eq!(
+            known.get("mem://room"),
+            Some(first.manifest().identity.clone())
+        );
+        connect(first).await.unwrap();
+
+        // Another key at the same address is refused, as is no key at all.
+        assert!(matches!(
+            connect(signed(2)).await,
+            Err(ClientError::Trust(TrustError::Changed { .. }))
+        ));
+        assert!(matches!(
+            connect(server(ServerOptions::default())).await,
+            Err(ClientError::Trust(TrustError::Unsigned))
+        ));
+    }
+
+    #[tokio::test]
+    async fn dropped_sessions_resume() {
+        use interconnect_core::{DeltaDecoder, Resume};
+
+        let auth = |resume| ClientWire::Auth {
+            identity: Identity::local("alice"),
+            name: None,
+            passport: None,
+            capabilities: Some(Capabilities::current()),
+            nonce: None,
+            resume,
+        };
+        let connect = |server: &Server<Counter>| {
+            let (client, end) = loopback();
+            let server = server.clone();
+            tokio::spawn(async move { server.serve(end).await });
+            Pipe(client)
+        };
+        let options = ServerOptions::default().announce_presence(true);
+
+        let server = server(options.clone().resume_grace(Duration::from_secs(60)));
+        let mut alice = connect(&server);
+        alice.say(auth(None)).await;
+        let ServerWire::Resume {
+            token,
+            resumed: false,
+        } = alice.hear().await
+        else {
+            panic!("expected a resume token");
+        };
+        assert!(matches!(alice.hear().await, ServerWire::Manifest(_)));
+        assert!(matches!(
+            alice.hear().await,
+            ServerWire::Snapshot { seq: 0, data: 0 }
+        ));
+        alice.say(ClientWire::Ack { seq: 0 }).await;
+
+        let mut bob = Pipe::join(&server, "bob").await;
+        bob.say(ClientWire::Intent(Add { add: 2 })).await;
+        while !matches!(bob.hear().await, ServerWire::Snapshot { data: 2, .. }) {}
+        drop(alice);
+
+        // Back with the last acked snapshot: a delta from it to now.
+        let mut alice = connect(&server);
+        alice.say(auth(Some(Resume { token, seq: 0 }))).await;
+        assert!(matches!(
+            alice.hear().await,
+            ServerWire::Resume { resumed: true, .. }
+        ));
+        assert!(matches!(alice.hear().await, ServerWire::Manifest(_)));
+        let ServerWire::Delta {
+            base_seq: 0,
+            seq,
+            patch,
+        } = alice.hear().await
+        else {
+            panic!("expected a delta");
+        };
+        let mut decoder = DeltaDecoder::new();
+        decoder.snapshot(0, &0u64).unwrap();
+        assert_eq!(decoder.delta::<u64>(0, seq, &patch).unwrap(), 2);
+
+        // Nobody saw alice leave or join again.
+        bob.say(ClientWire::Intent(Add { add: 1 })).await;
+        loop {
+            match bob.hear().await {
+                ServerWire::Snapshot { data: 3, .. } => break,
+                ServerWire::Snapshot { .. } => continue,
+                other => panic!("unexpected {other:?}"),
+            }
+        }
+
+        // Once the grace window closes, the session is gone.
+        let server = self::server(options.resume_grace(Duration::from_millis(10)));
+        let mut alice = connect(&server);
+        alice.say(auth(None)).await;
+        let ServerWire::Resume { token, .. } = alice.hear().await else {
+            panic!("expected a resume token");
+        };
+        let mut bob = Pipe::join(&server, "bob").await;
+        drop(alice);
+        loop {
+            if let ServerWire::System { message } = bob.hear().await
+                && message == "alice left"
+            {
+                break;
+            }
+        }
+        let mut alice = connect(&server);
+        alice.say(auth(Some(Resume { token, seq: 0 }))).await;
+        assert!(matches!(
+            alice.hear().await,
+            ServerWire::Resume { resumed: false, .. }
+        ));
+    }
+
+    #[tokio::test]
+    async fn stuck_sessions_do_not_block_resumes() {
+        use interconnect_core::Resume;
+        use std::sync::atomic::AtomicBool;
+
+        /// Stops sending, without failing, once `stuck` is set.
+        struct Stuck {
+            inner: Loopback,
+            stuck: Arc<AtomicBool>,
+        }
+
+        impl Transport for Stuck {
+            type Error = <Loopback as Transport>::Error;
+
+            async fn send(&mut self, data: &[u8]) -> Result<(), Self::Error> {
+                if self.stuck.load(Ordering::Relaxed) {
+                    std::future::pending::<()>().await;
+                }
+                self.inner.send(data).await
+            }
+
+            async fn recv(&mut self) -> Result<Option<Vec<u8>>, Self::Error> {
+                self.inner.recv().await
+            }
+        }
+
+        let auth = |resume| ClientWire::Auth {
+            identity: Identity::local("alice"),
+            name: None,
+            passport: None,
+            capabilities: Some(Capabilities::current()),
+            nonce: None,
+            resume,
+        };
+        let server = server(
+            ServerOptions::default()
+                .resume_grace(Duration::from_secs(60))
+                .idle_timeout(Duration::from_millis(50)),
+        );
+        let (client, end) = loopback();
+        let stuck = Arc::new(AtomicBool::new(false));
+        let end = Stuck {
+            inner: end,
+            stuck: stuck.clone(),
+        };
+        let serving = server.clone();
+        tokio::spawn(async move { serving.serve(end).await });
+        let mut alice = Pipe(client);
+        alice.say(auth(None)).await;
+        let ServerWire::Resume { token, .. } = alice.hear().await else {
+            panic!("expected a resume token");
+        };
+
+        stuck.store(true, Ordering::Relaxed);
+        server.system("into the void");
+        tokio::time::sleep(Duration::from_millis(10)).await;
+
+        let (client, end) = loopback();
+        let serving = server.clone();
+        tokio::spawn(async move { serving.serve(end).await });
+        let mut alice = Pipe(client);
+        alice.say(auth(Some(Resume { token, seq: 0 }))).await;
+        let answer = tokio::time::timeout(Duration::from_secs(1), alice.hear())
+            .await
+            .expect("resume waited on the stuck session");
+        assert!(matches!(answer, ServerWire::Resume { resumed: false, .. }));
+    }
+
+    #[tokio::test]
+    async fn split_connections_send_while_receiving() {
+        use futures_util::{SinkExt, StreamExt};
+        use interconnect_client::Connection;
+
+        let server = server(ServerOptions::default());
+        let (client, end) = loopback();
+        let serving = server.clone();
+        tokio::spawn(async move { serving.serve(end).await });
+        let (conn, _) =
+            Connection::<_, Add, u64>::connect(client, Identity::local("alice"), None, None)
+                .await
+                .unwrap();
+        let (tx, rx) = conn.split();
+
+        // One task waits for the total while the others send.
+        let total = tokio::spawn(async move {
+            let mut messages = std::pin::pin!(rx.into_stream());
+            while let Some(msg) = messages.next().await {
+                if let ServerWire::Snapshot { data: 6, .. } = msg.unwrap() {
+                    return true;
+                }
+            }
+            false
+        });
+        let other = tx.clone();
+        tokio::spawn(async move { other.send_intent(Add { add: 1 }).await.unwrap() });
+        tx.send_intent(Add { add: 2 }).await.unwrap();
+        let mut intents = std::pin::pin!(tx.into_sink());
+        intents.send(Add { add: 3 }).await.unwrap();
+
+        assert!(total.await.unwrap());
+        assert_eq!(server.read(|counter| counter.count).await, 6);
+    }
+
+    #[tokio::test]
+    async fn silent_sessions_are_evicted() {
+        use interconnect_client::{ConnectOptions, Connection, Heartbeat};
+
+        let server = server(ServerOptions::default().idle_timeout(Duration::from_millis(50)));
+        let mut silent = Pipe::join(&server, "alice").await;
+        assert!(matches!(silent.hear().await, ServerWire::Snapshot { .. }));
+        assert!(silent.0.recv().await.unwrap().is_none());
+
+        // A client with a heartbeat outlasts the timeout many times over.
+        let (client, end) = loopback();
+        let serving = server.clone();
+        tokio::spawn(async move { serving.serve(end).await });
+        let heartbeat = Heartbeat::new(Duration::from_millis(10), Duration::from_millis(100));
+        let options = ConnectOptions::default().heartbeat(heartbeat);
+        let (mut conn, _) = Connection::<_, Add, u64>::connect_with(
+            client,
+            Identity::local("bob"),
+            None,
+            None,
+            options,
+        )
+        .await
+        .unwrap();
+        let quiet = tokio::time::timeout(Duration::from_millis(300), conn.recv()).await;
+        assert!(quiet.is_err(), "nothing to receive");
+        assert!(!conn.is_ghost());
+        assert!(conn.latency().is_some());
+    }
+
+    #[tokio::test]
+    async fn unanswered_challenges_time_out() {
+        let server = server(ServerOptions::default().idle_timeout(Duration::from_millis(50)));
+        let (client, end) = loopback();
+        let mut client = Pipe(client);
+        let serving = tokio::spawn(async move { server.serve(end).await });
+        let keypair = interconnect_core::Keypair::from_seed([1; 32]);
+        client
+            .say(ClientWire::Auth {
+                identity: keypair.identity(),
+                name: None,
+                passport: None,
+                capabilities: None,
+                nonce: None,
+                resume: None,
+            })
+            .await;
+        assert!(matches!(client.hear().await, ServerWire::Challenge { .. }));
+
+        // Never answered.
+        let served = tokio::time::timeout(Duration::from_secs(1), serving)
+            .await
+            .expect("session outlived the timeout");
+        assert!(served.unwrap().is_err());
+    }
+
+    #[tokio::test]
+    async fn simulation_batches_intents_per_tick() {
+        let server = server(ServerOptions::default());
+        let mut alice = Pipe::join(&server, "alice").await;
+        alice.hear().await;
+
+        let options = TickOptions::new(Duration::from_millis(50)).snapshot_every(2);
+        let stats = server.simulate(options, |counter, _| counter.count += 100);
+        alice
+            .say(ClientWire::Request {
+                id: 1,
+                intent: Add { add: 3 },
+            })
+            .await;
+        alice.say(ClientWire::Intent(Add { add: 50 })).await;
+        alice
+            .say(ClientWire::Request {
+                id: 2,
+                intent: Add { add: 4 },
+            })
+            .await;
+
+        // Outcomes arrive in order, once their tick has run; snapshots only
+        // after every second step.
+        let mut heard = vec![];
+        let mut snapshot = None;
+        while heard.len() < 3 || snapshot.is_none() {
+            match alice.hear().await {
+                ServerWire::IntentResult { id, outcome } => heard.push((id, outcome.is_accepted())),
+                ServerWire::Error { code, .. } => {
+                    assert_eq!(code, "intent_error");
+                    heard.push((0, false));
+                }
+                ServerWire::Snapshot { data, .. } if data % 100 == 7 => snapshot = Some(data),
+                ServerWire::Snapshot { data, .. } => assert_eq!(data / 100 % 2, 0),
+                other => panic!("unexpected {other:?}"),
+            }
+        }
+        assert_eq!(heard, [(1, true), (0, false), (2, true)]);
+        assert_eq!(snapshot.unwrap() / 100 % 2, 0);
+        assert_eq!(stats.intents(), 3);
+
+        // Stopped, intents apply as they arrive again.
+        server.stop_simulation();
+        tokio::time::sleep(Duration::from_millis(120)).await;
+        let ticks = stats.ticks();
+        alice
+            .say(ClientWire::Request {
+                id: 3,
+                intent: Add { add: 1 },
+            })
+            .await;
+        loop {
+            if let ServerWire::IntentResult { id, outcome } = alice.hear().await {
+                assert_eq!(id, 3);
+                assert!(outcome.is_accepted());
+                break;
+            }
+        }
+        tokio::time::sleep(Duration::from_millis(120)).await;
+        assert_eq!(stats.ticks(), ticks);
+        assert_eq!(server.read(|counter| counter.count % 100).await, 8);
+    }
+
+    #[tokio::test]
+    async fn overruns_are_reported() {
+        let server = server(ServerOptions::default());
+        let (overruns, mut overran) = mpsc::unbounded_channel();
+        let options = TickOptions::new(Duration::from_millis(10))
+            .on_overrun(move |overrun| overruns.send(overrun).unwrap());
+        let stats = server.simulate(options, |_, tick| {
+            if tick.number == 1 {
+                std::thread::sleep(Duration::from_millis(30));
+            }
+        });
+        let overrun = overran.recv().await.unwrap();
+        assert_eq!(overrun.tick, 1);
+        assert!(overrun.took >= Duration::from_millis(30));
+        assert_eq!(overrun.period, Duration::from_millis(10));
+        assert_eq!(stats.overruns(), 1);
+    }
+}
@@ -0,0 +1,131 @@
+//! Fixed-timestep simulation, for [`Server::simulate`](crate::Server::simulate).
+//!
+//! A real-time room advances on a clock rather than per intent: intents
+//! that arrive between ticks are buffered, then applied in arrival order at
+//! the start of the next tick, and the simulation steps forward by the same
+//! `dt` every time.
+
+use std::fmt;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+/// Options for [`Server::simulate`](crate::Server::simulate).
+#[derive(Clone)]
+pub struct TickOptions {
+    /// Time between ticks, and the `dt` each tick advances by. Never zero.
+    pub(crate) period: Duration,
+    /// Send sessions a snapshot every this many ticks. Never zero.
+    pub(crate) snapshot_every: u32,
+    /// Called when a tick takes longer than `period`.
+    pub(crate) on_overrun: Option<Arc<dyn Fn(Overrun) + Send + Sync>>,
+}
+
+impl fmt::Debug for TickOptions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TickOptions")
+            .field("period", &self.period)
+            .field("snapshot_every", &self.snapshot_every)
+            .field("on_overrun", &self.on_overrun.is_some())
+            .finish()
+    }
+}
+
+impl TickOptions {
+    /// Tick every `period`, with a snapshot after every tick.
+    ///
+    /// Panics if `period` is zero.
+    pub fn new(period: Duration) -> Self {
+        assert!(!period.is_zero(), "tick period must not be zero");
+        Self {
+            period,
+            snapshot_every: 1,
+            on_overrun: None,
+        }
+    }
+
+    /// Tick `rate` times a second, at least once and at most a billion.
+    pub fn per_second(rate: u32) -> Self {
+        Self::new(Duration::from_secs(1) / rate.clamp(1, 1_000_000_000))
+    }
+
+    /// Time between ticks, and the `dt` each tick advances by.
+    pub fn period(&self) -> Duration {
+        self.period
+    }
+
+    /// Send sessions a snapshot every `ticks` ticks, or every tick if zero.
+    /// At 60 ticks a second, 3 sends 20 snapshots a second.
+    pub fn snapshot_every(mut self, ticks: u32) -> Self {
+        self.snapshot_every = ticks.max(1);
+        self
+    }
+
+    /// Call `f` when a tick takes longer than its period.
+    pub fn on_overrun(mut self, f: impl Fn(Overrun) + Send + Sync + 'static) -> Self {
+        self.on_overrun = Some(Arc::new(f));
+        self
+    }
+}
+
+/// One step of a simulation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Tick {
+    /// Counts from zero.
+    pub number: u64,
+    /// Simulated time since the last tick: always the tick period.
+    pub dt: Duration,
+}
+
+/// A tick that took longer than its period.
+///
+/// The ticks it ran into are skipped, not run late, so the simulation falls
+/// behind the wall clock rather than bursting to catch up.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Overrun {
+    /// The tick's number.
+    pub tick: u64,
+    /// How long it took, intents and step together.
+    pub took: Duration,
+    /// The period it should have fitted in.
+    pub period: Duration,
+}
+
+/// Counters for a running simulation. Cheap to clone; clones share counts.
+#[derive(Debug, Clone, Default)]
+pub struct TickStats {
+    inner: Arc<Counts>,
+}
+
+#[derive(Debug, Default)]
+struct Counts {
+    ticks: AtomicU64,
+    intents: AtomicU64,
+    overruns: AtomicU64,
+}
+
+impl TickStats {
+    /// Ticks run so far.
+    pub fn ticks(&self) -> u64 {
+        self.inner.ticks.load(Ordering::Relaxed)
+    }
+
+    /// Intents applied so far.
+    pub fn intents(&self) -> u64 {
+        self.inner.intents.load(Ordering::Relaxed)
+    }
+
+    /// Ticks that took longer than their period.
+    pub fn overruns(&self) -> u64 {
+        self.inner.overruns.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn ticked(&self, intents: u64) {
+        self.inner.ticks.fetch_add(1, Ordering::Relaxed);
+        self.inner.intents.fetch_add(intents, Ordering::Relaxed);
+    }
+
+    pub(crate) fn overran(&self) {
+        self.inner.overruns.fetch_add(1, Ordering::Relaxed);
+    }
+}
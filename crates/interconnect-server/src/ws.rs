@@ -0,0 +1,91 @@
+//! WebSocket listener and server-side transport.
+
+use crate::Listener;
+use futures_util::{SinkExt, StreamExt};
+use interconnect_core::Transport;
+use std::net::SocketAddr;
+use tokio::net::{TcpListener, TcpStream, ToSocketAddrs};
+use tokio_tungstenite::WebSocketStream;
+use tokio_tungstenite::tungstenite::{Error, Message};
+
+/// Accepts WebSocket clients on a TCP port.
+pub struct WsListener {
+    inner: TcpListener,
+}
+
+impl WsListener {
+    /// Listen on `addr`.
+    pub async fn bind(addr: impl ToSocketAddrs) -> std::io::Result<Self> {
+        Ok(Self {
+            inner: TcpListener::bind(addr).await?,
+        })
+    }
+
+    pub fn local_addr(&self) -> std::io::Result<SocketAddr> {
+        self.inner.local_addr()
+    }
+}
+
+impl Listener for WsListener {
+    type Transport = WsTransport;
+
+    async fn accept(&mut self) -> std::io::Result<WsTransport> {
+        let (stream, _) = self.inner.accept().await?;
+        Ok(WsTransport::accept(stream))
+    }
+}
+
+/// A server-side WebSocket connection.
+///
+/// The WebSocket upgrade runs on first use, so a slow client never holds up
+/// the accept loop.
+pub struct WsTransport {
+    pending: Option<TcpStream>,
+    ws: Option<WebSocketStream<TcpStream>>,
+}
+
+impl WsTransport {
+    /// Wrap a freshly accepted TCP connection.
+    pub fn accept(stream: TcpStream) -> Self {
+        Self {
+            pending: Some(stream),
+            ws: None,
+        }
+    }
+
+    async fn ws(&mut self) -> Result<&mut WebSocketStream<TcpStream>, Error> {
+        if let Some(stream) = self.pending.take() {
+            self.ws = Some(tokio_tungstenite::accept_async(stream).await?);
+        }
+        self.ws.as_mut().ok_or(Error::ConnectionClosed)
+    }
+}
+
+impl Transport for WsTransport {
+    type Error = Error;
+
+    async fn send(&mut self, data: &[u8]) -> Result<(), Error> {
+        // JSON goes out as text frames (what browsers expect); binary codecs
+        // as binary frames.
+        let msg = match std::str::from_utf8(data) {
+            Ok(text) => Message::Text(text.into()),
+            Err(_) => Message::Binary(data.to_vec().into()),
+        };
+        self.ws().await?.send(msg).await
+    }
+
+    async fn recv(&mut self) -> Result<Option<Vec<u8>>, Error> {
+        let ws = self.ws().await?;
+        loop {
+            match ws.next().await {
+                None => return Ok(None),
+                Some(Err(e)) => return Err(e),
+                Some(Ok(Message::Text(t))) => return Ok(Some(t.as_bytes().to_vec())),
+                Some(Ok(Message::Binary(b))) => return Ok(Some(b.into())),
+                Some(Ok(Message::Close(_))) => return Ok(None),
+                // tungstenite answers pings itself; skip control frames.
+                Some(Ok(_)) => continue,
+            }
+        }
+    }
+}
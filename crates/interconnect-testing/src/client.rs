@@ -0,0 +1,114 @@
+//! A simulated client.
+
+use crate::HarnessError;
+use interconnect_client::{ClientError, Connection};
+use interconnect_core::{IntentOutcome, Loopback, ServerWire, Wire};
+use std::time::Duration;
+
+/// A client connected to a [`Harness`](crate::Harness).
+///
+/// Keeps the latest snapshot and the system messages seen so far. Waiting
+/// methods fail with [`HarnessError::Timeout`] if nothing suitable arrives,
+/// and with `ClientError::Server` if the authority sends an error instead.
+/// Dropping the client disconnects it.
+pub struct TestClient<I, S> {
+    conn: Connection<Loopback, I, S>,
+    snapshot: S,
+    /// Snapshots received, including the initial one.
+    snapshots: u64,
+    system: Vec<String>,
+    timeout: Duration,
+}
+
+impl<I: Wire, S: Wire> TestClient<I, S> {
+    pub(crate) fn new(conn: Connection<Loopback, I, S>, snapshot: S, timeout: Duration) -> Self {
+        Self {
+            conn,
+            snapshot,
+            snapshots: 1,
+            system: Vec::new(),
+            timeout,
+        }
+    }
+
+    /// The latest snapshot received.
+    pub fn snapshot(&self) -> &S {
+        &self.snapshot
+    }
+
+    /// System messages received so far, oldest first.
+    pub fn system_messages(&self) -> &[String] {
+        &self.system
+    }
+
+    /// The underlying connection, for anything the harness does not wrap.
+    pub fn connection(&mut self) -> &mut Connection<Loopback, I, S> {
+        &mut self.conn
+    }
+
+    /// Send an intent without waiting for its outcome.
+    pub async fn send(&mut self, intent: I) -> Result<(), HarnessError> {
+        Ok(self.conn.send_intent(intent).await?)
+    }
+
+    /// Send an intent and wait for the authority's verdict.
+    pub async fn request(&mut self, intent: I) -> Result<IntentOutcome, HarnessError> {
+        tokio::time::timeout(self.timeout, self.conn.request(intent))
+            .await
+            .map_err(|_| HarnessError::Timeout(self.timeout))?
+            .map_err(Into::into)
+    }
+
+    /// Wait for the next snapshot.
+    pub async fn next_snapshot(&mut self) -> Result<&S, HarnessError> {
+        let seen = self.snapshots;
+        while self.snapshots == seen {
+            self.next().await?;
+        }
+        Ok(&self.snapshot)
+    }
+
+    /// Wait until the latest snapshot satisfies `pred`, which may already be
+    /// the case.
+    pub async fn wait_for(&mut self, mut pred: impl FnMut(&S) -> bool) -> Result<&S, HarnessError> {
+        while !pred(&self.snapshot) {
+            self.next_snapshot().await?;
+        }
+        Ok(&self.snapshot)
+    }
+
+    /// Ask to transfer to `destination` and return the passport to present
+    /// there with [`Harness::arrive`](crate::Harness::arrive).
+    pub async fn transfer(&mut self, destination: &str) -> Result<Vec<u8>, HarnessError> {
+        self.conn.request_transfer(destination.to_string()).await?;
+        loop {
+            if let Some(ServerWire::Transfer { passport, .. }) = self.next().await? {
+                return Ok(passport);
+            }
+        }
+    }
+
+    /// Receive the next message. Snapshots and system messages are recorded
+    /// rather than returned.
+    async fn next(&mut self) -> Result<Option<ServerWire<S>>, HarnessError> {
+        let msg = tokio::time::timeout(self.timeout, self.conn.recv())
+            .await
+            .map_err(|_| HarnessError::Timeout(self.timeout))??
+            .ok_or(ClientError::Closed)?;
+        match msg {
+            ServerWire::Snapshot { data, .. } => {
+                self.snapshot = data;
+                self.snapshots += 1;
+                Ok(None)
+            }
+            ServerWire::System { message } => {
+                self.system.push(message);
+                Ok(None)
+            }
+            ServerWire::Error { code, message } => {
+                Err(ClientError::Server { code, message }.into())
+            }
+            other => Ok(Some(other)),
+        }
+    }
+}
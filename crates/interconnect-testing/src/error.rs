@@ -0,0 +1,17 @@
+//! Harness error types.
+
+use interconnect_client::ClientError;
+use std::time::Duration;
+
+/// Errors from driving a simulated client.
+#[derive(Debug, thiserror::Error)]
+pub enum HarnessError {
+    /// The connection failed, or the authority sent a `ServerWire::Error`
+    /// (as `ClientError::Server`).
+    #[error(transparent)]
+    Client(#[from] ClientError),
+
+    /// Nothing suitable arrived in time.
+    #[error("timed out after {0:?}")]
+    Timeout(Duration),
+}
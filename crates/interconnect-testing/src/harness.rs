@@ -0,0 +1,306 @@
+//! An authority served over in-memory transports.
+
+use crate::{HarnessError, TestClient};
+use interconnect_client::Connection;
+use interconnect_core::{AsyncAuthority, Identity, Manifest, Wire, loopback};
+use interconnect_server::{Server, ServerOptions};
+use std::time::Duration;
+
+/// How long a simulated client waits for a message by default.
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Serves one authority to simulated clients.
+pub struct Harness<A> {
+    server: Server<A>,
+    timeout: Duration,
+}
+
+impl<A> Harness<A>
+where
+    A: AsyncAuthority + 'static,
+    A::Intent: Wire,
+    A::Snapshot: Wire,
+    A::Passport: Wire,
+{
+    /// Serve `authority` with default options.
+    pub fn new(authority: A) -> Self {
+        Self::with_options(authority, ServerOptions::default())
+    }
+
+    /// Serve `authority` with `options` (signer, passport verifier, ...).
+    pub fn with_options(authority: A, options: ServerOptions) -> Self {
+        let manifest = Manifest::new(Identity::local("harness"), "harness");
+        Self::from_server(Server::new(authority, manifest, options))
+    }
+
+    /// Connect clients to an existing server.
+    pub fn from_server(server: Server<A>) -> Self {
+        Self {
+            server,
+            timeout: DEFAULT_TIMEOUT,
+        }
+    }
+
+    /// Give up waiting for messages after `timeout`.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// The server, for `update`, `notify` and `read`.
+    pub fn server(&self) -> &Server<A> {
+        &self.server
+    }
+
+    /// Connect a new client as `local:<name>`.
+    pub async fn connect(
+        &self,
+        name: &str,
+    ) -> Result<TestClient<A::Intent, A::Snapshot>, HarnessError> {
+        self.join(name, None).await
+    }
+
+    /// Connect `local:<name>` carrying a passport from
+    /// [`TestClient::transfer`].
+    pub async fn arrive(
+        &self,
+        name: &str,
+        passport: Vec<u8>,
+    ) -> Result<TestClient<A::Intent, A::Snapshot>, HarnessError> {
+        self.join(name, Some(passport)).await
+    }
+
+    async fn join(
+        &self,
+        name: &str,
+        passport: Option<Vec<u8>>,
+    ) -> Result<TestClient<A::Intent, A::Snapshot>, HarnessError> {
+        let (client, end) = loopback();
+        let server = self.server.clone();
+        tokio::spawn(async move { server.serve(end).await });
+
+        let connect = Connection::connect(
+            client,
+            Identity::local(name),
+            Some(name.to_string()),
+            passport,
+        );
+        let (conn, snapshot) = tokio::time::timeout(self.timeout, connect)
+            .await
+            .map_err(|_| HarnessError::Timeout(self.timeout))??;
+        Ok(TestClient::new(conn, snapshot, self.timeout))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use interconnect_client::ClientError;
+    use interconnect_core::{
+        ImportResult, IntentOutcome, Keypair, PassportVerifier, ServerWire, Session,
+        SimpleAuthority,
+    };
+
+    #[derive(serde::Serialize, serde::Deserialize)]
+    struct Add {
+        add: u64,
+    }
+
+    /// A counter whose passports carry the count into the next room.
+    #[derive(Default)]
+    struct Counter {
+        count: u64,
+        /// Sessions between `on_connect` and `on_disconnect`.
+        present: u64,
+    }
+
+    #[derive(Debug, thiserror::Error)]
+    #[error("too big")]
+    struct TooBig;
+
+    impl SimpleAuthority for Counter {
+        type Intent = Add;
+        type Snapshot = u64;
+        type Passport = u64;
+        type Error = TooBig;
+
+        fn on_connect(&mut self, _session: &Session) -> Result<(), TooBig> {
+            self.present += 1;
+            Ok(())
+        }
+
+        fn on_transfer_in(
+            &mut self,
+            _session: &Session,
+            carried: u64,
+        ) -> Result<ImportResult<u64>, TooBig> {
+            self.count += carried;
+            Ok(ImportResult::accept(carried))
+        }
+
+        fn on_disconnect(&mut self, _session: &Session) {
+            self.present -= 1;
+        }
+
+        fn handle_intent(&mut self, _session: &Session, Add { add }: Add) -> Result<(), TooBig> {
+            if add > 10 {
+                return Err(TooBig);
+            }
+            self.count += add;
+            Ok(())
+        }
+
+        fn snapshot(&self) -> u64 {
+            self.count
+        }
+
+        fn emit_passport(&self, _session: &Session) -> u64 {
+            self.count
+        }
+
+        fn validate_destination(&self, destination: &str) -> bool {
+            destination.starts_with("mem://")
+        }
+    }
+
+    #[tokio::test]
+    async fn sessions_see_each_others_intents() {
+        let room = Harness::new(Counter::default());
+        let mut alice = room.connect("alice").await.unwrap();
+        let mut bob = room.connect("bob").await.unwrap();
+
+        alice.send(Add { add: 2 }).await.unwrap();
+        assert_eq!(*bob.wait_for(|&n| n == 2).await.unwrap(), 2);
+        assert_eq!(
+            bob.request(Add { add: 3 }).await.unwrap(),
+            IntentOutcome::Accepted
+        );
+        assert_eq!(*alice.wait_for(|&n| n == 5).await.unwrap(), 5);
+        assert!(!bob.request(Add { add: 30 }).await.unwrap().is_accepted());
+    }
+
+    #[tokio::test]
+    async fn transfer_between_rooms() {
+        let key = Keypair::from_seed([7; 32]);
+        let a = Harness::with_options(
+            Counter::default(),
+            ServerOptions::default().signer(key.clone()),
+        );
+        let verifier = PassportVerifier::new("mem://b").trust(key.identity());
+        let b = Harness::with_options(
+            Counter::default(),
+            ServerOptions::default().passports(verifier),
+        );
+
+        let mut alice = a.connect("alice").await.unwrap();
+        alice.request(Add { add: 4 }).await.unwrap();
+        let passport = alice.transfer("mem://b").await.unwrap();
+        let alice = b.arrive("alice", passport).await.unwrap();
+        assert_eq!(*alice.snapshot(), 4);
+
+        // Passports are only issued for destinations the room accepts.
+        let mut bob = a.connect("bob").await.unwrap();
+        assert!(matches!(
+            bob.transfer("ws://elsewhere").await,
+            Err(HarnessError::Client(interconnect_client::ClientError::Server { code, .. }))
+                if code == "invalid_destination"
+        ));
+    }
+
+    #[tokio::test]
+    async fn waiting_times_out() {
+        let room = Harness::new(Counter::default()).timeout(Duration::from_millis(20));
+        let mut alice = room.connect("alice").await.unwrap();
+        assert!(matches!(
+            alice.next_snapshot().await,
+            Err(HarnessError::Timeout(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn connections_follow_transfers() {
+        let key = Keypair::from_seed([7; 32]);
+        let a = Harness::with_options(
+            Counter::default(),
+            ServerOptions::default().signer(key.clone()),
+        );
+        let verifier = PassportVerifier::new("mem://b").trust(key.identity());
+        let b = Harness::with_options(
+            Counter::default(),
+            ServerOptions::default().passports(verifier),
+        );
+        // `mem://b` trusts `a`; `mem://c` trusts nobody.
+        let rooms = [
+            ("mem://b", b.server().clone()),
+            ("mem://c", Harness::new(Counter::default()).server().clone()),
+        ];
+
+        let mut alice = a.connect("alice").await.unwrap();
+        alice.request(Add { add: 4 }).await.unwrap();
+        let conn = alice.connection();
+        conn.follow_transfers(move |destination| {
+            let server = rooms
+                .iter()
+                .find(|(name, _)| *name == destination)
+                .map(|(_, server)| server.clone());
+            async move {
+                let server = server.ok_or(ClientError::Closed)?;
+                let (client, end) = loopback();
+                tokio::spawn(async move { server.serve(end).await });
+                Ok(client)
+            }
+        });
+
+        // Refused: alice stays in the first room.
+        conn.request_transfer("mem://c".into()).await.unwrap();
+        loop {
+            match conn.recv().await.unwrap() {
+                Some(ServerWire::Snapshot { .. }) => continue,
+                Some(ServerWire::Error { code, .. }) if code == "transfer_refused" => break,
+                other => panic!("expected a refusal, got {other:?}"),
+            }
+        }
+        assert!(alice.request(Add { add: 1 }).await.unwrap().is_accepted());
+        alice.wait_for(|&n| n == 5).await.unwrap();
+
+        let conn = alice.connection();
+        conn.request_transfer("mem://b".into()).await.unwrap();
+        loop {
+            match conn.recv().await.unwrap() {
+                Some(ServerWire::Snapshot { .. }) => continue,
+                Some(ServerWire::Manifest(_)) => break,
+                other => panic!("expected the destination's manifest, got {other:?}"),
+            }
+        }
+        assert!(matches!(
+            conn.recv().await.unwrap(),
+            Some(ServerWire::Snapshot { data: 5, .. })
+        ));
+        assert_eq!(b.server().read(|room| room.count).await, 5);
+    }
+
+    #[tokio::test]
+    async fn connections_resume_sessions() {
+        let room = Harness::with_options(
+            Counter::default(),
+            ServerOptions::default().resume_grace(Duration::from_secs(60)),
+        );
+        let mut alice = room.connect("alice").await.unwrap();
+        alice.request(Add { add: 4 }).await.unwrap();
+        alice.wait_for(|&n| n == 4).await.unwrap();
+
+        // The old transport is still open; the resume takes the session over.
+        let (client, end) = loopback();
+        let server = room.server().clone();
+        tokio::spawn(async move { server.serve(end).await });
+        let conn = alice.connection();
+        assert!(conn.resume(client).await.unwrap());
+        assert!(matches!(
+            conn.recv().await.unwrap(),
+            Some(ServerWire::Snapshot { data: 4, .. })
+        ));
+        assert!(alice.request(Add { add: 1 }).await.unwrap().is_accepted());
+        alice.wait_for(|&n| n == 5).await.unwrap();
+        assert_eq!(room.server().read(|room| room.present).await, 1);
+    }
+}
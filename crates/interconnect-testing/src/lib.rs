@@ -0,0 +1,47 @@
+//! In-process test harness for Interconnect authorities.
+//!
+//! Runs an authority on the real [`Server`](interconnect_server::Server)
+//! runtime and connects simulated clients to it over in-memory
+//! [`Loopback`](interconnect_core::Loopback) transports, so rooms can be
+//! unit-tested without networking. Clients go through the real handshake,
+//! deltas and all.
+//!
+//! # Quick Start
+//!
+//! ```ignore
+//! use interconnect_testing::Harness;
+//!
+//! #[tokio::test]
+//! async fn chat_fans_out() {
+//!     let room = Harness::new(ChatRoom::new());
+//!     let mut alice = room.connect("alice").await.unwrap();
+//!     let mut bob = room.connect("bob").await.unwrap();
+//!
+//!     alice.send(ChatIntent::Say { text: "hi".into() }).await.unwrap();
+//!     bob.wait_for(|s| s.messages.len() == 1).await.unwrap();
+//! }
+//! ```
+//!
+//! # Transfers
+//!
+//! Give the origin a signer and the destination a verifier that trusts it,
+//! then carry the passport across:
+//!
+//! ```ignore
+//! let key = Keypair::from_seed([1; 32]);
+//! let a = Harness::with_options(RoomA::new(), ServerOptions::default().signer(key.clone()));
+//! let verifier = PassportVerifier::new("mem://b").trust(key.identity());
+//! let b = Harness::with_options(RoomB::new(), ServerOptions::default().passports(verifier));
+//!
+//! let mut alice = a.connect("alice").await?;
+//! let passport = alice.transfer("mem://b").await?;
+//! let alice = b.arrive("alice", passport).await?;
+//! ```
+
+mod client;
+mod error;
+mod harness;
+
+pub use client::TestClient;
+pub use error::HarnessError;
+pub use harness::{DEFAULT_TIMEOUT, Harness};
@@ -8,10 +8,15 @@
 //! Run two servers:
 //!   cargo run --example chat -- --port 8001 --name "Server A" --peer ws://localhost:8002
 //!   cargo run --example chat -- --port 8002 --name "Server B" --peer ws://localhost:8001
+//!
+//! Passports are signed by the origin server. To have them honoured, give each
+//! server a persistent key and the other's identity (printed at startup):
+//!   --key a.key --peer-identity ed25519:...
 
 mod protocol;
 mod server;
 
+use interconnect_core::{Identity, Keypair};
 use std::net::SocketAddr;
 use tracing_subscriber::EnvFilter;
 
@@ -25,6 +30,14 @@ async fn main() -> anyhow::Result<()> {
     let port = parse_arg(&args, "--port").unwrap_or(8001);
     let name = parse_arg_string(&args, "--name").unwrap_or_else(|| format!("Server:{port}"));
     let peer = parse_arg_string(&args, "--peer");
+    let url = parse_arg_string(&args, "--url").unwrap_or_else(|| format!("ws://localhost:{port}"));
+    let peer_identity = parse_arg_string(&args, "--peer-identity")
+        .map(|s| s.parse::<Identity>())
+        .transpose()?;
+    let keypair = match parse_arg_string(&args, "--key") {
+        Some(path) => Keypair::load_or_generate(path)?,
+        None => Keypair::generate()?,
+    };
 
     let addr: SocketAddr = ([127, 0, 0, 1], port).into();
 
@@ -33,7 +46,15 @@ async fn main() -> anyhow::Result<()> {
         tracing::info!("Peer server: {}", p);
     }
 
-    server::run(addr, name, peer).await
+    server::run(server::Config {
+        addr,
+        name,
+        url,
+        peer,
+        peer_identity,
+        keypair,
+    })
+    .await
 }
 
 fn parse_arg(args: &[String], flag: &str) -> Option<u16> {
@@ -9,13 +9,19 @@ use axum::{
     routing::{get, post},
     Json, Router,
 };
-use interconnect_core::{Identity, Manifest};
+use interconnect_core::{Identity, ImportError, ImportPolicy, Manifest};
 use serde::Deserialize;
 use std::collections::HashMap;
 use std::sync::Arc;
 use std::time::{SystemTime, UNIX_EPOCH};
 use tokio::sync::RwLock;
 
+/// Reputation from other forums is honoured, within limits.
+const IMPORT_POLICY: &str = r#"
+[field_limits]
+reputation = { min = -100, max = 100 }
+"#;
+
 struct StoredThread {
     thread: Thread,
     body: String,
@@ -28,6 +34,7 @@ struct ServerState {
     port: u16,
     threads: Vec<StoredThread>,
     users: HashMap<Identity, ForumProfile>,
+    import_policy: ImportPolicy,
     next_thread_id: u64,
     next_reply_id: u64,
 }
@@ -39,17 +46,26 @@ impl ServerState {
             port,
             threads: Vec::new(),
             users: HashMap::new(),
+            import_policy: ImportPolicy::from_toml(IMPORT_POLICY)
+                .expect("built-in import policy is valid"),
             next_thread_id: 1,
             next_reply_id: 1,
         }
     }
 
-    fn apply_import_policy(&self, passport: &ForumPassport) -> ForumImportResult {
-        // Simple policy: accept reputation but cap it, require minimum rep to post
-        let reputation = passport.reputation.clamp(-100, 100);
+    fn apply_import_policy(
+        &self,
+        passport: &ForumPassport,
+    ) -> Result<ForumImportResult, ImportError> {
+        // Cap reputation per policy, then require minimum rep to post
+        let imported = self.import_policy.apply_to(passport)?;
+        for rejection in &imported.rejected {
+            tracing::info!("Import: {} {}", rejection.item, rejection.reason);
+        }
+        let reputation = imported.passport.reputation;
         let can_post = reputation >= 0 || passport.post_count > 10;
 
-        ForumImportResult {
+        Ok(ForumImportResult {
             reputation,
             can_post,
             restriction_reason: if can_post {
@@ -57,7 +73,7 @@ impl ServerState {
             } else {
                 Some("New users with negative reputation must wait for approval".to_string())
             },
-        }
+        })
     }
 
     fn get_or_create_user(&mut self, identity: &Identity, name: &str) -> &mut ForumProfile {
@@ -105,16 +121,13 @@ fn now() -> u64 {
 
 async fn get_manifest(State(state): State<AppState>) -> Json<Manifest> {
     let s = state.read().await;
-    Json(Manifest {
-        identity: Identity::local(&s.name),
-        name: s.name.clone(),
-        substrate: None,
-        metadata: serde_json::json!({
+    Json(
+        Manifest::new(Identity::local(&s.name), s.name.clone()).metadata(serde_json::json!({
             "type": "forum",
             "version": "0.1",
             "thread_count": s.threads.len()
-        }),
-    })
+        })),
+    )
 }
 
 #[derive(Deserialize)]
@@ -300,9 +313,11 @@ async fn get_profile(
 async fn import_user(
     State(state): State<AppState>,
     Json(passport): Json<ForumPassport>,
-) -> Json<ForumImportResult> {
+) -> Result<Json<ForumImportResult>, StatusCode> {
     let s = state.read().await;
-    let result = s.apply_import_policy(&passport);
+    let result = s
+        .apply_import_policy(&passport)
+        .map_err(|_| StatusCode::BAD_REQUEST)?;
     tracing::info!(
         "Import request from {}: reputation {} -> {}, can_post: {}",
         passport.display_name,
@@ -310,5 +325,5 @@ async fn import_user(
         result.reputation,
         result.can_post
     );
-    Json(result)
+    Ok(Json(result))
 }
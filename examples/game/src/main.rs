@@ -11,11 +11,16 @@
 //!   cargo run -p interconnect-example-game -- --port 8002 --name "Cave" --peer ws://localhost:8001
 //!
 //! The Cave zone has a stricter import policy (no weapons allowed).
+//!
+//! Passports are signed by the origin zone. To have them honoured, give each
+//! zone a persistent key and the other's identity (printed at startup):
+//!   --key forest.key --peer-identity ed25519:...
 
 mod protocol;
 mod server;
 mod world;
 
+use interconnect_core::{Identity, Keypair};
 use tracing_subscriber::EnvFilter;
 
 #[tokio::main]
@@ -28,10 +33,26 @@ async fn main() -> anyhow::Result<()> {
     let port = parse_arg(&args, "--port").unwrap_or(8001);
     let name = parse_arg_string(&args, "--name").unwrap_or_else(|| "Zone".to_string());
     let peer = parse_arg_string(&args, "--peer");
+    let url = parse_arg_string(&args, "--url").unwrap_or_else(|| format!("ws://localhost:{port}"));
+    let peer_identity = parse_arg_string(&args, "--peer-identity")
+        .map(|s| s.parse::<Identity>())
+        .transpose()?;
+    let keypair = match parse_arg_string(&args, "--key") {
+        Some(path) => Keypair::load_or_generate(path)?,
+        None => Keypair::generate()?,
+    };
 
     tracing::info!("Starting zone '{}' on port {}", name, port);
 
-    server::run(port, name, peer).await
+    server::run(server::Config {
+        port,
+        name,
+        url,
+        peer,
+        peer_identity,
+        keypair,
+    })
+    .await
 }
 
 fn parse_arg(args: &[String], flag: &str) -> Option<u16> {
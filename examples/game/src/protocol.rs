@@ -4,8 +4,10 @@ use interconnect_core::Identity;
 use serde::{Deserialize, Serialize};
 
 /// Player intent (what the client wants to do).
+///
+/// Zone transfer is the wire protocol's `TransferRequest`, not an intent.
 #[derive(Debug, Clone, Serialize, Deserialize)]
-#[serde(tag = "type", rename_all = "snake_case")]
+#[serde(tag = "action", rename_all = "snake_case")]
 pub enum GameIntent {
     /// Move in a direction.
     Move { dx: f32, dy: f32 },
@@ -15,8 +17,6 @@ pub enum GameIntent {
     PickUp { item_id: u64 },
     /// Drop an item.
     Drop { slot: usize },
-    /// Request transfer to another zone.
-    Transfer { destination: String },
 }
 
 /// World snapshot (authoritative state).
@@ -61,6 +61,15 @@ pub enum ItemKind {
 }
 
 impl ItemKind {
+    pub const ALL: [ItemKind; 6] = [
+        ItemKind::Sword,
+        ItemKind::Shield,
+        ItemKind::Potion,
+        ItemKind::Key,
+        ItemKind::Gem,
+        ItemKind::Torch,
+    ];
+
     pub fn is_weapon(&self) -> bool {
         matches!(self, ItemKind::Sword)
     }
@@ -83,21 +92,3 @@ pub struct GamePassport {
     pub inventory: Vec<InventoryItem>,
     pub origin_zone: String,
 }
-
-impl GamePassport {
-    pub fn to_bytes(&self) -> Vec<u8> {
-        serde_json::to_vec(self).unwrap()
-    }
-
-    pub fn from_bytes(data: &[u8]) -> Option<Self> {
-        serde_json::from_slice(data).ok()
-    }
-}
-
-/// Import policy result.
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct ImportResult {
-    pub accepted_items: Vec<InventoryItem>,
-    pub rejected_items: Vec<(InventoryItem, String)>,
-    pub health: u32,
-}
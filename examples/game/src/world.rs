@@ -1,11 +1,23 @@
 //! World state and simulation.
 
 use crate::protocol::{
-    GamePassport, GameSnapshot, ImportResult, InventoryItem, ItemKind, PlayerState, WorldItem,
+    GameIntent, GamePassport, GameSnapshot, InventoryItem, ItemKind, PlayerState, WorldItem,
+};
+use interconnect_core::{
+    Identity, ImportError, ImportPolicy, ImportResult, Session, SimpleAuthority,
 };
-use interconnect_core::Identity;
 use std::collections::HashMap;
 
+/// Import policy shared by every zone.
+const IMPORT_POLICY: &str = r#"
+[paths]
+items = "inventory"
+item_id = "kind"
+
+[field_limits]
+health = { min = 1, max = 100 }
+"#;
+
 /// A player in the world.
 pub struct Player {
     pub identity: Identity,
@@ -30,15 +42,16 @@ impl Player {
         }
     }
 
-    pub fn from_passport(passport: GamePassport, import: ImportResult) -> Self {
+    /// Build a player from a passport that has been through import policy.
+    pub fn from_passport(passport: GamePassport) -> Self {
         Self {
             identity: passport.identity,
             name: passport.name,
             x: 0.0, // Spawn at origin
             y: 0.0,
-            health: import.health,
+            health: passport.health,
             max_health: passport.max_health,
-            inventory: import.accepted_items,
+            inventory: passport.inventory,
         }
     }
 
@@ -77,20 +90,40 @@ pub struct World {
     pub players: HashMap<Identity, Player>,
     pub items: Vec<WorldItem>,
     pub allow_weapons: bool,
+    /// The one zone players may transfer to.
+    peer: Option<String>,
+    import_policy: ImportPolicy,
     next_item_id: u64,
 }
 
 impl World {
-    pub fn new(name: String) -> Self {
+    pub fn new(name: String, peer: Option<String>) -> Self {
         // "Cave" zones don't allow weapons
         let allow_weapons = !name.to_lowercase().contains("cave");
 
+        let mut import_policy =
+            ImportPolicy::from_toml(IMPORT_POLICY).expect("built-in import policy is valid");
+        if !allow_weapons {
+            // Ban by wire name, which is what the policy sees in passports
+            let weapons = ItemKind::ALL.into_iter().filter(ItemKind::is_weapon);
+            import_policy
+                .banned_items
+                .extend(weapons.filter_map(|kind| {
+                    serde_json::to_value(kind)
+                        .ok()?
+                        .as_str()
+                        .map(str::to_string)
+                }));
+        }
+
         let mut world = Self {
             name,
             tick: 0,
             players: HashMap::new(),
             items: Vec::new(),
             allow_weapons,
+            peer,
+            import_policy,
             next_item_id: 1,
         };
 
@@ -114,26 +147,11 @@ impl World {
     }
 
     /// Apply import policy to incoming passport.
-    pub fn apply_import_policy(&self, passport: &GamePassport) -> ImportResult {
-        let mut accepted = Vec::new();
-        let mut rejected = Vec::new();
-
-        for item in &passport.inventory {
-            if !self.allow_weapons && item.kind.is_weapon() {
-                rejected.push((item.clone(), "Weapons not allowed in this zone".to_string()));
-            } else {
-                accepted.push(item.clone());
-            }
-        }
-
-        // Clamp health to reasonable bounds
-        let health = passport.health.clamp(1, 100);
-
-        ImportResult {
-            accepted_items: accepted,
-            rejected_items: rejected,
-            health,
-        }
+    pub fn apply_import_policy(
+        &self,
+        passport: &GamePassport,
+    ) -> Result<ImportResult<GamePassport>, ImportError> {
+        self.import_policy.apply_to(passport)
     }
 
     pub fn add_player(&mut self, player: Player) {
@@ -148,8 +166,108 @@ impl World {
         self.tick += 1;
         // Could add physics, AI, etc. here
     }
+}
+
+impl SimpleAuthority for World {
+    type Intent = GameIntent;
+    type Snapshot = GameSnapshot;
+    type Passport = GamePassport;
+    type Error = ImportError;
+
+    fn on_connect(&mut self, session: &Session) -> Result<(), ImportError> {
+        self.add_player(Player::new(session.identity.clone(), session.name.clone()));
+        tracing::info!("{} joined", session.name);
+        Ok(())
+    }
+
+    fn on_transfer_in(
+        &mut self,
+        _session: &Session,
+        passport: GamePassport,
+    ) -> Result<ImportResult<GamePassport>, ImportError> {
+        let result = self.apply_import_policy(&passport)?;
+        tracing::info!(
+            "{} arrived from {}, {} items accepted, {} rejected",
+            result.passport.name,
+            result.passport.origin_zone,
+            result.passport.inventory.len(),
+            result.rejected.len()
+        );
+        self.add_player(Player::from_passport(result.passport.clone()));
+        Ok(result)
+    }
+
+    fn on_disconnect(&mut self, session: &Session) {
+        if let Some(player) = self.remove_player(&session.identity) {
+            tracing::info!("{} disconnected", player.name);
+        }
+    }
+
+    fn handle_intent(&mut self, session: &Session, intent: GameIntent) -> Result<(), ImportError> {
+        let identity = &session.identity;
+        match intent {
+            GameIntent::Move { dx, dy } => {
+                if let Some(player) = self.players.get_mut(identity) {
+                    player.move_by(dx, dy);
+                }
+            }
+
+            GameIntent::PickUp { item_id } => {
+                // Get player position first
+                let player_pos = self
+                    .players
+                    .get(identity)
+                    .map(|p| (p.x, p.y, p.name.clone()));
+                if let Some((px, py, name)) = player_pos
+                    && let Some(idx) = self.items.iter().position(|i| i.id == item_id)
+                {
+                    let item = &self.items[idx];
+                    let dist = ((px - item.x).powi(2) + (py - item.y).powi(2)).sqrt();
+                    if dist < 2.0 {
+                        let item = self.items.remove(idx);
+                        if let Some(player) = self.players.get_mut(identity) {
+                            player.inventory.push(InventoryItem {
+                                kind: item.kind,
+                                count: 1,
+                            });
+                        }
+                        tracing::info!("{} picked up {:?}", name, item.kind);
+                    }
+                }
+            }
+
+            GameIntent::UseItem { slot } => {
+                if let Some(player) = self.players.get_mut(identity)
+                    && let Some(item) = player.inventory.get(slot)
+                    && item.kind == ItemKind::Potion
+                {
+                    player.health = (player.health + 25).min(player.max_health);
+                    player.inventory.remove(slot);
+                    tracing::info!("{} used a potion", player.name);
+                }
+            }
+
+            GameIntent::Drop { slot } => {
+                // Extract what we need from player first
+                let drop_info = self.players.get_mut(identity).and_then(|player| {
+                    if slot < player.inventory.len() {
+                        let item = player.inventory.remove(slot);
+                        Some((item.kind, player.x, player.y))
+                    } else {
+                        None
+                    }
+                });
+                // Then add to world
+                if let Some((kind, x, y)) = drop_info {
+                    let id = self.tick;
+                    self.items.push(WorldItem { id, kind, x, y });
+                }
+            }
+        }
+        Ok(())
+    }
 
-    pub fn snapshot(&self) -> GameSnapshot {
+    fn snapshot(&self) -> GameSnapshot {
         GameSnapshot {
             tick: self.tick,
             players: self.players.values().map(|p| p.to_state()).collect(),
@@ -157,4 +275,53 @@ impl World {
             zone_name: self.name.clone(),
         }
     }
+
+    fn emit_passport(&self, session: &Session) -> GamePassport {
+        match self.players.get(&session.identity) {
+            Some(player) => player.to_passport(self.name.clone()),
+            None => Player::new(session.identity.clone(), session.name.clone())
+                .to_passport(self.name.clone()),
+        }
+    }
+
+    fn validate_destination(&self, destination: &str) -> bool {
+        self.peer.as_deref() == Some(destination)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use interconnect_core::{Keypair, PassportVerifier};
+    use interconnect_server::ServerOptions;
+    use interconnect_testing::Harness;
+
+    #[tokio::test]
+    async fn weapons_stay_out_of_the_cave() {
+        let key = Keypair::from_seed([3; 32]);
+        let field = Harness::with_options(
+            World::new("field".into(), Some("mem://cave".into())),
+            ServerOptions::default().signer(key.clone()),
+        );
+        let verifier = PassportVerifier::new("mem://cave").trust(key.identity());
+        let cave = Harness::with_options(
+            World::new("cave".into(), None),
+            ServerOptions::default().passports(verifier),
+        );
+
+        // The sword lies at (-5, 3) and is item 2.
+        let mut alice = field.connect("alice").await.unwrap();
+        alice.send(GameIntent::Move { dx: -5.0, dy: 3.0 }).await.unwrap();
+        alice.send(GameIntent::PickUp { item_id: 2 }).await.unwrap();
+        alice
+            .wait_for(|s| s.players[0].equipped == Some(ItemKind::Sword))
+            .await
+            .unwrap();
+
+        let passport = alice.transfer("mem://cave").await.unwrap();
+        let alice = cave.arrive("alice", passport).await.unwrap();
+        let snapshot = alice.snapshot();
+        assert_eq!(snapshot.zone_name, "cave");
+        assert_eq!(snapshot.players[0].equipped, None);
+    }
 }
@@ -65,15 +65,16 @@ pub async fn run(port: u16, name: String) -> anyhow::Result<()> {
 /// GET /manifest - Interconnect manifest
 async fn get_manifest(State(state): State<AppState>) -> Json<Manifest> {
     let s = state.read().await;
-    Json(Manifest {
-        identity: s.identity.clone(),
-        name: format!("{}@localhost:{}", s.name, s.port),
-        substrate: None,
-        metadata: serde_json::json!({
+    Json(
+        Manifest::new(
+            s.identity.clone(),
+            format!("{}@localhost:{}", s.name, s.port),
+        )
+        .metadata(serde_json::json!({
             "type": "microblog",
             "version": "0.1"
-        }),
-    })
+        })),
+    )
 }
 
 /// GET /timeline - this server's posts
@@ -4,9 +4,10 @@
 //! input; stdout/stderr become snapshot lines.
 
 use crate::protocol::{ProcessIntent, ProcessPassport, ProcessSnapshot};
-use interconnect_core::{ImportResult, Session, SimpleAuthority};
+use interconnect_core::{AsyncAuthority, ImportResult, Session};
 use std::sync::{Arc, Mutex};
 use tokio::io::AsyncWriteExt;
+use tokio::process::ChildStdin;
 use tokio::sync::mpsc;
 
 const MAX_LINES: usize = 200;
@@ -21,8 +22,8 @@ struct ProcessState {
 pub struct ProcessAuthority {
     /// Display label for the command.
     command: String,
-    /// Channel to send text lines to the process's stdin.
-    stdin_tx: mpsc::UnboundedSender<String>,
+    /// The process's stdin, written directly from `handle_intent`.
+    stdin: ChildStdin,
     /// Shared output state, updated by background I/O tasks.
     state: Arc<Mutex<ProcessState>>,
 }
@@ -31,8 +32,8 @@ pub struct ProcessAuthority {
 pub enum ProcessError {
     #[error("process has exited")]
     NotRunning,
-    #[error("stdin send failed: {0}")]
-    SendFailed(String),
+    #[error("stdin write failed: {0}")]
+    SendFailed(#[from] std::io::Error),
 }
 
 impl ProcessAuthority {
@@ -62,22 +63,6 @@ impl ProcessAuthority {
             exit_code: None,
         }));
 
-        let (stdin_tx, mut stdin_rx) = mpsc::unbounded_channel::<String>();
-
-        // Forward stdin channel → process stdin
-        tokio::spawn(async move {
-            let mut stdin = child_stdin;
-            while let Some(line) = stdin_rx.recv().await {
-                if stdin.write_all(line.as_bytes()).await.is_err() {
-                    break;
-                }
-                if stdin.write_all(b"\n").await.is_err() {
-                    break;
-                }
-                let _ = stdin.flush().await;
-            }
-        });
-
         // Read stdout → shared state
         {
             let state = state.clone();
@@ -138,22 +123,26 @@ impl ProcessAuthority {
             format!("{command} {}", args.join(" "))
         };
 
-        Ok(Self { command: label, stdin_tx, state })
+        Ok(Self {
+            command: label,
+            stdin: child_stdin,
+            state,
+        })
     }
 }
 
-impl SimpleAuthority for ProcessAuthority {
+impl AsyncAuthority for ProcessAuthority {
     type Intent = ProcessIntent;
     type Snapshot = ProcessSnapshot;
     type Passport = ProcessPassport;
     type Error = ProcessError;
 
-    fn on_connect(&mut self, session: &Session) -> Result<(), Self::Error> {
+    async fn on_connect(&mut self, session: &Session) -> Result<(), Self::Error> {
         tracing::info!("{} connected", session.name);
         Ok(())
     }
 
-    fn on_transfer_in(
+    async fn on_transfer_in(
         &mut self,
         _session: &Session,
         passport: Self::Passport,
@@ -162,11 +151,11 @@ impl SimpleAuthority for ProcessAuthority {
         Ok(ImportResult::accept(passport))
     }
 
-    fn on_disconnect(&mut self, session: &Session) {
+    async fn on_disconnect(&mut self, session: &Session) {
         tracing::info!("{} disconnected", session.name);
     }
 
-    fn handle_intent(
+    async fn handle_intent(
         &mut self,
         session: &Session,
         intent: Self::Intent,
@@ -180,9 +169,10 @@ impl SimpleAuthority for ProcessAuthority {
 
         match intent {
             ProcessIntent::SendInput { text } => {
-                self.stdin_tx
-                    .send(text)
-                    .map_err(|e| ProcessError::SendFailed(e.to_string()))?;
+                // A failed write surfaces to the sender as an intent error.
+                self.stdin.write_all(text.as_bytes()).await?;
+                self.stdin.write_all(b"\n").await?;
+                self.stdin.flush().await?;
             }
             ProcessIntent::SendSignal { signal } => {
                 // TODO: implement via nix crate; log for now
@@ -196,7 +186,7 @@ impl SimpleAuthority for ProcessAuthority {
         Ok(())
     }
 
-    fn snapshot(&self) -> Self::Snapshot {
+    async fn snapshot_for(&self, _session: &Session) -> Self::Snapshot {
         let s = self.state.lock().unwrap();
         ProcessSnapshot {
             lines: s.lines.clone(),
@@ -206,14 +196,14 @@ impl SimpleAuthority for ProcessAuthority {
         }
     }
 
-    fn emit_passport(&self, session: &Session) -> Self::Passport {
+    async fn emit_passport(&self, session: &Session) -> Self::Passport {
         ProcessPassport {
             name: session.name.clone(),
             origin: self.command.clone(),
         }
     }
 
-    fn validate_destination(&self, _destination: &str) -> bool {
+    async fn validate_destination(&self, _destination: &str) -> bool {
         false
     }
 }
@@ -5,11 +5,29 @@
 //!
 //! Usage:
 //!   cargo run --bin process-client -- ws://localhost:8080 --name alice
+//!   cargo run --bin process-client -- ws://localhost:8080 --codec msgpack
+//!   cargo run --bin process-client -- ws://localhost:8080 --key ~/.interconnect/id.key
+//!   cargo run --bin process-client -- ws://localhost:8080 --cache ~/.interconnect/substrates
+//!   cargo run --bin process-client -- ws://localhost:8080 --schema
+//!   cargo run --bin process-client -- unix:/tmp/room.sock
+//!   cargo run --bin process-client -- http://localhost:8080 --long-poll
+//!
+//! With `--key`, the client uses (or creates) an ed25519 keypair and answers
+//! the server's identity challenge with it. With `--cache`, the room's
+//! substrate is kept in that directory and only fetched on first visit. With
+//! `--schema`, the client prints the room's intent schema and exits. An
+//! `http://` URL uses the HTTP transport, receiving by SSE or, with
+//! `--long-poll`, by long-poll.
 
 mod protocol;
 
-use interconnect_client::WsConnection;
-use interconnect_core::{Identity, ServerWire};
+use interconnect_client::{
+    ClientError, ConnectOptions, Connection, HttpMode, HttpTransport, WsTransport,
+};
+use interconnect_core::{
+    Codec, ConnectionState, Identity, IntentOutcome, Keypair, ServerWire, SubstrateStore,
+    Transport, UnixTransport,
+};
 use protocol::{ProcessIntent, ProcessSnapshot};
 use tokio::io::{AsyncBufReadExt, BufReader};
 use tracing_subscriber::EnvFilter;
@@ -24,12 +42,72 @@ async fn main() -> anyhow::Result<()> {
 
     let url = args.get(1).map(|s| s.as_str()).unwrap_or("ws://localhost:8080");
     let name = parse_flag_str(&args, "--name").unwrap_or_else(|| "client".to_string());
+    let codec: Codec = match parse_flag_str(&args, "--codec") {
+        Some(c) => c.parse()?,
+        None => Codec::Json,
+    };
+
+    let mut options = ConnectOptions::default().codec(codec);
+    let identity = match parse_flag_str(&args, "--key") {
+        Some(path) => {
+            let keypair = Keypair::load_or_generate(path)?;
+            let identity = keypair.identity();
+            options = options.signer(keypair);
+            identity
+        }
+        None => Identity::local(&name),
+    };
+    if let Some(dir) = parse_flag_str(&args, "--cache") {
+        options = options
+            .substrate_store(SubstrateStore::open(dir)?)
+            .on_state(|state| {
+                if state == ConnectionState::LoadingSubstrate {
+                    eprintln!("Loading substrate...");
+                }
+            });
+    }
 
-    eprintln!("Connecting to {} as {}...", url, name);
+    eprintln!("Connecting to {} as {} ({identity}, {codec})...", url, name);
+
+    let show_schema = args.iter().any(|a| a == "--schema");
+    if let Some(path) = url.strip_prefix("unix:") {
+        let transport = UnixTransport::connect(path).await?;
+        run(transport, identity, name, options, show_schema).await
+    } else if url.starts_with("http://") || url.starts_with("https://") {
+        let mode = if args.iter().any(|a| a == "--long-poll") {
+            HttpMode::LongPoll
+        } else {
+            HttpMode::Sse
+        };
+        let transport = HttpTransport::connect_with(url, mode).await?;
+        run(transport, identity, name, options, show_schema).await
+    } else {
+        let transport = WsTransport::connect(url).await?;
+        run(transport, identity, name, options, show_schema).await
+    }
+}
 
-    let transport = interconnect_client::WsTransport::connect(url).await?;
-    let (mut conn, snapshot): (WsConnection<ProcessIntent, ProcessSnapshot>, ProcessSnapshot) =
-        WsConnection::connect(transport, Identity::local(&name), Some(name), None).await?;
+async fn run<T>(
+    transport: T,
+    identity: Identity,
+    name: String,
+    options: ConnectOptions,
+    show_schema: bool,
+) -> anyhow::Result<()>
+where
+    T: Transport,
+    T::Error: Into<ClientError>,
+{
+    let (mut conn, snapshot): (Connection<T, ProcessIntent, ProcessSnapshot>, ProcessSnapshot) =
+        Connection::connect_with(transport, identity, Some(name), None, options).await?;
+
+    if show_schema {
+        match &conn.manifest().schema {
+            Some(schema) => println!("{}", serde_json::to_string_pretty(&schema.intent)?),
+            None => eprintln!("The room publishes no schema."),
+        }
+        return Ok(());
+    }
 
     eprintln!(
         "Connected to '{}' — command: {}",
@@ -58,7 +136,12 @@ async fn main() -> anyhow::Result<()> {
                 match line? {
                     None => break, // EOF
                     Some(text) => {
-                        conn.send_intent(ProcessIntent::SendInput { text }).await?;
+                        // Wait for the verdict so a dead process is reported
+                        // against the line that hit it.
+                        let outcome = conn.request(ProcessIntent::SendInput { text }).await?;
+                        if let IntentOutcome::Rejected { message, .. } = outcome {
+                            eprintln!("[rejected]: {message}");
+                        }
                     }
                 }
             }
@@ -67,7 +150,7 @@ async fn main() -> anyhow::Result<()> {
             msg = conn.recv() => {
                 match msg? {
                     None => {
-                        eprintln!("[disconnected]");
+                        eprintln!("[authority lost; room paused]");
                         break;
                     }
                     Some(ServerWire::Snapshot { data, .. }) => {
@@ -8,6 +8,12 @@
 //!   cargo run --example process -- --port 8080 --name "my-room" -- bash
 //!   cargo run --example process -- -- python3 -i
 //!   cargo run --example process -- -- cat
+//!   cargo run --example process -- --unix /tmp/room.sock -- bash
+//!   cargo run --example process -- --http -- bash
+//!
+//! With `--unix`, the room listens on a Unix socket (length-prefixed frames)
+//! instead of a WebSocket port. With `--http`, the port serves the HTTP
+//! endpoint (for clients behind proxies that break WebSockets).
 
 mod authority;
 mod protocol;
@@ -39,7 +45,7 @@ async fn main() -> anyhow::Result<()> {
         };
 
     if command_args.is_empty() {
-        anyhow::bail!("Usage: process [--port N] [--name NAME] -- <command> [args...]");
+        anyhow::bail!("Usage: process [--port N] [--http] [--unix PATH] [--name NAME] -- <command> [args...]");
     }
 
     let command = command_args[0].clone();
@@ -49,7 +55,12 @@ async fn main() -> anyhow::Result<()> {
     tracing::info!("Starting process room '{name}' on {addr}");
     tracing::info!("Command: {command} {}", cmd_args.join(" "));
 
-    server::run(addr, name, command, cmd_args).await
+    let listen = match parse_flag_str(&args, "--unix") {
+        Some(path) => server::Listen::Unix(path.into()),
+        None if args.iter().any(|a| a == "--http") => server::Listen::Http(addr),
+        None => server::Listen::Ws(addr),
+    };
+    server::run(listen, name, command, cmd_args).await
 }
 
 fn parse_flag_u16(args: &[String], flag: &str) -> Option<u16> {
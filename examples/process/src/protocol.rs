@@ -3,11 +3,12 @@
 //! A process room wraps a running subprocess. Clients send input to its stdin
 //! and receive its stdout/stderr as snapshots.
 
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
 /// Intent sent by a client to steer the process.
-#[derive(Debug, Clone, Serialize, Deserialize)]
-#[serde(tag = "type", rename_all = "snake_case")]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(tag = "action", rename_all = "snake_case")]
 pub enum ProcessIntent {
     /// Send a line of text to the process's stdin.
     SendInput { text: String },
@@ -16,7 +17,7 @@ pub enum ProcessIntent {
 }
 
 /// Signals a client can request.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub enum ProcessSignal {
     /// SIGINT (Ctrl-C).
@@ -26,7 +27,7 @@ pub enum ProcessSignal {
 }
 
 /// Snapshot of the process room state.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct ProcessSnapshot {
     /// Recent output lines (stdout + stderr interleaved, last 200).
     pub lines: Vec<String>,
@@ -1,198 +1,77 @@
-//! WebSocket server for a process room.
+//! Server for a process room, over WebSocket, HTTP or a Unix socket.
 
 use crate::authority::ProcessAuthority;
 use crate::protocol::{ProcessIntent, ProcessSnapshot};
-use futures_util::{SinkExt, StreamExt};
-use interconnect_core::{
-    from_json_str, to_json_string, ClientWire, Identity, Manifest, ServerWire, Session,
-    SimpleAuthority,
-};
+use interconnect_core::{Identity, Manifest, RoomSchema, Substrate};
+use interconnect_server::{Server, ServerOptions, WsListener, http_router};
 use std::net::SocketAddr;
-use std::sync::{
-    Arc,
-    atomic::{AtomicU64, Ordering},
-};
-use tokio::net::{TcpListener, TcpStream};
-use tokio::sync::{RwLock, broadcast, mpsc};
-use tokio_tungstenite::tungstenite::Message;
-
-struct ServerState {
-    authority: ProcessAuthority,
-    manifest: Manifest,
-    next_session_id: u64,
+use std::path::PathBuf;
+use tokio::sync::mpsc;
+
+/// Where the room accepts clients.
+pub enum Listen {
+    Ws(SocketAddr),
+    /// The HTTP endpoint (POST up; SSE or long-poll down).
+    Http(SocketAddr),
+    Unix(PathBuf),
 }
 
-type SharedState = Arc<RwLock<ServerState>>;
-
+/// Serve the room wherever `listen` says.
 pub async fn run(
-    addr: SocketAddr,
+    listen: Listen,
     name: String,
     command: String,
     args: Vec<String>,
 ) -> anyhow::Result<()> {
     let identity = Identity::local(&name);
-    let manifest = Manifest {
-        identity,
-        name: name.clone(),
-        substrate: None,
-        metadata: serde_json::json!({ "type": "process", "command": &command }),
-    };
+    // The room's static description. Clients cache it by hash.
+    let substrate = Substrate::new(serde_json::to_vec(&serde_json::json!({
+        "name": &name,
+        "command": &command,
+        "args": &args,
+    }))?);
+    let manifest = Manifest::new(identity, name.clone())
+        .schema(RoomSchema::of::<ProcessIntent, ProcessSnapshot>())
+        .metadata(serde_json::json!({ "type": "process", "command": &command }));
 
     let (update_tx, mut update_rx) = mpsc::unbounded_channel::<()>();
-    let (broadcast_tx, _) = broadcast::channel::<String>(256);
-
     let authority = ProcessAuthority::spawn(&command, &args, update_tx).await?;
     tracing::info!("Process started: {command}");
 
-    let state = Arc::new(RwLock::new(ServerState {
+    let server = Server::new(
         authority,
         manifest,
-        next_session_id: 1,
-    }));
-
-    let listener = TcpListener::bind(addr).await?;
-    tracing::info!("Listening on ws://{addr}");
+        ServerOptions::default().substrate(substrate),
+    );
 
     // Push a new snapshot to all clients whenever the process produces output.
-    let seq_counter = Arc::new(AtomicU64::new(1));
     {
-        let state = state.clone();
-        let broadcast_tx = broadcast_tx.clone();
-        let seq_counter = seq_counter.clone();
+        let server = server.clone();
         tokio::spawn(async move {
             while update_rx.recv().await.is_some() {
-                let s = state.read().await;
-                let snapshot = s.authority.snapshot();
-                let seq = seq_counter.fetch_add(1, Ordering::SeqCst);
-                if let Ok(msg) =
-                    to_json_string(&ServerWire::<ProcessSnapshot>::Snapshot { seq, data: snapshot })
-                {
-                    let _ = broadcast_tx.send(msg);
-                }
+                server.notify();
             }
         });
     }
 
-    loop {
-        let (stream, client_addr) = listener.accept().await?;
-        let state = state.clone();
-        let broadcast_tx = broadcast_tx.clone();
-
-        tokio::spawn(async move {
-            if let Err(e) = handle_connection(stream, client_addr, state, broadcast_tx).await {
-                tracing::warn!("Connection error from {client_addr}: {e}");
-            }
-        });
-    }
-}
-
-async fn handle_connection(
-    stream: TcpStream,
-    addr: SocketAddr,
-    state: SharedState,
-    broadcast_tx: broadcast::Sender<String>,
-) -> anyhow::Result<()> {
-    let ws = tokio_tungstenite::accept_async(stream).await?;
-    let (mut sink, mut stream) = ws.split();
-
-    tracing::debug!("Connection from {addr}");
-
-    // Auth handshake
-    let session = loop {
-        let msg = stream
-            .next()
-            .await
-            .ok_or_else(|| anyhow::anyhow!("Connection closed during auth"))??;
-
-        if let Message::Text(text) = msg {
-            let wire: ClientWire<ProcessIntent> = from_json_str(&text)?;
-            if let ClientWire::Auth { identity, name, .. } = wire {
-                let mut s = state.write().await;
-                let id = s.next_session_id;
-                s.next_session_id += 1;
-                let display_name = name.unwrap_or_else(|| identity.payload().to_string());
-                let session = Session::new(id, identity, display_name);
-                s.authority.on_connect(&session)?;
-                break session;
-            }
+    match listen {
+        Listen::Ws(addr) => {
+            let listener = WsListener::bind(addr).await?;
+            tracing::info!("Listening on ws://{addr}");
+            server.run(listener).await?;
         }
-    };
-
-    // Send manifest
-    {
-        let s = state.read().await;
-        let msg: ServerWire<ProcessSnapshot> = ServerWire::Manifest(s.manifest.clone());
-        sink.send(Message::Text(to_json_string(&msg)?.into())).await?;
-    }
-
-    // Send initial snapshot
-    {
-        let s = state.read().await;
-        let snapshot = s.authority.snapshot();
-        let msg: ServerWire<ProcessSnapshot> = ServerWire::Snapshot { seq: 0, data: snapshot };
-        sink.send(Message::Text(to_json_string(&msg)?.into())).await?;
-    }
-
-    let mut broadcast_rx = broadcast_tx.subscribe();
-
-    loop {
-        tokio::select! {
-            msg = stream.next() => {
-                let msg = match msg {
-                    Some(Ok(m)) => m,
-                    Some(Err(e)) => {
-                        tracing::debug!("WebSocket error: {e}");
-                        break;
-                    }
-                    None => break,
-                };
-
-                if let Message::Text(text) = msg {
-                    let wire: ClientWire<ProcessIntent> = match from_json_str(&text) {
-                        Ok(w) => w,
-                        Err(e) => {
-                            tracing::warn!("Invalid message: {e}");
-                            continue;
-                        }
-                    };
-
-                    match wire {
-                        ClientWire::Intent(intent) => {
-                            let mut s = state.write().await;
-                            if let Err(e) = s.authority.handle_intent(&session, intent) {
-                                let msg: ServerWire<ProcessSnapshot> =
-                                    ServerWire::error("intent_error", e.to_string());
-                                sink.send(Message::Text(to_json_string(&msg)?.into())).await?;
-                            }
-                        }
-                        ClientWire::Ping => {
-                            sink.send(Message::Text(
-                                to_json_string(&ServerWire::<ProcessSnapshot>::Pong)?.into(),
-                            ))
-                            .await?;
-                        }
-                        _ => {}
-                    }
-                }
-            }
-
-            msg = broadcast_rx.recv() => {
-                match msg {
-                    Ok(m) => sink.send(Message::Text(m.into())).await?,
-                    Err(broadcast::error::RecvError::Lagged(n)) => {
-                        tracing::warn!("{} missed {n} snapshot(s)", session.name);
-                    }
-                    Err(broadcast::error::RecvError::Closed) => break,
-                }
-            }
+        Listen::Http(addr) => {
+            let listener = tokio::net::TcpListener::bind(addr).await?;
+            tracing::info!("Listening on http://{addr}");
+            axum::serve(listener, http_router(server)).await?;
+        }
+        Listen::Unix(path) => {
+            // A stale socket from an earlier run would make bind fail.
+            let _ = std::fs::remove_file(&path);
+            let listener = tokio::net::UnixListener::bind(&path)?;
+            tracing::info!("Listening on unix:{}", path.display());
+            server.run(listener).await?;
         }
     }
-
-    {
-        let mut s = state.write().await;
-        s.authority.on_disconnect(&session);
-    }
-
-    tracing::debug!("Connection closed: {addr}");
     Ok(())
 }
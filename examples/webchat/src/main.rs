@@ -6,7 +6,6 @@
 //!   cargo run -p interconnect-example-webchat
 //!   Then open http://localhost:3030 in a browser.
 
-use anyhow::Context;
 use axum::{
     Router,
     extract::{
@@ -16,12 +15,9 @@ use axum::{
     response::{Html, IntoResponse, Response},
     routing::{any, get},
 };
-use futures_util::{SinkExt, StreamExt};
-use interconnect_core::{Identity, Manifest, from_json_str, to_json_string};
+use interconnect_core::{Identity, ImportResult, Manifest, Session, SimpleAuthority, Transport};
+use interconnect_server::{Server, ServerOptions};
 use serde::{Deserialize, Serialize};
-use std::sync::Arc;
-use std::sync::atomic::{AtomicU64, Ordering};
-use tokio::sync::{Mutex, broadcast};
 
 // ── Protocol types ────────────────────────────────────────────────────────────
 
@@ -43,7 +39,7 @@ pub struct ChatSnapshot {
 
 /// Intents a client may send.
 #[derive(Debug, Clone, Serialize, Deserialize)]
-#[serde(tag = "type", rename_all = "snake_case")]
+#[serde(tag = "action", rename_all = "snake_case")]
 pub enum ChatIntent {
     SendMessage { text: String },
 }
@@ -54,9 +50,15 @@ struct RoomState {
     messages: Vec<ChatMessage>,
 }
 
+#[derive(Debug, thiserror::Error)]
+#[error("webchat rooms do not accept transfers")]
+struct NoTransfers;
+
 impl RoomState {
     fn new() -> Self {
-        Self { messages: Vec::new() }
+        Self {
+            messages: Vec::new(),
+        }
     }
 
     fn add_message(&mut self, author: String, text: String) {
@@ -64,56 +66,58 @@ impl RoomState {
             .duration_since(std::time::UNIX_EPOCH)
             .unwrap_or_default()
             .as_millis() as u64;
-        self.messages.push(ChatMessage { author, text, timestamp });
+        self.messages.push(ChatMessage {
+            author,
+            text,
+            timestamp,
+        });
         if self.messages.len() > 100 {
             self.messages.remove(0);
         }
     }
+}
 
-    fn snapshot(&self) -> ChatSnapshot {
-        ChatSnapshot { messages: self.messages.clone() }
+impl SimpleAuthority for RoomState {
+    type Intent = ChatIntent;
+    type Snapshot = ChatSnapshot;
+    type Passport = ();
+    type Error = NoTransfers;
+
+    fn on_connect(&mut self, session: &Session) -> Result<(), NoTransfers> {
+        tracing::info!("Client authenticated: {}", session.name);
+        Ok(())
     }
-}
 
-// ── Shared application state ──────────────────────────────────────────────────
+    fn on_transfer_in(
+        &mut self,
+        _session: &Session,
+        _passport: (),
+    ) -> Result<ImportResult<()>, NoTransfers> {
+        Err(NoTransfers)
+    }
 
-#[derive(Clone)]
-struct AppState {
-    room: Arc<Mutex<RoomState>>,
-    manifest: Manifest,
-    broadcast_tx: broadcast::Sender<String>,
-    seq: Arc<AtomicU64>,
-}
+    fn on_disconnect(&mut self, session: &Session) {
+        tracing::info!("Client disconnected: {}", session.name);
+    }
 
-impl AppState {
-    fn new() -> Self {
-        let (broadcast_tx, _) = broadcast::channel(256);
-        let identity = Identity::local("webchat");
-        let manifest = Manifest {
-            identity,
-            name: "WebChat Room".to_string(),
-            substrate: None,
-            metadata: serde_json::json!({ "type": "webchat" }),
-        };
-        Self {
-            room: Arc::new(Mutex::new(RoomState::new())),
-            manifest,
-            broadcast_tx,
-            seq: Arc::new(AtomicU64::new(1)),
+    fn handle_intent(&mut self, session: &Session, intent: ChatIntent) -> Result<(), NoTransfers> {
+        match intent {
+            ChatIntent::SendMessage { text } => self.add_message(session.name.clone(), text),
         }
+        Ok(())
     }
 
-    async fn broadcast_snapshot(&self) {
-        let room = self.room.lock().await;
-        let seq = self.seq.fetch_add(1, Ordering::SeqCst);
-        let wire = interconnect_core::ServerWire::<ChatSnapshot>::Snapshot {
-            seq,
-            data: room.snapshot(),
-        };
-        if let Ok(json) = to_json_string(&wire) {
-            let _ = self.broadcast_tx.send(json);
+    fn snapshot(&self) -> ChatSnapshot {
+        ChatSnapshot {
+            messages: self.messages.clone(),
         }
     }
+
+    fn emit_passport(&self, _session: &Session) {}
+
+    fn validate_destination(&self, _destination: &str) -> bool {
+        false
+    }
 }
 
 // ── HTTP handlers ─────────────────────────────────────────────────────────────
@@ -122,121 +126,41 @@ async fn index_handler() -> impl IntoResponse {
     Html(include_str!("index.html"))
 }
 
-async fn ws_handler(ws: WebSocketUpgrade, State(state): State<AppState>) -> Response {
-    ws.on_upgrade(|socket| handle_ws(socket, state))
+async fn ws_handler(ws: WebSocketUpgrade, State(server): State<Server<RoomState>>) -> Response {
+    ws.on_upgrade(|socket| async move {
+        if let Err(e) = server.serve(AxumTransport(socket)).await {
+            tracing::warn!("Connection error: {e}");
+        }
+    })
 }
 
 // ── WebSocket / Interconnect room ─────────────────────────────────────────────
 
-async fn handle_ws(socket: WebSocket, state: AppState) {
-    if let Err(e) = run_connection(socket, state).await {
-        tracing::warn!("Connection error: {e}");
-    }
-}
-
-async fn run_connection(mut socket: WebSocket, state: AppState) -> anyhow::Result<()> {
-    use interconnect_core::ClientWire;
-
-    // ── Auth handshake ────────────────────────────────────────────────────────
-    let client_name: String = loop {
-        let msg = socket
-            .recv()
-            .await
-            .context("Connection closed during auth")??;
-
-        if let Message::Text(text) = msg {
-            let wire: ClientWire<ChatIntent> = match from_json_str(text.as_str()) {
-                Ok(w) => w,
-                Err(e) => {
-                    tracing::warn!("Auth parse error: {e}");
-                    continue;
-                }
-            };
-            if let ClientWire::Auth { identity, name, .. } = wire {
-                let resolved = name.unwrap_or_else(|| identity.payload().to_string());
-                tracing::info!("Client authenticated: {resolved}");
-                break resolved;
-            }
-        }
-    };
+/// An axum WebSocket as an Interconnect transport. The session itself is run
+/// by `interconnect-server`.
+struct AxumTransport(WebSocket);
 
-    // ── Send manifest ─────────────────────────────────────────────────────────
-    {
-        let wire = interconnect_core::ServerWire::<ChatSnapshot>::Manifest(state.manifest.clone());
-        let json = to_json_string(&wire)?;
-        socket.send(Message::Text(json.into())).await?;
-    }
+impl Transport for AxumTransport {
+    type Error = axum::Error;
 
-    // ── Send initial snapshot ─────────────────────────────────────────────────
-    {
-        let room = state.room.lock().await;
-        let wire = interconnect_core::ServerWire::<ChatSnapshot>::Snapshot {
-            seq: 0,
-            data: room.snapshot(),
+    async fn send(&mut self, data: &[u8]) -> Result<(), axum::Error> {
+        let msg = match std::str::from_utf8(data) {
+            Ok(text) => Message::Text(text.into()),
+            Err(_) => Message::Binary(data.to_vec().into()),
         };
-        let json = to_json_string(&wire)?;
-        drop(room);
-        socket.send(Message::Text(json.into())).await?;
+        self.0.send(msg).await
     }
 
-    // ── Main loop: receive intents and broadcast updates ──────────────────────
-    let mut broadcast_rx = state.broadcast_tx.subscribe();
-    let (mut sink, mut stream) = socket.split();
-
-    loop {
-        tokio::select! {
-            msg = stream.next() => {
-                let msg = match msg {
-                    Some(Ok(m)) => m,
-                    Some(Err(e)) => {
-                        tracing::debug!("WebSocket error: {e}");
-                        break;
-                    }
-                    None => break,
-                };
-
-                if let Message::Text(text) = msg {
-                    let wire: ClientWire<ChatIntent> = match from_json_str(text.as_str()) {
-                        Ok(w) => w,
-                        Err(e) => {
-                            tracing::warn!("Invalid message from {client_name}: {e}");
-                            continue;
-                        }
-                    };
-
-                    match wire {
-                        ClientWire::Intent(ChatIntent::SendMessage { text }) => {
-                            {
-                                let mut room = state.room.lock().await;
-                                room.add_message(client_name.clone(), text);
-                            }
-                            state.broadcast_snapshot().await;
-                        }
-                        ClientWire::Ping => {
-                            let pong = to_json_string(
-                                &interconnect_core::ServerWire::<ChatSnapshot>::Pong
-                            )?;
-                            sink.send(Message::Text(pong.into())).await?;
-                        }
-                        _ => {}
-                    }
-                }
-            }
-
-            broadcast_msg = broadcast_rx.recv() => {
-                match broadcast_msg {
-                    Ok(json) => sink.send(Message::Text(json.into())).await?,
-                    Err(broadcast::error::RecvError::Lagged(n)) => {
-                        tracing::warn!("{client_name} missed {n} snapshot(s)");
-                    }
-                    Err(broadcast::error::RecvError::Closed) => break,
-                }
+    async fn recv(&mut self) -> Result<Option<Vec<u8>>, axum::Error> {
+        loop {
+            match self.0.recv().await.transpose()? {
+                None | Some(Message::Close(_)) => return Ok(None),
+                Some(Message::Text(text)) => return Ok(Some(text.as_bytes().to_vec())),
+                Some(Message::Binary(bytes)) => return Ok(Some(bytes.into())),
+                Some(_) => continue,
             }
         }
     }
-
-    tracing::info!("Client disconnected: {client_name}");
-    Ok(())
 }
 
 // ── Entry point ───────────────────────────────────────────────────────────────
@@ -251,12 +175,14 @@ async fn main() {
         )
         .init();
 
-    let state = AppState::new();
+    let manifest = Manifest::new(Identity::local("webchat"), "WebChat Room")
+        .metadata(serde_json::json!({ "type": "webchat" }));
+    let server = Server::new(RoomState::new(), manifest, ServerOptions::default());
 
     let app = Router::new()
         .route("/", get(index_handler))
         .route("/ws", any(ws_handler))
-        .with_state(state);
+        .with_state(server);
 
     let addr = "127.0.0.1:3030";
     let listener = tokio::net::TcpListener::bind(addr).await.unwrap();